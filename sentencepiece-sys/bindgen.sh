@@ -5,4 +5,11 @@ if ! which bindgen &> /dev/null; then
   exit 1
 fi
 
-bindgen -o src/bindings.rs src/ffi/sentencepiece.h
+# Only generate bindings for the wrapper, so that the bindings do not
+# contain definitions that are specific to the platform or word size.
+bindgen -o src/bindings.rs \
+  --allowlist-function 'spp_.*' \
+  --allowlist-function 'spt_.*' \
+  --allowlist-type 'SentencePiece.*' \
+  --allowlist-var 'SPP_.*' \
+  src/ffi/sentencepiece.h
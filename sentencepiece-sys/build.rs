@@ -28,8 +28,15 @@ fn build_sentencepiece(builder: &mut Build) {
         "cargo:rustc-link-search=native={}",
         dst.join("lib").display()
     );
+    // The trainer library depends on the sentencepiece library.
+    println!("cargo:rustc-link-lib=static=sentencepiece_train");
     println!("cargo:rustc-link-lib=static=sentencepiece");
 
+    // Metadata for dependent crates (DEP_SENTENCEPIECE_INCLUDE and
+    // DEP_SENTENCEPIECE_LIB), so that they can reuse this build.
+    println!("cargo:include={}", dst.join("include").display());
+    println!("cargo:lib={}", dst.join("lib").display());
+
     builder.include("source/src");
 }
 
@@ -44,6 +51,15 @@ fn find_sentencepiece(builder: &mut Build) -> bool {
         builder.include(i);
     }
 
+    // Metadata for dependent crates (DEP_SENTENCEPIECE_INCLUDE and
+    // DEP_SENTENCEPIECE_LIB).
+    if let Ok(include) = env::join_paths(&lib.include_paths) {
+        println!("cargo:include={}", include.to_string_lossy());
+    }
+    if let Ok(link) = env::join_paths(&lib.link_paths) {
+        println!("cargo:lib={}", link.to_string_lossy());
+    }
+
     true
 }
 
@@ -56,7 +72,10 @@ fn main() {
         build_sentencepiece(&mut builder);
     }
 
-    builder.file("src/ffi/sentencepiece.cpp").cpp(true);
+    builder
+        .file("src/ffi/sentencepiece.cpp")
+        .file("src/ffi/sentencepiece_trainer.cpp")
+        .cpp(true);
 
     if builder.get_compiler().is_like_msvc() {
         builder.flag("/std:c++17");
@@ -68,9 +87,19 @@ fn main() {
         builder.flag("/utf-8");
     } else {
         builder.flag("-std=c++17");
+        // The shim uses threads for batch encoding.
+        builder.flag("-pthread");
     }
 
     builder.compile("sentencepiece_wrap");
 
+    if env::var("CARGO_CFG_TARGET_FAMILY").as_deref() == Ok("unix") {
+        println!("cargo:rustc-link-lib=pthread");
+    }
+
     println!("cargo:rerun-if-changed=src/ffi/sentencepiece.cpp");
+    println!("cargo:rerun-if-changed=src/ffi/sentencepiece_trainer.cpp");
+    println!("cargo:rerun-if-changed=src/ffi/status.h");
+    println!("cargo:rerun-if-changed=src/ffi/types.h");
+    println!("cargo:rerun-if-changed=src/ffi/util.h");
 }
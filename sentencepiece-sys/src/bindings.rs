@@ -1,61 +1,20 @@
 /* automatically generated by rust-bindgen 0.66.1 */
 
-pub const __bool_true_false_are_defined: u32 = 1;
-pub const true_: u32 = 1;
-pub const false_: u32 = 0;
-pub const _VCRT_COMPILER_PREPROCESSOR: u32 = 1;
-pub const _SAL_VERSION: u32 = 20;
-pub const __SAL_H_VERSION: u32 = 180000000;
-pub const _USE_DECLSPECS_FOR_SAL: u32 = 0;
-pub const _USE_ATTRIBUTES_FOR_SAL: u32 = 0;
-pub const _CRT_PACKING: u32 = 8;
-pub const _HAS_EXCEPTIONS: u32 = 1;
-pub const _STL_LANG: u32 = 0;
-pub const _HAS_CXX17: u32 = 0;
-pub const _HAS_CXX20: u32 = 0;
-pub const _HAS_CXX23: u32 = 0;
-pub const _HAS_NODISCARD: u32 = 0;
-pub const WCHAR_MIN: u32 = 0;
-pub const WCHAR_MAX: u32 = 65535;
-pub const WINT_MIN: u32 = 0;
-pub const WINT_MAX: u32 = 65535;
-pub type wchar_t = ::std::os::raw::c_ushort;
-pub type max_align_t = f64;
-pub type va_list = *mut ::std::os::raw::c_char;
-extern "C" {
-    pub fn __va_start(arg1: *mut *mut ::std::os::raw::c_char, ...);
-}
-pub type __vcrt_bool = bool;
-extern "C" {
-    pub fn __security_init_cookie();
-}
-extern "C" {
-    pub fn __security_check_cookie(_StackCookie: usize);
-}
-extern "C" {
-    pub fn __report_gsfailure(_StackCookie: usize) -> !;
-}
-extern "C" {
-    pub static mut __security_cookie: usize;
-}
-pub type int_least8_t = ::std::os::raw::c_schar;
-pub type int_least16_t = ::std::os::raw::c_short;
-pub type int_least32_t = ::std::os::raw::c_int;
-pub type int_least64_t = ::std::os::raw::c_longlong;
-pub type uint_least8_t = ::std::os::raw::c_uchar;
-pub type uint_least16_t = ::std::os::raw::c_ushort;
-pub type uint_least32_t = ::std::os::raw::c_uint;
-pub type uint_least64_t = ::std::os::raw::c_ulonglong;
-pub type int_fast8_t = ::std::os::raw::c_schar;
-pub type int_fast16_t = ::std::os::raw::c_int;
-pub type int_fast32_t = ::std::os::raw::c_int;
-pub type int_fast64_t = ::std::os::raw::c_longlong;
-pub type uint_fast8_t = ::std::os::raw::c_uchar;
-pub type uint_fast16_t = ::std::os::raw::c_uint;
-pub type uint_fast32_t = ::std::os::raw::c_uint;
-pub type uint_fast64_t = ::std::os::raw::c_ulonglong;
-pub type intmax_t = ::std::os::raw::c_longlong;
-pub type uintmax_t = ::std::os::raw::c_ulonglong;
+pub const SPP_EXCEPTION: i32 = -1;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SentencePieceWithId {
+    pub id: u32,
+    pub begin: u32,
+    pub end: u32,
+    pub piece_len: usize,
+}
+pub type spp_reserve_buffer = ::std::option::Option<
+    unsafe extern "C" fn(
+        buffer: *mut ::std::os::raw::c_void,
+        size: usize,
+    ) -> *mut ::std::os::raw::c_void,
+>;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct SentencePieceProcessor {
@@ -66,6 +25,12 @@ pub struct SentencePieceProcessor {
 pub struct SentencePieceText {
     _unused: [u8; 0],
 }
+extern "C" {
+    pub fn spp_exception_message(len: *mut usize) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn spp_status_message(len: *mut usize) -> *const ::std::os::raw::c_char;
+}
 extern "C" {
     pub fn spp_decode_piece_ids(
         spp: *mut SentencePieceProcessor,
@@ -75,6 +40,16 @@ extern "C" {
         decoded_len: *mut usize,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn spp_decode_piece_ids_into(
+        spp: *mut SentencePieceProcessor,
+        pieces: *const u32,
+        pieces_len: usize,
+        reserve: spp_reserve_buffer,
+        decoded: *mut ::std::os::raw::c_void,
+        decoded_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn spp_decode_pieces(
         spp: *mut SentencePieceProcessor,
@@ -84,6 +59,66 @@ extern "C" {
         decoded_len: *mut usize,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn spp_decode_piece_ids_batch(
+        spp: *mut SentencePieceProcessor,
+        pieces: *const u32,
+        pieces_lens: *const usize,
+        n_sequences: usize,
+        decoded: *mut *mut ::std::os::raw::c_uchar,
+        decoded_lens: *mut *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_decode_piece_ids_as_serialized_proto(
+        spp: *mut SentencePieceProcessor,
+        pieces: *const u32,
+        pieces_len: usize,
+        len: *mut usize,
+    ) -> *mut ::std::os::raw::c_uchar;
+}
+extern "C" {
+    pub fn spp_encode(
+        spp: *mut SentencePieceProcessor,
+        sentence: *const ::std::os::raw::c_char,
+        sentence_len: usize,
+        reserve: spp_reserve_buffer,
+        pieces: *mut ::std::os::raw::c_void,
+        n_pieces: *mut usize,
+        piece_data: *mut ::std::os::raw::c_void,
+        piece_data_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_encode_as_ids(
+        spp: *mut SentencePieceProcessor,
+        sentence: *const ::std::os::raw::c_char,
+        sentence_len: usize,
+        ids: *mut *mut u32,
+        ids_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_encode_as_ids_batch(
+        spp: *mut SentencePieceProcessor,
+        sentences: *const ::std::os::raw::c_char,
+        sentence_lens: *const usize,
+        n_sentences: usize,
+        n_threads: usize,
+        ids: *mut *mut u32,
+        ids_lens: *mut *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_encode_as_pieces(
+        spp: *mut SentencePieceProcessor,
+        sentence: *const ::std::os::raw::c_char,
+        sentence_len: usize,
+        pieces: *mut *mut ::std::os::raw::c_uchar,
+        piece_lens: *mut *mut usize,
+        n_pieces: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn spp_encode_as_serialized_proto(
         spp: *mut SentencePieceProcessor,
@@ -92,6 +127,58 @@ extern "C" {
         len: *mut usize,
     ) -> *mut ::std::os::raw::c_uchar;
 }
+extern "C" {
+    pub fn spp_encode_as_serialized_proto_into(
+        spp: *mut SentencePieceProcessor,
+        sentence: *const ::std::os::raw::c_char,
+        sentence_len: usize,
+        reserve: spp_reserve_buffer,
+        serialized: *mut ::std::os::raw::c_void,
+        serialized_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_nbest_encode_as_serialized_proto(
+        spp: *mut SentencePieceProcessor,
+        sentence: *const ::std::os::raw::c_char,
+        sentence_len: usize,
+        nbest: usize,
+        serialized: *mut *mut ::std::os::raw::c_uchar,
+        serialized_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_normalize(
+        spp: *mut SentencePieceProcessor,
+        sentence: *const ::std::os::raw::c_char,
+        sentence_len: usize,
+        normalized: *mut *mut ::std::os::raw::c_uchar,
+        normalized_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_normalize_with_offsets(
+        spp: *mut SentencePieceProcessor,
+        sentence: *const ::std::os::raw::c_char,
+        sentence_len: usize,
+        normalized: *mut *mut ::std::os::raw::c_uchar,
+        normalized_len: *mut usize,
+        norm_to_orig: *mut *mut usize,
+        norm_to_orig_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_sample_encode_and_score_as_serialized_proto(
+        spp: *mut SentencePieceProcessor,
+        sentence: *const ::std::os::raw::c_char,
+        sentence_len: usize,
+        len: *mut usize,
+        num_samples: usize,
+        alpha: f32,
+        wor: bool,
+        include_best: bool,
+    ) -> *mut ::std::os::raw::c_uchar;
+}
 extern "C" {
     pub fn spp_sample_encode_as_serialized_proto(
         spp: *mut SentencePieceProcessor,
@@ -102,9 +189,44 @@ extern "C" {
         alpha: f32,
     ) -> *mut ::std::os::raw::c_uchar;
 }
+extern "C" {
+    pub fn spp_set_decode_extra_options(
+        spp: *mut SentencePieceProcessor,
+        options: *const ::std::os::raw::c_char,
+        options_len: usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_set_encode_extra_options(
+        spp: *mut SentencePieceProcessor,
+        options: *const ::std::os::raw::c_char,
+        options_len: usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_set_vocabulary(
+        spp: *mut SentencePieceProcessor,
+        pieces: *const ::std::os::raw::c_char,
+        piece_lens: *const usize,
+        n_pieces: usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_reset_vocabulary(spp: *mut SentencePieceProcessor) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spp_load_vocabulary(
+        spp: *mut SentencePieceProcessor,
+        filename: *const ::std::os::raw::c_char,
+        threshold: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn spp_new() -> *mut SentencePieceProcessor;
 }
+extern "C" {
+    pub fn spp_set_random_generator_seed(seed: ::std::os::raw::c_uint);
+}
 extern "C" {
     pub fn spp_from_serialized_proto(
         spp: *mut SentencePieceProcessor,
@@ -127,15 +249,37 @@ extern "C" {
 extern "C" {
     pub fn spp_free(spp: *mut SentencePieceProcessor);
 }
+extern "C" {
+    pub fn spp_free_buffer(buffer: *mut ::std::os::raw::c_void);
+}
 extern "C" {
     pub fn spp_bos_id(spp: *mut SentencePieceProcessor) -> ::std::os::raw::c_int;
 }
 extern "C" {
     pub fn spp_eos_id(spp: *mut SentencePieceProcessor) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn spp_get_score(spp: *mut SentencePieceProcessor, id: ::std::os::raw::c_int) -> f32;
+}
+extern "C" {
+    pub fn spp_id_to_piece(
+        spp: *mut SentencePieceProcessor,
+        id: ::std::os::raw::c_int,
+        len: *mut usize,
+    ) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn spp_is_byte(spp: *mut SentencePieceProcessor, id: ::std::os::raw::c_int) -> bool;
+}
+extern "C" {
+    pub fn spp_is_control(spp: *mut SentencePieceProcessor, id: ::std::os::raw::c_int) -> bool;
+}
 extern "C" {
     pub fn spp_is_unknown(spp: *mut SentencePieceProcessor, id: ::std::os::raw::c_int) -> bool;
 }
+extern "C" {
+    pub fn spp_is_unused(spp: *mut SentencePieceProcessor, id: ::std::os::raw::c_int) -> bool;
+}
 extern "C" {
     pub fn spp_pad_id(spp: *mut SentencePieceProcessor) -> ::std::os::raw::c_int;
 }
@@ -145,9 +289,55 @@ extern "C" {
         piece: *const ::std::os::raw::c_char,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn spp_pieces_to_ids(
+        spp: *mut SentencePieceProcessor,
+        pieces: *const ::std::os::raw::c_char,
+        piece_lens: *const usize,
+        n_pieces: usize,
+        ids: *mut u32,
+    ) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn spp_piece_size(spp: *mut SentencePieceProcessor) -> ::std::os::raw::c_int;
 }
 extern "C" {
     pub fn spp_unk_id(spp: *mut SentencePieceProcessor) -> ::std::os::raw::c_int;
 }
+pub type spt_next_sentence = ::std::option::Option<
+    unsafe extern "C" fn(
+        ctx: *mut ::std::os::raw::c_void,
+        sentence: *mut *const ::std::os::raw::c_char,
+        sentence_len: *mut usize,
+    ) -> bool,
+>;
+pub type spt_log_line = ::std::option::Option<
+    unsafe extern "C" fn(line: *const ::std::os::raw::c_char, line_len: usize),
+>;
+extern "C" {
+    pub fn spt_train(
+        args: *const ::std::os::raw::c_char,
+        args_len: usize,
+        log: spt_log_line,
+        model: *mut *mut ::std::os::raw::c_uchar,
+        model_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spt_train_from_iterator(
+        args: *const ::std::os::raw::c_char,
+        args_len: usize,
+        next: spt_next_sentence,
+        ctx: *mut ::std::os::raw::c_void,
+        log: spt_log_line,
+        model: *mut *mut ::std::os::raw::c_uchar,
+        model_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn spt_compile_normalization_rules(
+        filename: *const ::std::os::raw::c_char,
+        model: *mut *mut ::std::os::raw::c_uchar,
+        model_len: *mut usize,
+    ) -> ::std::os::raw::c_int;
+}
@@ -1,34 +1,181 @@
+#include <algorithm>
+#include <condition_variable>
 #include <cstdint>
 #include <cstdlib>
 #include <cstring>
-#include <type_traits>
+#include <deque>
+#include <exception>
+#include <functional>
+#include <limits>
+#include <mutex>
+#include <string>
+#include <thread>
 #include <vector>
 
 #include <sentencepiece_processor.h>
 
+#include "types.h"
+#include "util.h"
+
 using absl::string_view;
 using sentencepiece::SentencePieceProcessor;
 using sentencepiece::SentencePieceText;
 
-// Inspired by:
-// https://stackoverflow.com/a/14589519
-template<typename E>
-constexpr auto to_underlying_type(E e) -> typename std::underlying_type<E>::type 
-{
-   return static_cast<typename std::underlying_type<E>::type>(e);
+namespace {
+
+// Pool of worker threads that is shared by batch calls.
+class ThreadPool {
+ public:
+  explicit ThreadPool(size_t n_threads) {
+    // Joinable threads must not be destroyed, stop the threads that
+    // were started when starting a thread fails.
+    try {
+      workers_.reserve(n_threads);
+      for (size_t i = 0; i < n_threads; ++i) {
+        workers_.emplace_back([this] { Work(); });
+      }
+    } catch (...) {
+      Stop();
+      throw;
+    }
+  }
+
+  ~ThreadPool() {
+    Stop();
+  }
+
+  size_t size() const {
+    return workers_.size();
+  }
+
+  // Tasks must not throw exceptions.
+  void Submit(std::function<void()> task) {
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      tasks_.push_back(std::move(task));
+    }
+    available_.notify_one();
+  }
+
+ private:
+  void Stop() {
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      stop_ = true;
+    }
+    available_.notify_all();
+    for (auto &worker : workers_) {
+      worker.join();
+    }
+  }
+
+  void Work() {
+    for (;;) {
+      std::function<void()> task;
+      {
+        std::unique_lock<std::mutex> lock(mutex_);
+        available_.wait(lock, [this] { return stop_ || !tasks_.empty(); });
+        if (tasks_.empty()) {
+          return;
+        }
+        task = std::move(tasks_.front());
+        tasks_.pop_front();
+      }
+      task();
+    }
+  }
+
+  std::vector<std::thread> workers_;
+  std::deque<std::function<void()>> tasks_;
+  std::mutex mutex_;
+  std::condition_variable available_;
+  bool stop_ = false;
+};
+
+// The pool has a worker for each core, except for the core of the
+// calling thread. The pool is never destroyed, so that the workers do
+// not have to be stopped during process exit.
+ThreadPool &thread_pool() {
+  static ThreadPool *pool = new ThreadPool(std::max(1u, std::thread::hardware_concurrency()) - 1);
+  return *pool;
+}
+
+// Group of tasks that waits for its tasks when it is destroyed, so that
+// tasks cannot outlive the data they refer to when the caller unwinds.
+class TaskGroup {
+ public:
+  ~TaskGroup() {
+    std::unique_lock<std::mutex> lock(mutex_);
+    done_.wait(lock, [this] { return pending_ == 0; });
+  }
+
+  void Add() {
+    std::lock_guard<std::mutex> lock(mutex_);
+    ++pending_;
+  }
+
+  void Done() {
+    // Notify while holding the lock, the group can be destroyed as soon
+    // as the lock is released.
+    std::lock_guard<std::mutex> lock(mutex_);
+    if (--pending_ == 0) {
+      done_.notify_all();
+    }
+  }
+
+ private:
+  std::mutex mutex_;
+  std::condition_variable done_;
+  size_t pending_ = 0;
+};
+
+}  // namespace
+
+std::string &exception_message() {
+  thread_local std::string message;
+  return message;
+}
+
+std::string &status_message() {
+  thread_local std::string message;
+  return message;
 }
 
 extern "C" {
 
+char const *spp_exception_message(size_t *len) {
+  auto const &message = exception_message();
+  *len = message.size();
+  return message.data();
+}
+
+char const *spp_status_message(size_t *len) {
+  auto const &message = status_message();
+  *len = message.size();
+  return message.data();
+}
+
 SentencePieceProcessor *spp_new() {
-  return new SentencePieceProcessor();
+  return catch_exceptions<SentencePieceProcessor *>(nullptr, [] {
+    return new SentencePieceProcessor();
+  });
+}
+
+void spp_set_random_generator_seed(unsigned int seed) {
+  catch_exceptions(SPP_EXCEPTION, [&] {
+    sentencepiece::SetRandomGeneratorSeed(seed);
+    return 0;
+  });
 }
 
 int spp_bos_id(SentencePieceProcessor *spp) {
-  return spp->bos_id();
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    return spp->bos_id();
+  });
 }
 
 int spp_decode_piece_ids(SentencePieceProcessor *spp, uint32_t const *pieces, size_t pieces_len, unsigned char **decoded, size_t *decoded_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
     std::vector<int> int_pieces;
     int_pieces.reserve(pieces_len);
 
@@ -40,16 +187,32 @@ int spp_decode_piece_ids(SentencePieceProcessor *spp, uint32_t const *pieces, si
     auto status = spp->Decode(int_pieces, &decoded_string);
 
     *decoded_len = decoded_string.size();
-    *decoded = static_cast<unsigned char *>(malloc(decoded_string.size()));
+    *decoded = static_cast<unsigned char *>(checked_malloc(decoded_string.size()));
     memcpy(*decoded, decoded_string.data(), decoded_string.size());
 
-    return to_underlying_type(status.code());
+    return status_code(status);
+  });
+}
+
+int spp_decode_piece_ids_into(SentencePieceProcessor *spp, uint32_t const *pieces, size_t pieces_len, spp_reserve_buffer reserve, void *decoded, size_t *decoded_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    std::vector<int> int_pieces(pieces, pieces + pieces_len);
+
+    std::string decoded_string;
+    auto status = spp->Decode(int_pieces, &decoded_string);
+
+    *decoded_len = decoded_string.size();
+    memcpy(reserve_or_throw(reserve, decoded, decoded_string.size()), decoded_string.data(), decoded_string.size());
+
+    return status_code(status);
+  });
 }
 
 int spp_decode_pieces(SentencePieceProcessor *spp, char const * const *pieces, size_t pieces_len, unsigned char **decoded, size_t *decoded_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
     std::vector<absl::string_view> str_pieces;
     str_pieces.reserve(pieces_len);
-  
+
     for (char const * const *piece = pieces; piece != pieces + pieces_len; ++piece) {
         str_pieces.push_back(*piece);
     }
@@ -58,81 +221,486 @@ int spp_decode_pieces(SentencePieceProcessor *spp, char const * const *pieces, s
     auto status = spp->Decode(str_pieces, &decoded_string);
 
     *decoded_len = decoded_string.size();
-    *decoded = static_cast<unsigned char *>(malloc(decoded_string.size()));
+    *decoded = static_cast<unsigned char *>(checked_malloc(decoded_string.size()));
     memcpy(*decoded, decoded_string.data(), decoded_string.size());
 
-    return to_underlying_type(status.code());
+    return status_code(status);
+  });
+}
+
+int spp_decode_piece_ids_batch(SentencePieceProcessor *spp, uint32_t const *pieces, size_t const *pieces_lens, size_t n_sequences, unsigned char **decoded, size_t **decoded_lens) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    std::vector<std::string> decoded_strings;
+    decoded_strings.reserve(n_sequences);
+
+    uint32_t const *sequence = pieces;
+    for (size_t i = 0; i < n_sequences; ++i) {
+      std::vector<int> int_pieces(sequence, sequence + pieces_lens[i]);
+      sequence += pieces_lens[i];
+
+      std::string decoded_string;
+      auto status = spp->Decode(int_pieces, &decoded_string);
+      if (!status.ok()) {
+        return status_code(status);
+      }
+
+      decoded_strings.push_back(std::move(decoded_string));
+    }
+
+    size_t total_len = 0;
+    for (auto const &decoded_string : decoded_strings) {
+      total_len += decoded_string.size();
+    }
+
+    *decoded = static_cast<unsigned char *>(checked_malloc(total_len));
+    *decoded_lens = static_cast<size_t *>(checked_malloc(n_sequences * sizeof(size_t)));
+
+    unsigned char *data = *decoded;
+    for (size_t i = 0; i < decoded_strings.size(); ++i) {
+      memcpy(data, decoded_strings[i].data(), decoded_strings[i].size());
+      data += decoded_strings[i].size();
+      (*decoded_lens)[i] = decoded_strings[i].size();
+    }
+
+    return to_underlying_type(sentencepiece::util::StatusCode::kOk);
+  });
+}
+
+unsigned char *spp_decode_piece_ids_as_serialized_proto(SentencePieceProcessor *spp, uint32_t const *pieces, size_t pieces_len, size_t *len) {
+  return catch_exceptions<unsigned char *>(nullptr, [&] {
+    std::vector<int> int_pieces;
+    int_pieces.reserve(pieces_len);
+
+    for (uint32_t const *piece = pieces; piece != pieces + pieces_len; ++piece) {
+        int_pieces.push_back(static_cast<int>(*piece));
+    }
+
+    auto serialized = spp->DecodeIdsAsSerializedProto(int_pieces);
+
+    *len = serialized.size();
+    unsigned char *data = (unsigned char *) checked_malloc(serialized.size());
+    memcpy(data, serialized.data(), serialized.size());
+
+    return data;
+  });
+}
+
+int spp_encode(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, spp_reserve_buffer reserve, void *pieces, size_t *n_pieces, void *piece_data, size_t *piece_data_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    sentencepiece::ImmutableSentencePieceText spt;
+    auto status = spp->Encode(absl::string_view(sentence, sentence_len), &spt);
+
+    size_t data_len = 0;
+    for (size_t i = 0; i < spt.pieces_size(); ++i) {
+      data_len += spt.pieces(static_cast<int>(i)).piece().size();
+    }
+
+    *n_pieces = spt.pieces_size();
+    // The buffers are not necessarily aligned, so the pieces are copied.
+    auto pieces_data = reserve_or_throw(reserve, pieces, spt.pieces_size() * sizeof(SentencePieceWithId));
+    *piece_data_len = data_len;
+    auto data = reserve_or_throw(reserve, piece_data, data_len);
+
+    for (size_t i = 0; i < spt.pieces_size(); ++i) {
+      auto piece = spt.pieces(static_cast<int>(i));
+      SentencePieceWithId piece_with_id{piece.id(), piece.begin(), piece.end(), piece.piece().size()};
+      memcpy(pieces_data + i * sizeof(SentencePieceWithId), &piece_with_id, sizeof(SentencePieceWithId));
+      memcpy(data, piece.piece().data(), piece.piece().size());
+      data += piece.piece().size();
+    }
+
+    return status_code(status);
+  });
+}
+
+int spp_encode_as_ids(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, uint32_t **ids, size_t *ids_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    std::vector<int> int_ids;
+    auto status = spp->Encode(absl::string_view(sentence, sentence_len), &int_ids);
+
+    *ids_len = int_ids.size();
+    *ids = static_cast<uint32_t *>(checked_malloc(int_ids.size() * sizeof(uint32_t)));
+    for (size_t i = 0; i < int_ids.size(); ++i) {
+      (*ids)[i] = static_cast<uint32_t>(int_ids[i]);
+    }
+
+    return status_code(status);
+  });
+}
+
+int spp_encode_as_ids_batch(SentencePieceProcessor *spp, char const *sentences, size_t const *sentence_lens, size_t n_sentences, size_t n_threads, uint32_t **ids, size_t **ids_lens) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    std::vector<char const *> sentence_ptrs;
+    sentence_ptrs.reserve(n_sentences);
+    for (size_t i = 0; i < n_sentences; ++i) {
+      sentence_ptrs.push_back(sentences);
+      sentences += sentence_lens[i];
+    }
+
+    // The calling thread encodes sentences as well.
+    auto &pool = thread_pool();
+    size_t max_threads = pool.size() + 1;
+    if (n_threads == 0 || n_threads > max_threads) {
+      n_threads = max_threads;
+    }
+    n_threads = std::max<size_t>(1, std::min(n_threads, n_sentences));
+
+    std::vector<std::vector<int>> encoded(n_sentences);
+    std::vector<sentencepiece::util::Status> statuses(n_sentences);
+    std::vector<std::exception_ptr> exceptions(n_threads);
+
+    // Exceptions are passed to the calling thread, which rethrows them.
+    auto encode_sentences = [&](size_t thread) {
+      try {
+        for (size_t i = thread; i < n_sentences; i += n_threads) {
+          statuses[i] = spp->Encode(string_view(sentence_ptrs[i], sentence_lens[i]), &encoded[i]);
+        }
+      } catch (...) {
+        exceptions[thread] = std::current_exception();
+      }
+    };
+
+    {
+      TaskGroup tasks;
+      for (size_t thread = 1; thread < n_threads; ++thread) {
+        tasks.Add();
+        try {
+          pool.Submit([&, thread] {
+            encode_sentences(thread);
+            tasks.Done();
+          });
+        } catch (...) {
+          tasks.Done();
+          throw;
+        }
+      }
+      encode_sentences(0);
+    }
+
+    for (auto const &exception : exceptions) {
+      if (exception) {
+        std::rethrow_exception(exception);
+      }
+    }
+
+    for (auto const &status : statuses) {
+      if (!status.ok()) {
+        return status_code(status);
+      }
+    }
+
+    size_t total_len = 0;
+    for (auto const &sentence_ids : encoded) {
+      total_len += sentence_ids.size();
+    }
+
+    *ids = static_cast<uint32_t *>(checked_malloc(total_len * sizeof(uint32_t)));
+    *ids_lens = static_cast<size_t *>(checked_malloc(n_sentences * sizeof(size_t)));
+
+    uint32_t *data = *ids;
+    for (size_t i = 0; i < n_sentences; ++i) {
+      data = std::copy(encoded[i].begin(), encoded[i].end(), data);
+      (*ids_lens)[i] = encoded[i].size();
+    }
+
+    return to_underlying_type(sentencepiece::util::StatusCode::kOk);
+  });
+}
+
+int spp_encode_as_pieces(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, unsigned char **pieces, size_t **piece_lens, size_t *n_pieces) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    std::vector<std::string> str_pieces;
+    auto status = spp->Encode(absl::string_view(sentence, sentence_len), &str_pieces);
+
+    size_t total_len = 0;
+    for (auto const &piece : str_pieces) {
+      total_len += piece.size();
+    }
+
+    *n_pieces = str_pieces.size();
+    *pieces = static_cast<unsigned char *>(checked_malloc(total_len));
+    *piece_lens = static_cast<size_t *>(checked_malloc(str_pieces.size() * sizeof(size_t)));
+
+    unsigned char *data = *pieces;
+    for (size_t i = 0; i < str_pieces.size(); ++i) {
+      memcpy(data, str_pieces[i].data(), str_pieces[i].size());
+      data += str_pieces[i].size();
+      (*piece_lens)[i] = str_pieces[i].size();
+    }
+
+    return status_code(status);
+  });
 }
 
 unsigned char *spp_encode_as_serialized_proto(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, size_t *len) {
-  auto sentence_view = absl::string_view(sentence, sentence_len);
-  auto serialized = spp->EncodeAsSerializedProto(sentence_view);
+  return catch_exceptions<unsigned char *>(nullptr, [&] {
+    auto sentence_view = absl::string_view(sentence, sentence_len);
+    auto serialized = spp->EncodeAsSerializedProto(sentence_view);
 
-  *len = serialized.size();
-  unsigned char *data = (unsigned char *) malloc(serialized.size());
-  memcpy(data, serialized.data(), serialized.size());
+    *len = serialized.size();
+    unsigned char *data = (unsigned char *) checked_malloc(serialized.size());
+    memcpy(data, serialized.data(), serialized.size());
 
-  return data;
+    return data;
+  });
 }
 
 
+int spp_encode_as_serialized_proto_into(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, spp_reserve_buffer reserve, void *serialized, size_t *serialized_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto sentence_view = absl::string_view(sentence, sentence_len);
+
+    std::string serialized_string;
+    sentencepiece::ImmutableSentencePieceText spt;
+    auto status = spp->Encode(sentence_view, &spt);
+    if (status.ok()) {
+      serialized_string = spt.SerializeAsString();
+    }
+
+    *serialized_len = serialized_string.size();
+    memcpy(reserve_or_throw(reserve, serialized, serialized_string.size()), serialized_string.data(), serialized_string.size());
+
+    return status_code(status);
+  });
+}
+
+int spp_nbest_encode_as_serialized_proto(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, size_t nbest, unsigned char **serialized, size_t *serialized_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto sentence_view = absl::string_view(sentence, sentence_len);
+
+    std::string serialized_string;
+    sentencepiece::ImmutableNBestSentencePieceText nbest_spt;
+    auto status = spp->NBestEncode(sentence_view, static_cast<int>(nbest), &nbest_spt);
+    if (status.ok()) {
+      serialized_string = nbest_spt.SerializeAsString();
+    }
+
+    *serialized_len = serialized_string.size();
+    *serialized = static_cast<unsigned char *>(checked_malloc(serialized_string.size()));
+    memcpy(*serialized, serialized_string.data(), serialized_string.size());
+
+    return status_code(status);
+  });
+}
+
+int spp_normalize(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, unsigned char **normalized, size_t *normalized_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto sentence_view = absl::string_view(sentence, sentence_len);
+
+    std::string normalized_string;
+    auto status = spp->Normalize(sentence_view, &normalized_string);
+
+    *normalized_len = normalized_string.size();
+    *normalized = static_cast<unsigned char *>(checked_malloc(normalized_string.size()));
+    memcpy(*normalized, normalized_string.data(), normalized_string.size());
+
+    return status_code(status);
+  });
+}
+
+int spp_normalize_with_offsets(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, unsigned char **normalized, size_t *normalized_len, size_t **norm_to_orig, size_t *norm_to_orig_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto sentence_view = absl::string_view(sentence, sentence_len);
+
+    std::string normalized_string;
+    std::vector<size_t> offsets;
+    auto status = spp->Normalize(sentence_view, &normalized_string, &offsets);
+
+    *normalized_len = normalized_string.size();
+    *normalized = static_cast<unsigned char *>(checked_malloc(normalized_string.size()));
+    memcpy(*normalized, normalized_string.data(), normalized_string.size());
+
+    *norm_to_orig_len = offsets.size();
+    *norm_to_orig = static_cast<size_t *>(checked_malloc(offsets.size() * sizeof(size_t)));
+    memcpy(*norm_to_orig, offsets.data(), offsets.size() * sizeof(size_t));
+
+    return status_code(status);
+  });
+}
+
+unsigned char *spp_sample_encode_and_score_as_serialized_proto(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, size_t *len, size_t num_samples, float alpha, bool wor, bool include_best) {
+  return catch_exceptions<unsigned char *>(nullptr, [&] {
+    auto sentence_view = absl::string_view(sentence, sentence_len);
+    auto serialized = spp->SampleEncodeAndScoreAsSerializedProto(sentence_view, static_cast<int>(num_samples), alpha, wor, include_best);
+
+    *len = serialized.size();
+    unsigned char *data = (unsigned char *) checked_malloc(serialized.size());
+    memcpy(data, serialized.data(), serialized.size());
+
+    return data;
+  });
+}
+
 unsigned char *spp_sample_encode_as_serialized_proto(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, size_t *len, size_t nbest, float alpha) {
-  auto sentence_view = absl::string_view(sentence, sentence_len);
-  auto serialized = spp->SampleEncodeAsSerializedProto(sentence_view, static_cast<int>(nbest), alpha);
+  return catch_exceptions<unsigned char *>(nullptr, [&] {
+    auto sentence_view = absl::string_view(sentence, sentence_len);
+    auto serialized = spp->SampleEncodeAsSerializedProto(sentence_view, static_cast<int>(nbest), alpha);
+
+    *len = serialized.size();
+    unsigned char *data = (unsigned char *) checked_malloc(serialized.size());
+    memcpy(data, serialized.data(), serialized.size());
+
+    return data;
+  });
+}
+
+int spp_set_decode_extra_options(SentencePieceProcessor *spp, char const *options, size_t options_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto status = spp->SetDecodeExtraOptions(string_view(options, options_len));
+    return status_code(status);
+  });
+}
+
+int spp_set_encode_extra_options(SentencePieceProcessor *spp, char const *options, size_t options_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto status = spp->SetEncodeExtraOptions(string_view(options, options_len));
+    return status_code(status);
+  });
+}
+
+int spp_set_vocabulary(SentencePieceProcessor *spp, char const *pieces, size_t const *piece_lens, size_t n_pieces) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    std::vector<absl::string_view> valid_vocab;
+    valid_vocab.reserve(n_pieces);
+
+    char const *piece = pieces;
+    for (size_t i = 0; i < n_pieces; ++i) {
+      valid_vocab.emplace_back(piece, piece_lens[i]);
+      piece += piece_lens[i];
+    }
+
+    auto status = spp->SetVocabulary(valid_vocab);
+    return status_code(status);
+  });
+}
 
-  *len = serialized.size();
-  unsigned char *data = (unsigned char *) malloc(serialized.size());
-  memcpy(data, serialized.data(), serialized.size());
+int spp_reset_vocabulary(SentencePieceProcessor *spp) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto status = spp->ResetVocabulary();
+    return status_code(status);
+  });
+}
 
-  return data;
+int spp_load_vocabulary(SentencePieceProcessor *spp, char const *filename, int threshold) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto status = spp->LoadVocabulary(filename, threshold);
+    return status_code(status);
+  });
 }
 
 int spp_eos_id(SentencePieceProcessor *spp) {
-  return spp->eos_id();
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    return spp->eos_id();
+  });
+}
+
+float spp_get_score(SentencePieceProcessor *spp, int id) {
+  return catch_exceptions(std::numeric_limits<float>::quiet_NaN(), [&] {
+    return spp->GetScore(id);
+  });
+}
+
+char const *spp_id_to_piece(SentencePieceProcessor *spp, int id, size_t *len) {
+  *len = 0;
+  return catch_exceptions<char const *>(nullptr, [&] {
+    auto const &piece = spp->IdToPiece(id);
+    *len = piece.size();
+    return piece.data();
+  });
+}
+
+bool spp_is_byte(SentencePieceProcessor *spp, int id) {
+  return catch_exceptions(false, [&] {
+    return spp->IsByte(id);
+  });
+}
+
+bool spp_is_control(SentencePieceProcessor *spp, int id) {
+  return catch_exceptions(false, [&] {
+    return spp->IsControl(id);
+  });
 }
 
 int spp_load(SentencePieceProcessor *spp, char const *filename) {
-  auto status = spp->Load(filename);
-  return to_underlying_type(status.code());
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto status = spp->Load(filename);
+    return status_code(status);
+  });
 }
 
 bool spp_is_unknown(SentencePieceProcessor *spp, int id) {
-  return spp->IsUnknown(id);
+  return catch_exceptions(false, [&] {
+    return spp->IsUnknown(id);
+  });
+}
+
+bool spp_is_unused(SentencePieceProcessor *spp, int id) {
+  return catch_exceptions(false, [&] {
+    return spp->IsUnused(id);
+  });
 }
 
 int spp_pad_id(SentencePieceProcessor *spp) {
-  return spp->pad_id();
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    return spp->pad_id();
+  });
 }
 
 int spp_piece_size(SentencePieceProcessor *spp) {
-  return spp->GetPieceSize();
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    return spp->GetPieceSize();
+  });
 }
 
 int spp_piece_to_id(SentencePieceProcessor *spp, char const *piece) {
-  return spp->PieceToId(piece);
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    return spp->PieceToId(piece);
+  });
+}
+
+int spp_pieces_to_ids(SentencePieceProcessor *spp, char const *pieces, size_t const *piece_lens, size_t n_pieces, uint32_t *ids) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    char const *piece = pieces;
+    for (size_t i = 0; i < n_pieces; ++i) {
+      ids[i] = static_cast<uint32_t>(spp->PieceToId(absl::string_view(piece, piece_lens[i])));
+      piece += piece_lens[i];
+    }
+
+    return to_underlying_type(sentencepiece::util::StatusCode::kOk);
+  });
 }
 
 int spp_from_serialized_proto(SentencePieceProcessor *spp, char const *data, size_t len) {
-  auto status = spp->LoadFromSerializedProto(string_view(data, len));
-  return to_underlying_type(status.code());
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    auto status = spp->LoadFromSerializedProto(string_view(data, len));
+    return status_code(status);
+  });
 }
 
 unsigned char *spp_to_serialized_proto(SentencePieceProcessor *spp, size_t *len) {
-  auto serialized = spp->serialized_model_proto();
+  return catch_exceptions<unsigned char *>(nullptr, [&] {
+    auto serialized = spp->serialized_model_proto();
 
-  *len = serialized.size();
-  unsigned char *data = (unsigned char *) malloc(serialized.size());
-  memcpy(data, serialized.data(), serialized.size());
+    *len = serialized.size();
+    unsigned char *data = (unsigned char *) checked_malloc(serialized.size());
+    memcpy(data, serialized.data(), serialized.size());
 
-  return data;
+    return data;
+  });
 }
 
 void spp_free(SentencePieceProcessor *spp) {
   delete spp;
 }
 
+void spp_free_buffer(void *buffer) {
+  free(buffer);
+}
+
 int spp_unk_id(SentencePieceProcessor *spp) {
-  return spp->unk_id();
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    return spp->unk_id();
+  });
 }
 
 }
@@ -2,6 +2,9 @@
 #include <stddef.h>
 #include <stdint.h>
 
+#include "status.h"
+#include "types.h"
+
 #ifdef __cplusplus
 extern "C" {
 #endif
@@ -10,16 +13,74 @@ typedef struct SentencePieceProcessor SentencePieceProcessor;
 
 typedef struct SentencePieceText SentencePieceText;
 
+// Message of the last exception that was caught in the calling thread.
+char const *spp_exception_message(size_t *len);
+
+// Message of the last failed status that was returned in the calling thread.
+char const *spp_status_message(size_t *len);
+
 int spp_decode_piece_ids(SentencePieceProcessor *spp, uint32_t const *pieces, size_t pieces_len, unsigned char **decoded, size_t *decoded_len);
 
+// The decoded sentence is written to a buffer that is reserved with `reserve`.
+int spp_decode_piece_ids_into(SentencePieceProcessor *spp, uint32_t const *pieces, size_t pieces_len, spp_reserve_buffer reserve, void *decoded, size_t *decoded_len);
+
+// The sequences are concatenated in `pieces`, their lengths are given in `pieces_lens`.
+// The decoded sentences are concatenated in `decoded`, their lengths are stored in `decoded_lens`.
+int spp_decode_piece_ids_batch(SentencePieceProcessor *spp, uint32_t const *pieces, size_t const *pieces_lens, size_t n_sequences, unsigned char **decoded, size_t **decoded_lens);
+
 int spp_decode_pieces(SentencePieceProcessor *spp, char const * const *pieces, size_t pieces_len, unsigned char **decoded, size_t *decoded_len);
 
+unsigned char *spp_decode_piece_ids_as_serialized_proto(SentencePieceProcessor *spp, uint32_t const *pieces, size_t pieces_len, size_t *len);
+
+// The piece strings are concatenated in `piece_data`, their lengths are stored in the pieces.
+// The pieces and piece data are written to buffers that are reserved with `reserve`.
+int spp_encode(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, spp_reserve_buffer reserve, void *pieces, size_t *n_pieces, void *piece_data, size_t *piece_data_len);
+
+int spp_encode_as_ids(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, uint32_t **ids, size_t *ids_len);
+
+// The sentences are concatenated in `sentences`, their lengths are given in `sentence_lens`.
+// The sentences are encoded on `n_threads` threads, using a thread pool that is shared by
+// all calls. The number of threads is limited to the number of cores, which is also used
+// when `n_threads` is 0. The identifiers are concatenated in `ids`, their lengths are stored in `ids_lens`.
+int spp_encode_as_ids_batch(SentencePieceProcessor *spp, char const *sentences, size_t const *sentence_lens, size_t n_sentences, size_t n_threads, uint32_t **ids, size_t **ids_lens);
+
+// The pieces are concatenated in `pieces`, their lengths are stored in `piece_lens`.
+int spp_encode_as_pieces(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, unsigned char **pieces, size_t **piece_lens, size_t *n_pieces);
+
 unsigned char *spp_encode_as_serialized_proto(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, size_t *len);
 
+// The serialized proto is written to a buffer that is reserved with `reserve`.
+int spp_encode_as_serialized_proto_into(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, spp_reserve_buffer reserve, void *serialized, size_t *serialized_len);
+
+int spp_nbest_encode_as_serialized_proto(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, size_t nbest, unsigned char **serialized, size_t *serialized_len);
+
+int spp_normalize(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, unsigned char **normalized, size_t *normalized_len);
+
+// `norm_to_orig` maps every byte of the normalized sentence, plus the end of
+// the sentence, to a byte offset in the input. Its length is stored in
+// `norm_to_orig_len`, which is `normalized_len + 1`.
+int spp_normalize_with_offsets(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, unsigned char **normalized, size_t *normalized_len, size_t **norm_to_orig, size_t *norm_to_orig_len);
+
+unsigned char *spp_sample_encode_and_score_as_serialized_proto(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, size_t *len, size_t num_samples, float alpha, bool wor, bool include_best);
+
 unsigned char *spp_sample_encode_as_serialized_proto(SentencePieceProcessor *spp, char const *sentence, size_t sentence_len, size_t *len, size_t nbest, float alpha);
 
+int spp_set_decode_extra_options(SentencePieceProcessor *spp, char const *options, size_t options_len);
+
+int spp_set_encode_extra_options(SentencePieceProcessor *spp, char const *options, size_t options_len);
+
+// The pieces are concatenated in `pieces`, their lengths are given in `piece_lens`.
+int spp_set_vocabulary(SentencePieceProcessor *spp, char const *pieces, size_t const *piece_lens, size_t n_pieces);
+
+int spp_reset_vocabulary(SentencePieceProcessor *spp);
+
+int spp_load_vocabulary(SentencePieceProcessor *spp, char const *filename, int threshold);
+
 SentencePieceProcessor *spp_new();
 
+// Set the seed of the random generators that are created after this call.
+void spp_set_random_generator_seed(unsigned int seed);
+
 int spp_from_serialized_proto(SentencePieceProcessor *spp, char const *data, size_t len);
 
 unsigned char *spp_to_serialized_proto(SentencePieceProcessor *spp, size_t *len);
@@ -28,20 +89,69 @@ int spp_load(SentencePieceProcessor *spp, char const *filename);
 
 void spp_free(SentencePieceProcessor *spp);
 
+// Free a buffer that was allocated by the shim. Buffers must be freed with
+// this function rather than the allocator of the caller, since the shim
+// may use a different C runtime.
+void spp_free_buffer(void *buffer);
+
+// The following accessors return SPP_EXCEPTION (integers), false (predicates),
+// NaN (scores) or NULL (pieces) when sentencepiece raised an exception.
+
 int spp_bos_id(SentencePieceProcessor *spp);
 
 int spp_eos_id(SentencePieceProcessor *spp);
 
+float spp_get_score(SentencePieceProcessor *spp, int id);
+
+char const *spp_id_to_piece(SentencePieceProcessor *spp, int id, size_t *len);
+
+bool spp_is_byte(SentencePieceProcessor *spp, int id);
+
+bool spp_is_control(SentencePieceProcessor *spp, int id);
+
 bool spp_is_unknown(SentencePieceProcessor *spp, int id);
 
+bool spp_is_unused(SentencePieceProcessor *spp, int id);
+
 int spp_pad_id(SentencePieceProcessor *spp);
 
 int spp_piece_to_id(SentencePieceProcessor *spp, char const *piece);
 
+// The pieces are concatenated in `pieces`, their lengths are given in `piece_lens`.
+// `ids` must have room for `n_pieces` identifiers.
+int spp_pieces_to_ids(SentencePieceProcessor *spp, char const *pieces, size_t const *piece_lens, size_t n_pieces, uint32_t *ids);
+
 int spp_piece_size(SentencePieceProcessor *spp);
 
 int spp_unk_id(SentencePieceProcessor *spp);
 
+// Trainer. When model is NULL, the model is written to the files given by
+// the arguments, otherwise the serialized model is returned.
+
+// Callback that retrieves the next training sentence. Returns false when
+// there are no more sentences. The sentence must remain valid until the
+// next call.
+typedef bool (*spt_next_sentence)(void *ctx, char const **sentence, size_t *sentence_len);
+
+// Callback that receives a line that sentencepiece logged, without the
+// trailing newline. The callback can be called from several threads.
+typedef void (*spt_log_line)(char const *line, size_t line_len);
+
+// When log is not NULL, the lines that are logged during training are passed
+// to log rather than written to standard error. Standard error is redirected
+// while training, so only one training can run at a time.
+//
+// When model is not NULL, it is set to the serialized model, which must be
+// freed with spp_free_buffer. It is set to NULL when training fails.
+int spt_train(char const *args, size_t args_len, spt_log_line log, unsigned char **model, size_t *model_len);
+
+int spt_train_from_iterator(char const *args, size_t args_len, spt_next_sentence next, void *ctx, spt_log_line log, unsigned char **model, size_t *model_len);
+
+// Compile the normalization rules in a TSV file. Returns a serialized model
+// with the compiled rules in its normalizer spec. model is set to NULL when
+// the rules cannot be compiled.
+int spt_compile_normalization_rules(char const *filename, unsigned char **model, size_t *model_len);
+
 #ifdef __cplusplus
 }
 #endif
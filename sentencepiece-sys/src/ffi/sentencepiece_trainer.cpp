@@ -0,0 +1,178 @@
+#include <cstdlib>
+#include <cstring>
+#include <iostream>
+#include <memory>
+#include <mutex>
+#include <streambuf>
+#include <string>
+
+#include <sentencepiece_trainer.h>
+
+#include "sentencepiece.h"
+#include "util.h"
+
+using absl::string_view;
+using sentencepiece::SentenceIterator;
+using sentencepiece::SentencePieceNormalizer;
+using sentencepiece::SentencePieceTrainer;
+
+namespace {
+
+// Sentence iterator that retrieves sentences through a C callback.
+class CallbackSentenceIterator : public SentenceIterator {
+ public:
+  CallbackSentenceIterator(spt_next_sentence next, void *ctx) : next_(next), ctx_(ctx) {
+    Next();
+  }
+
+  bool done() const override {
+    return done_;
+  }
+
+  void Next() override {
+    char const *sentence = nullptr;
+    size_t sentence_len = 0;
+    if (next_(ctx_, &sentence, &sentence_len)) {
+      value_.assign(sentence, sentence_len);
+    } else {
+      done_ = true;
+      value_.clear();
+    }
+  }
+
+  std::string const &value() const override {
+    return value_;
+  }
+
+  sentencepiece::util::Status status() const override {
+    return sentencepiece::util::OkStatus();
+  }
+
+ private:
+  spt_next_sentence next_;
+  void *ctx_;
+  bool done_ = false;
+  std::string value_;
+};
+
+// Stream buffer that passes the lines that are written to it to a callback.
+// Lines are buffered per thread, since trainer threads log concurrently.
+class LogStreambuf : public std::streambuf {
+ public:
+  explicit LogStreambuf(spt_log_line log) : log_(log) {}
+
+ protected:
+  int overflow(int c) override {
+    if (c != traits_type::eof()) {
+      put(static_cast<char>(c));
+    }
+    return traits_type::not_eof(c);
+  }
+
+  std::streamsize xsputn(char const *s, std::streamsize n) override {
+    for (std::streamsize i = 0; i < n; ++i) {
+      put(s[i]);
+    }
+    return n;
+  }
+
+ private:
+  void put(char c) {
+    thread_local std::string line;
+    if (c == '\n') {
+      log_(line.data(), line.size());
+      line.clear();
+    } else {
+      line.push_back(c);
+    }
+  }
+
+  spt_log_line log_;
+};
+
+// Redirects std::cerr, to which sentencepiece logs, while in scope.
+class CerrRedirect {
+ public:
+  explicit CerrRedirect(std::streambuf *buf) : old_(std::cerr.rdbuf(buf)) {}
+
+  ~CerrRedirect() {
+    std::cerr.rdbuf(old_);
+  }
+
+ private:
+  std::streambuf *old_;
+};
+
+// Trainings are serialized, since std::cerr is global. Otherwise, the log
+// of a training could end up in the log callback of another training.
+std::mutex &train_mutex() {
+  static std::mutex mutex;
+  return mutex;
+}
+
+int train(char const *args, size_t args_len, SentenceIterator *sentences, spt_log_line log, unsigned char **model, size_t *model_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    std::lock_guard<std::mutex> lock(train_mutex());
+
+    std::unique_ptr<LogStreambuf> log_buf;
+    std::unique_ptr<CerrRedirect> redirect;
+    if (log != nullptr) {
+      log_buf.reset(new LogStreambuf(log));
+      redirect.reset(new CerrRedirect(log_buf.get()));
+    }
+
+    std::string serialized;
+    auto status = SentencePieceTrainer::Train(string_view(args, args_len), sentences,
+      model == nullptr ? nullptr : &serialized);
+
+    if (model != nullptr) {
+      if (!status.ok()) {
+        *model = nullptr;
+        *model_len = 0;
+        return status_code(status);
+      }
+
+      *model_len = serialized.size();
+      *model = static_cast<unsigned char *>(checked_malloc(serialized.size()));
+      memcpy(*model, serialized.data(), serialized.size());
+    }
+
+    return status_code(status);
+  });
+}
+
+}
+
+extern "C" {
+
+int spt_train(char const *args, size_t args_len, spt_log_line log, unsigned char **model, size_t *model_len) {
+  return train(args, args_len, nullptr, log, model, model_len);
+}
+
+int spt_train_from_iterator(char const *args, size_t args_len, spt_next_sentence next, void *ctx, spt_log_line log, unsigned char **model, size_t *model_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    CallbackSentenceIterator sentences(next, ctx);
+    return train(args, args_len, &sentences, log, model, model_len);
+  });
+}
+
+int spt_compile_normalization_rules(char const *filename, unsigned char **model, size_t *model_len) {
+  return catch_exceptions(SPP_EXCEPTION, [&] {
+    SentencePieceNormalizer normalizer;
+    auto status = normalizer.LoadFromRuleTSV(filename);
+    if (!status.ok()) {
+      *model = nullptr;
+      *model_len = 0;
+      return status_code(status);
+    }
+
+    std::string serialized = normalizer.serialized_model_proto();
+    *model_len = serialized.size();
+    *model = static_cast<unsigned char *>(checked_malloc(serialized.size()));
+    memcpy(*model, serialized.data(), serialized.size());
+
+    return status_code(status);
+  });
+}
+
+}
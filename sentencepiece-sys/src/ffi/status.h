@@ -0,0 +1,4 @@
+#pragma once
+
+// Status that is returned when the wrapper caught a C++ exception.
+#define SPP_EXCEPTION -1
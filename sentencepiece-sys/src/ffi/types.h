@@ -0,0 +1,17 @@
+#pragma once
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Piece of an encoded sentence. The piece strings are returned separately.
+typedef struct SentencePieceWithId {
+  uint32_t id;
+  uint32_t begin;
+  uint32_t end;
+  size_t piece_len;
+} SentencePieceWithId;
+
+// Callback that reserves room for `size` bytes in `buffer`. Returns a pointer
+// to the reserved memory, which the caller fills. The memory is not
+// necessarily aligned. Returns NULL when the memory could not be reserved.
+typedef void *(*spp_reserve_buffer)(void *buffer, size_t size);
@@ -0,0 +1,73 @@
+#pragma once
+
+#include <cstdlib>
+#include <exception>
+#include <new>
+#include <string>
+#include <type_traits>
+
+#include <sentencepiece_processor.h>
+
+#include "status.h"
+#include "types.h"
+
+// Inspired by:
+// https://stackoverflow.com/a/14589519
+template<typename E>
+constexpr auto to_underlying_type(E e) -> typename std::underlying_type<E>::type 
+{
+   return static_cast<typename std::underlying_type<E>::type>(e);
+}
+
+// Message of the last exception that was caught in the calling thread.
+std::string &exception_message();
+
+// Message of the last failed status that was returned in the calling thread.
+std::string &status_message();
+
+// Store the message of a failed status and return the status code.
+inline int status_code(sentencepiece::util::Status const &status) {
+  if (!status.ok()) {
+    status_message() = status.error_message();
+  }
+  return to_underlying_type(status.code());
+}
+
+// Allocate `size` bytes with malloc, throwing std::bad_alloc when the
+// memory could not be allocated.
+inline void *checked_malloc(size_t size) {
+  void *data = malloc(size);
+  if (data == nullptr && size > 0) {
+    throw std::bad_alloc();
+  }
+  return data;
+}
+
+// Reserve room for `size` bytes in `buffer`, throwing std::bad_alloc when
+// the memory could not be reserved.
+inline unsigned char *reserve_or_throw(spp_reserve_buffer reserve, void *buffer, size_t size) {
+  auto data = static_cast<unsigned char *>(reserve(buffer, size));
+  if (data == nullptr) {
+    throw std::bad_alloc();
+  }
+  return data;
+}
+
+// Exceptions must not unwind into Rust. Call f and return its result.
+// If f throws an exception, store its message and return error_value.
+template<typename R, typename F>
+R catch_exceptions(R error_value, F f) noexcept {
+  try {
+    return f();
+  } catch (std::exception const &e) {
+    try {
+      exception_message() = e.what();
+    } catch (...) {
+      exception_message().clear();
+    }
+  } catch (...) {
+    exception_message() = "unknown exception";
+  }
+
+  return error_value;
+}
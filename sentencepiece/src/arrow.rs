@@ -0,0 +1,171 @@
+use arrow::array::{
+    Array, ArrowPrimitiveType, GenericStringArray, LargeListArray, LargeListBuilder,
+    OffsetSizeTrait, PrimitiveBuilder,
+};
+use arrow::datatypes::ArrowNativeType;
+
+use crate::{SentencePieceError, SentencePieceProcessor};
+
+/// Encoded string array.
+///
+/// Every list corresponds to the string at the same index of the
+/// encoded array. The lists are null for null strings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArrowEncoding {
+    /// Piece identifiers.
+    pub ids: LargeListArray,
+
+    /// Begin byte offsets of the pieces.
+    ///
+    /// Only present when offsets are requested.
+    pub begin_offsets: Option<LargeListArray>,
+
+    /// End byte offsets of the pieces.
+    ///
+    /// Only present when offsets are requested.
+    pub end_offsets: Option<LargeListArray>,
+}
+
+impl SentencePieceProcessor {
+    /// Encode an Arrow string array.
+    ///
+    /// The piece identifiers are stored as lists of the primitive type
+    /// `T`, typically `Int32Type` or `Int64Type`. When `offsets` is
+    /// set, the begin and end byte offsets of the pieces are returned
+    /// as well.
+    ///
+    /// Returns `SentencePieceError::InvalidArgument` when an identifier
+    /// or offset cannot be represented by `T`.
+    pub fn encode_arrow<T, O>(
+        &self,
+        texts: &GenericStringArray<O>,
+        offsets: bool,
+    ) -> Result<ArrowEncoding, SentencePieceError>
+    where
+        T: ArrowPrimitiveType,
+        O: OffsetSizeTrait,
+    {
+        let list_builder =
+            || LargeListBuilder::with_capacity(PrimitiveBuilder::<T>::new(), texts.len());
+        let mut ids = list_builder();
+        let mut begin_offsets = list_builder();
+        let mut end_offsets = list_builder();
+
+        for text in texts.iter() {
+            let text = match text {
+                Some(text) => text,
+                None => {
+                    ids.append(false);
+                    begin_offsets.append(false);
+                    end_offsets.append(false);
+                    continue;
+                }
+            };
+
+            for piece in self.encode(text)? {
+                ids.values().append_value(to_native::<T>(piece.id)?);
+                if offsets {
+                    begin_offsets
+                        .values()
+                        .append_value(to_native::<T>(piece.span.0)?);
+                    end_offsets
+                        .values()
+                        .append_value(to_native::<T>(piece.span.1)?);
+                }
+            }
+
+            ids.append(true);
+            begin_offsets.append(true);
+            end_offsets.append(true);
+        }
+
+        let (begin_offsets, end_offsets) = if offsets {
+            (Some(begin_offsets.finish()), Some(end_offsets.finish()))
+        } else {
+            (None, None)
+        };
+
+        Ok(ArrowEncoding {
+            ids: ids.finish(),
+            begin_offsets,
+            end_offsets,
+        })
+    }
+}
+
+fn to_native<T: ArrowPrimitiveType>(value: u32) -> Result<T::Native, SentencePieceError> {
+    T::Native::from_usize(value as usize).ok_or_else(|| {
+        SentencePieceError::InvalidArgument(format!(
+            "{} cannot be represented as {}",
+            value,
+            T::DATA_TYPE
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{
+        Array, Int32Array, Int64Array, LargeListArray, LargeStringArray, StringArray,
+    };
+    use arrow::datatypes::{Int32Type, Int64Type, Int8Type};
+
+    use crate::test_util::toy_model;
+    use crate::SentencePieceError;
+
+    fn list<A: Array + Clone + 'static>(lists: &LargeListArray, idx: usize) -> A {
+        lists
+            .value(idx)
+            .as_any()
+            .downcast_ref::<A>()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn encodes_string_array() {
+        let model = toy_model();
+        let texts = StringArray::from(vec![Some("I saw a girl."), None, Some("")]);
+        let encoding = model.encode_arrow::<Int64Type, _>(&texts, false).unwrap();
+
+        assert_eq!(encoding.ids.len(), 3);
+        assert_eq!(
+            list::<Int64Array>(&encoding.ids, 0).values(),
+            &[8, 465, 10, 947, 4]
+        );
+        assert!(encoding.ids.is_null(1));
+        assert!(list::<Int64Array>(&encoding.ids, 2).is_empty());
+        assert_eq!(encoding.begin_offsets, None);
+        assert_eq!(encoding.end_offsets, None);
+    }
+
+    #[test]
+    fn encodes_string_array_with_offsets() {
+        let model = toy_model();
+        let texts = LargeStringArray::from(vec!["I saw a girl.", "I saw"]);
+        let encoding = model.encode_arrow::<Int32Type, _>(&texts, true).unwrap();
+
+        assert_eq!(list::<Int32Array>(&encoding.ids, 1).values(), &[8, 465]);
+        let begin_offsets = encoding.begin_offsets.unwrap();
+        let end_offsets = encoding.end_offsets.unwrap();
+        assert_eq!(
+            list::<Int32Array>(&begin_offsets, 0).values(),
+            &[0, 1, 5, 7, 12]
+        );
+        assert_eq!(
+            list::<Int32Array>(&end_offsets, 0).values(),
+            &[1, 5, 7, 12, 13]
+        );
+        assert_eq!(list::<Int32Array>(&end_offsets, 1).values(), &[1, 5]);
+    }
+
+    #[test]
+    fn rejects_identifiers_that_do_not_fit() {
+        let model = toy_model();
+        let texts = StringArray::from(vec!["I saw"]);
+        assert!(matches!(
+            model.encode_arrow::<Int8Type, _>(&texts, false),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+}
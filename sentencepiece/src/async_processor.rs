@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+
+use crate::{BatchOptions, PaddedBatch, PieceWithId, SentencePieceError, SentencePieceProcessor};
+
+/// Processor that encodes and decodes on tokio's blocking thread pool.
+///
+/// Encoding and decoding large documents can take long enough to stall
+/// an async runtime. The methods of this processor run the calls into
+/// sentencepiece with `spawn_blocking`, limiting the number of calls
+/// that run concurrently. Calls that exceed the limit wait until a
+/// running call finishes.
+///
+/// The methods must be called from within a tokio runtime.
+#[derive(Clone)]
+pub struct AsyncProcessor {
+    spp: Arc<SentencePieceProcessor>,
+    permits: Arc<Semaphore>,
+}
+
+impl AsyncProcessor {
+    /// Construct an asynchronous processor.
+    ///
+    /// At most `max_concurrency` calls into sentencepiece run at the
+    /// same time. Returns `SentencePieceError::InvalidArgument` when
+    /// `max_concurrency` is `0`.
+    pub fn new(
+        spp: SentencePieceProcessor,
+        max_concurrency: usize,
+    ) -> Result<Self, SentencePieceError> {
+        if max_concurrency == 0 {
+            return Err(SentencePieceError::InvalidArgument(
+                "maximum concurrency must be at least 1".to_string(),
+            ));
+        }
+
+        Ok(AsyncProcessor {
+            spp: Arc::new(spp),
+            permits: Arc::new(Semaphore::new(max_concurrency)),
+        })
+    }
+
+    /// Decode a sentence from piece identifiers.
+    ///
+    /// See [`SentencePieceProcessor::decode_piece_ids`].
+    pub async fn decode_async(&self, pieces: Vec<u32>) -> Result<String, SentencePieceError> {
+        self.run_blocking(move |spp| spp.decode_piece_ids(&pieces))
+            .await
+    }
+
+    /// Encode a sentence.
+    ///
+    /// See [`SentencePieceProcessor::encode`].
+    pub async fn encode_async(
+        &self,
+        sentence: impl Into<String>,
+    ) -> Result<Vec<PieceWithId>, SentencePieceError> {
+        let sentence = sentence.into();
+        self.run_blocking(move |spp| spp.encode(&sentence)).await
+    }
+
+    /// Encode a batch of sentences as padded matrices.
+    ///
+    /// See [`SentencePieceProcessor::encode_batch`]. The batch is encoded
+    /// in a single blocking call.
+    pub async fn encode_batch_async<S>(
+        &self,
+        sentences: Vec<S>,
+        options: BatchOptions,
+    ) -> Result<PaddedBatch, SentencePieceError>
+    where
+        S: AsRef<str> + Send + 'static,
+    {
+        self.run_blocking(move |spp| spp.encode_batch(&sentences, &options))
+            .await
+    }
+
+    /// Get the wrapped processor.
+    pub fn processor(&self) -> &SentencePieceProcessor {
+        &self.spp
+    }
+
+    async fn run_blocking<T>(
+        &self,
+        f: impl FnOnce(&SentencePieceProcessor) -> T + Send + 'static,
+    ) -> T
+    where
+        T: Send + 'static,
+    {
+        // The permit is moved into the task, so that it is only released
+        // when the blocking call finishes, even if this future is dropped.
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Semaphore is never closed");
+        let spp = self.spp.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            f(&spp)
+        })
+        .await;
+
+        match result {
+            Ok(value) => value,
+            Err(err) => match err.try_into_panic() {
+                Ok(panic) => std::panic::resume_unwind(panic),
+                Err(_) => panic!("Blocking task was cancelled by runtime shutdown"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::Builder;
+
+    use crate::test_util::toy_model;
+    use crate::{AsyncProcessor, BatchOptions, SentencePieceError};
+
+    #[test]
+    fn encodes_and_decodes_asynchronously() {
+        let model = toy_model();
+        let processor = AsyncProcessor::new(model.clone(), 2).unwrap();
+        let runtime = Builder::new_current_thread().build().unwrap();
+
+        runtime.block_on(async {
+            let sentences = ["I saw a girl.", "I saw", "I saw a girl with a telescope."];
+            let handles = sentences
+                .iter()
+                .map(|&sentence| {
+                    let processor = processor.clone();
+                    tokio::spawn(async move { processor.encode_async(sentence).await })
+                })
+                .collect::<Vec<_>>();
+            for (sentence, handle) in sentences.iter().zip(handles) {
+                assert_eq!(
+                    handle.await.unwrap().unwrap(),
+                    model.encode(sentence).unwrap()
+                );
+            }
+
+            assert_eq!(processor.decode_async(vec![8, 465]).await.unwrap(), "I saw");
+
+            let batch = processor
+                .encode_batch_async(sentences.to_vec(), BatchOptions::default())
+                .await
+                .unwrap();
+            assert_eq!(
+                batch,
+                model
+                    .encode_batch(&sentences, &BatchOptions::default())
+                    .unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn rejects_zero_concurrency() {
+        assert!(matches!(
+            AsyncProcessor::new(toy_model(), 0),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,465 @@
+use std::os::raw::c_char;
+
+#[cfg(feature = "ndarray")]
+use ndarray::Array2;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use sentencepiece_sys::{spp_decode_piece_ids_batch, spp_encode_as_ids_batch};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "rayon")]
+use crate::PieceWithId;
+use crate::{c_error, CData, SentencePieceError, SentencePieceProcessor};
+
+/// Minimum number of sentences that a rayon task encodes or decodes.
+#[cfg(feature = "rayon")]
+const MIN_CHUNK_LEN: usize = 64;
+
+/// Options for batch encoding.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BatchOptions {
+    /// Identifier of the padding piece.
+    ///
+    /// When `None`, the padding piece of the model is used, or `0` if
+    /// the model does not have a padding piece.
+    pub pad_id: Option<u32>,
+
+    /// Truncate sentences to this number of pieces.
+    pub max_len: Option<usize>,
+
+    /// Return the begin and end offsets of the pieces.
+    pub offsets: bool,
+}
+
+/// Batch of encoded sentences, padded to the same length.
+///
+/// The matrices are stored in row-major order with the shape
+/// *[batch_size, seq_len]*, so that they can be passed directly as
+/// tensors to ONNX or Triton models.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct PaddedBatch {
+    /// Number of sentences in the batch.
+    pub batch_size: usize,
+
+    /// Length of the longest encoded sentence in the batch.
+    pub seq_len: usize,
+
+    /// Piece identifiers, padded with the padding identifier.
+    pub ids: Vec<u32>,
+
+    /// Attention mask, `1` for pieces and `0` for padding.
+    pub attention_mask: Vec<u32>,
+
+    /// Begin byte offsets of the pieces, `0` for padding.
+    ///
+    /// Only present when `BatchOptions::offsets` is set.
+    pub begin_offsets: Option<Vec<u32>>,
+
+    /// End byte offsets of the pieces, `0` for padding.
+    ///
+    /// Only present when `BatchOptions::offsets` is set.
+    pub end_offsets: Option<Vec<u32>>,
+}
+
+#[cfg(feature = "ndarray")]
+impl PaddedBatch {
+    /// Get the attention mask as a matrix with the shape
+    /// *[batch_size, seq_len]*.
+    pub fn attention_mask_array(&self) -> Array2<i64> {
+        self.to_array(&self.attention_mask)
+    }
+
+    /// Get the piece identifiers as a matrix with the shape
+    /// *[batch_size, seq_len]*.
+    pub fn ids_array(&self) -> Array2<i64> {
+        self.to_array(&self.ids)
+    }
+
+    fn to_array(&self, data: &[u32]) -> Array2<i64> {
+        Array2::from_shape_fn((self.batch_size, self.seq_len), |(row, col)| {
+            data[row * self.seq_len + col] as i64
+        })
+    }
+}
+
+impl SentencePieceProcessor {
+    /// Encode a batch of sentences as ndarray matrices.
+    ///
+    /// Returns the piece identifiers and the attention mask as `i64`
+    /// matrices with the shape *[batch_size, seq_len]*, which is the
+    /// input format of most transformer models. The padding identifier
+    /// and maximum length are set in `options`, offsets are not
+    /// returned.
+    #[cfg(feature = "ndarray")]
+    pub fn encode_batch_array(
+        &self,
+        sentences: &[impl AsRef<str>],
+        options: &BatchOptions,
+    ) -> Result<(Array2<i64>, Array2<i64>), SentencePieceError> {
+        let batch = self.encode_batch(
+            sentences,
+            &BatchOptions {
+                offsets: false,
+                ..*options
+            },
+        )?;
+        Ok((batch.ids_array(), batch.attention_mask_array()))
+    }
+
+    /// Encode a batch of sentences as padded matrices.
+    pub fn encode_batch(
+        &self,
+        sentences: &[impl AsRef<str>],
+        options: &BatchOptions,
+    ) -> Result<PaddedBatch, SentencePieceError> {
+        let mut encoded = sentences
+            .iter()
+            .map(|sentence| self.encode(sentence.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(max_len) = options.max_len {
+            for pieces in &mut encoded {
+                pieces.truncate(max_len);
+            }
+        }
+
+        let batch_size = encoded.len();
+        let seq_len = encoded.iter().map(Vec::len).max().unwrap_or(0);
+        let pad_id = options.pad_id.or_else(|| self.pad_id()).unwrap_or(0);
+
+        let mut ids = vec![pad_id; batch_size * seq_len];
+        let mut attention_mask = vec![0; batch_size * seq_len];
+        let mut begin_offsets = vec![0; batch_size * seq_len];
+        let mut end_offsets = vec![0; batch_size * seq_len];
+
+        for (row, pieces) in encoded.iter().enumerate() {
+            let row_start = row * seq_len;
+            for (col, piece) in pieces.iter().enumerate() {
+                ids[row_start + col] = piece.id;
+                attention_mask[row_start + col] = 1;
+                begin_offsets[row_start + col] = piece.span.0;
+                end_offsets[row_start + col] = piece.span.1;
+            }
+        }
+
+        let (begin_offsets, end_offsets) = if options.offsets {
+            (Some(begin_offsets), Some(end_offsets))
+        } else {
+            (None, None)
+        };
+
+        Ok(PaddedBatch {
+            batch_size,
+            seq_len,
+            ids,
+            attention_mask,
+            begin_offsets,
+            end_offsets,
+        })
+    }
+
+    /// Encode a batch of sentences as piece identifiers on multiple
+    /// threads.
+    ///
+    /// The batch is encoded in a single call into sentencepiece, which
+    /// encodes the sentences on `n_threads` threads of a thread pool that
+    /// is shared by all calls. The number of threads is limited to the
+    /// number of cores. When `n_threads` is `0`, one thread per core is
+    /// used. If any sentences cannot be
+    /// encoded, the error of the first such sentence in the batch is
+    /// returned.
+    pub fn encode_as_ids_batch(
+        &self,
+        sentences: &[impl AsRef<str>],
+        n_threads: usize,
+    ) -> Result<Vec<Vec<u32>>, SentencePieceError> {
+        let concatenated = sentences
+            .iter()
+            .map(|sentence| sentence.as_ref())
+            .collect::<String>();
+        let sentence_lens = sentences
+            .iter()
+            .map(|sentence| sentence.as_ref().len())
+            .collect::<Vec<_>>();
+
+        let mut ids = std::ptr::null_mut::<u32>();
+        let mut ids_lens = std::ptr::null_mut::<usize>();
+
+        let status = unsafe {
+            spp_encode_as_ids_batch(
+                self.inner,
+                concatenated.as_ptr() as *const c_char,
+                sentence_lens.as_ptr(),
+                sentences.len(),
+                n_threads,
+                &mut ids,
+                &mut ids_lens,
+            )
+        };
+
+        let c_ids_lens = CData {
+            data: ids_lens,
+            len: sentences.len(),
+        };
+        let c_ids = CData {
+            data: ids,
+            len: c_ids_lens.iter().sum(),
+        };
+
+        if status != 0 {
+            return Err(c_error(status));
+        }
+
+        let mut rest = &*c_ids;
+        Ok(c_ids_lens
+            .iter()
+            .map(|&len| {
+                let (sentence_ids, tail) = rest.split_at(len);
+                rest = tail;
+                sentence_ids.to_vec()
+            })
+            .collect())
+    }
+
+    /// Decode a batch of sentences from piece identifiers.
+    ///
+    /// The batch is decoded in a single call into sentencepiece. If any
+    /// sentences cannot be decoded, the error of the first such sentence
+    /// in the batch is returned.
+    pub fn decode_batch(
+        &self,
+        batch: &[impl AsRef<[u32]>],
+    ) -> Result<Vec<String>, SentencePieceError> {
+        let pieces = batch
+            .iter()
+            .flat_map(|ids| ids.as_ref().iter().copied())
+            .collect::<Vec<_>>();
+        let pieces_lens = batch
+            .iter()
+            .map(|ids| ids.as_ref().len())
+            .collect::<Vec<_>>();
+
+        let mut decoded = std::ptr::null_mut::<u8>();
+        let mut decoded_lens = std::ptr::null_mut::<usize>();
+
+        let status = unsafe {
+            spp_decode_piece_ids_batch(
+                self.inner,
+                pieces.as_ptr(),
+                pieces_lens.as_ptr(),
+                batch.len(),
+                &mut decoded,
+                &mut decoded_lens,
+            )
+        };
+
+        let c_decoded_lens = CData {
+            data: decoded_lens,
+            len: batch.len(),
+        };
+        let c_decoded = CData {
+            data: decoded,
+            len: c_decoded_lens.iter().sum(),
+        };
+
+        if status != 0 {
+            return Err(c_error(status));
+        }
+
+        let mut rest = &*c_decoded;
+        c_decoded_lens
+            .iter()
+            .map(|&len| {
+                let (sentence, tail) = rest.split_at(len);
+                rest = tail;
+                String::from_utf8(sentence.to_owned())
+                    .map_err(|err| SentencePieceError::InvalidUtf8(err.into_bytes()))
+            })
+            .collect()
+    }
+
+    /// Decode a batch of sentences in parallel.
+    ///
+    /// The batch is split in chunks that are decoded on the rayon thread
+    /// pool. If any sentences cannot be decoded, the error of the first
+    /// such sentence in the batch is returned.
+    #[cfg(feature = "rayon")]
+    pub fn par_decode_batch(
+        &self,
+        batch: &[impl AsRef<[u32]> + Sync],
+    ) -> Result<Vec<String>, SentencePieceError> {
+        let chunks = batch
+            .par_chunks(MIN_CHUNK_LEN)
+            .map(|chunk| self.decode_batch(chunk))
+            .collect::<Vec<_>>();
+
+        let mut decoded = Vec::with_capacity(batch.len());
+        for chunk in chunks {
+            decoded.extend(chunk?);
+        }
+        Ok(decoded)
+    }
+
+    /// Encode a batch of sentences in parallel.
+    ///
+    /// The sentences are encoded in chunks on the rayon thread pool.
+    /// Returns the result of every sentence, in the order of the batch,
+    /// so that a sentence that cannot be encoded does not fail the
+    /// batch and its error can be attributed to the sentence.
+    #[cfg(feature = "rayon")]
+    pub fn par_encode_batch(
+        &self,
+        sentences: &[impl AsRef<str> + Sync],
+    ) -> Vec<Result<Vec<PieceWithId>, SentencePieceError>> {
+        sentences
+            .par_iter()
+            .with_min_len(MIN_CHUNK_LEN)
+            .map(|sentence| self.encode(sentence.as_ref()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{invalid_utf8_model, toy_model, INVALID_UTF8_PIECE};
+    use crate::{BatchOptions, CSentencePieceError, SentencePieceError};
+
+    #[test]
+    fn encodes_padded_batch_with_offsets() {
+        let model = toy_model();
+        let batch = model
+            .encode_batch(
+                &["I saw a girl.", "I saw"],
+                &BatchOptions {
+                    offsets: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(batch.batch_size, 2);
+        assert_eq!(batch.seq_len, 5);
+        assert_eq!(batch.ids, [8, 465, 10, 947, 4, 8, 465, 0, 0, 0]);
+        assert_eq!(batch.attention_mask, [1, 1, 1, 1, 1, 1, 1, 0, 0, 0]);
+        assert_eq!(
+            batch.begin_offsets.unwrap(),
+            [0, 1, 5, 7, 12, 0, 1, 0, 0, 0]
+        );
+        assert_eq!(batch.end_offsets.unwrap(), [1, 5, 7, 12, 13, 1, 5, 0, 0, 0]);
+    }
+
+    #[test]
+    fn truncates_and_pads_batch() {
+        let model = toy_model();
+        let batch = model
+            .encode_batch(
+                &["I saw a girl.", "I saw"],
+                &BatchOptions {
+                    pad_id: Some(2),
+                    max_len: Some(3),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(batch.seq_len, 3);
+        assert_eq!(batch.ids, [8, 465, 10, 8, 465, 2]);
+        assert_eq!(batch.begin_offsets, None);
+    }
+
+    #[test]
+    fn encodes_batch_as_ids_on_threads() {
+        let model = toy_model();
+        let sentences = ["I saw a girl.", "", "I saw a girl with a telescope."].repeat(10);
+        for &n_threads in &[0, 1, 4, 100] {
+            let encoded = model.encode_as_ids_batch(&sentences, n_threads).unwrap();
+            assert_eq!(encoded.len(), sentences.len());
+            for (sentence, ids) in sentences.iter().zip(&encoded) {
+                assert_eq!(*ids, model.encode_as_ids(sentence).unwrap());
+            }
+        }
+
+        let empty: [&str; 0] = [];
+        assert!(model.encode_as_ids_batch(&empty, 0).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn encodes_batch_as_arrays() {
+        let model = toy_model();
+        let (ids, attention_mask) = model
+            .encode_batch_array(
+                &["I saw a girl.", "I saw"],
+                &BatchOptions {
+                    pad_id: Some(2),
+                    max_len: Some(4),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(ids, ndarray::arr2(&[[8, 465, 10, 947], [8, 465, 2, 2]]));
+        assert_eq!(attention_mask, ndarray::arr2(&[[1, 1, 1, 1], [1, 1, 0, 0]]));
+
+        let empty: [&str; 0] = [];
+        let (ids, _) = model
+            .encode_batch_array(&empty, &BatchOptions::default())
+            .unwrap();
+        assert_eq!(ids.shape(), [0, 0]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn encodes_batch_in_parallel() {
+        let model = toy_model();
+        let sentences = ["I saw a girl.", "I saw a girl with a telescope."].repeat(100);
+        let encoded = model.par_encode_batch(&sentences);
+        assert_eq!(encoded.len(), 200);
+        for (sentence, pieces) in sentences.iter().zip(encoded) {
+            assert_eq!(pieces.unwrap(), model.encode(sentence).unwrap());
+        }
+    }
+
+    #[test]
+    fn decode_batch_reports_invalid_utf8() {
+        let model = invalid_utf8_model();
+        assert_eq!(
+            model.decode_batch(&[vec![], vec![3]]),
+            Err(SentencePieceError::InvalidUtf8(INVALID_UTF8_PIECE.to_vec()))
+        );
+    }
+
+    #[test]
+    fn decodes_batch() {
+        let model = toy_model();
+        let batch = vec![
+            vec![8, 465, 10, 947, 4],
+            vec![],
+            vec![8, 465, 10, 947, 41, 10, 170, 168, 110, 28, 20, 143, 4],
+        ];
+        assert_eq!(
+            model.decode_batch(&batch).unwrap(),
+            ["I saw a girl.", "", "I saw a girl with a telescope."]
+        );
+        assert!(model.decode_batch(&[] as &[Vec<u32>]).unwrap().is_empty());
+
+        assert!(matches!(
+            model.decode_batch(&[vec![8, 465], vec![8, 1000]]),
+            Err(SentencePieceError::CError {
+                code: CSentencePieceError::OutOfRange,
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn decodes_batch_in_parallel() {
+        let model = toy_model();
+        let batch = [vec![8, 465, 10, 947, 4], vec![8, 465]].repeat(100);
+        let decoded = model.par_decode_batch(&batch).unwrap();
+        assert_eq!(decoded, model.decode_batch(&batch).unwrap());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::model::model_proto::sentence_piece::Type;
+use crate::{json, IoError, ModelType, SentencePieceError, SentencePieceProcessor};
+
+impl SentencePieceProcessor {
+    /// Reconstruct the merges of a BPE model.
+    ///
+    /// sentencepiece does not store the merges of a BPE model. Since the
+    /// pieces of a BPE model are ordered by merge priority, the merges
+    /// are reconstructed by splitting every normal piece into two
+    /// normal pieces in all possible ways. The merges are ordered by the
+    /// identifier of the merged piece and then by the identifiers of the
+    /// left and right pieces.
+    ///
+    /// Returns `SentencePieceError::UnsupportedModelType` if the model
+    /// is not a BPE model.
+    pub fn bpe_merges(&self) -> Result<Vec<(String, String)>, SentencePieceError> {
+        let model_type = self.model_type();
+        if model_type != ModelType::Bpe {
+            return Err(SentencePieceError::UnsupportedModelType(model_type));
+        }
+
+        let model = self.model_proto()?;
+
+        let normal_pieces = model
+            .pieces
+            .iter()
+            .enumerate()
+            .filter(|(_, piece)| piece.r#type() == Type::Normal)
+            .map(|(id, piece)| (id as u32, piece.piece()))
+            .collect::<Vec<_>>();
+        let ids = normal_pieces
+            .iter()
+            .map(|&(id, piece)| (piece, id))
+            .collect::<HashMap<_, _>>();
+
+        let mut merges = Vec::new();
+        for &(id, piece) in &normal_pieces {
+            for (split, _) in piece.char_indices().skip(1) {
+                let (left, right) = piece.split_at(split);
+                if let (Some(&left_id), Some(&right_id)) = (ids.get(left), ids.get(right)) {
+                    merges.push(((id, left_id, right_id), left, right));
+                }
+            }
+        }
+
+        merges.sort_unstable_by_key(|&(key, _, _)| key);
+
+        Ok(merges
+            .into_iter()
+            .map(|(_, left, right)| (left.to_owned(), right.to_owned()))
+            .collect())
+    }
+
+    /// Write a BPE model as GPT-2-style `vocab.json` and `merges.txt`.
+    ///
+    /// `vocab_json` contains a JSON object that maps every piece to its
+    /// identifier. `merges_txt` contains the merges reconstructed by
+    /// [`SentencePieceProcessor::bpe_merges`], one per line. The pieces
+    /// are written as-is, so whitespace is represented by `▁` rather
+    /// than using GPT-2's byte-to-unicode mapping.
+    pub fn write_bpe_files(
+        &self,
+        mut vocab_json: impl Write,
+        mut merges_txt: impl Write,
+    ) -> Result<(), IoError> {
+        let merges = self.bpe_merges()?;
+
+        self.write_vocab_json(&mut vocab_json)
+            .map_err(|error| IoError::Io {
+                desc: "Cannot write BPE vocabulary".to_string(),
+                error,
+            })?;
+
+        write_merges(&mut merges_txt, &merges).map_err(|error| IoError::Io {
+            desc: "Cannot write BPE merges".to_string(),
+            error,
+        })
+    }
+
+    fn write_vocab_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(b"{")?;
+        for id in 0..self.len() as u32 {
+            writer.write_all(if id == 0 { b"\n  " } else { b",\n  " })?;
+            let piece = String::from_utf8_lossy(
+                self.piece_bytes(id)
+                    .expect("Identifier is within the vocabulary"),
+            );
+            json::write_string(writer, &piece)?;
+            write!(writer, ": {}", id)?;
+        }
+        writer.write_all(b"\n}\n")?;
+        writer.flush()
+    }
+}
+
+fn write_merges(writer: &mut impl Write, merges: &[(String, String)]) -> io::Result<()> {
+    writeln!(writer, "#version: 0.2")?;
+    for (left, right) in merges {
+        writeln!(writer, "{} {}", left, right)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ModelBuilder, ModelType, PieceType, SentencePieceError, SentencePieceProcessor};
+
+    fn bpe_model() -> SentencePieceProcessor {
+        ModelBuilder::new()
+            .model_type(ModelType::Bpe)
+            .pieces(vec![
+                ("<unk>", 0., PieceType::Unknown),
+                ("<s>", 0., PieceType::Control),
+                ("</s>", 0., PieceType::Control),
+                ("▁a", 0., PieceType::Normal),
+                ("ab", -1., PieceType::Normal),
+                ("▁ab", -2., PieceType::Normal),
+                ("▁", -3., PieceType::Normal),
+                ("a", -4., PieceType::Normal),
+                ("b", -5., PieceType::Normal),
+            ])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn reconstructs_bpe_merges() {
+        let merges = bpe_model().bpe_merges().unwrap();
+        assert_eq!(
+            merges,
+            [("▁", "a"), ("a", "b"), ("▁a", "b"), ("▁", "ab")]
+                .iter()
+                .map(|&(left, right)| (left.to_string(), right.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn writes_bpe_files() {
+        let mut vocab = Vec::new();
+        let mut merges = Vec::new();
+        bpe_model()
+            .write_bpe_files(&mut vocab, &mut merges)
+            .unwrap();
+
+        let vocab = String::from_utf8(vocab).unwrap();
+        assert!(vocab.starts_with("{\n  \"<unk>\": 0,\n  \"<s>\": 1,"));
+        assert!(vocab.ends_with("  \"b\": 8\n}\n"));
+        assert_eq!(
+            String::from_utf8(merges).unwrap(),
+            "#version: 0.2\n▁ a\na b\n▁a b\n▁ ab\n"
+        );
+    }
+
+    #[test]
+    fn rejects_unigram_model() {
+        let model =
+            SentencePieceProcessor::from_serialized_proto(include_bytes!("../testdata/toy.model"))
+                .unwrap();
+        assert_eq!(
+            model.bpe_merges(),
+            Err(SentencePieceError::UnsupportedModelType(ModelType::Unigram))
+        );
+    }
+}
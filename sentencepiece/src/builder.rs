@@ -0,0 +1,385 @@
+use crate::model::{model_proto, trainer_spec, ModelProto, NormalizerSpec, TrainerSpec};
+use crate::{ModelType, PieceType, SentencePieceError, SentencePieceProcessor};
+
+/// Builder for small sentencepiece models.
+///
+/// This builder constructs a model from a list of pieces, which is
+/// mostly useful for writing deterministic tests without binary model
+/// files. The model uses the identity normalizer.
+///
+/// Special pieces are identified by their strings, which default to
+/// `<unk>`, `<s>`, `</s>`, and `<pad>`. The model must contain exactly
+/// one piece of the type `PieceType::Unknown`.
+///
+/// ```
+/// use sentencepiece::{ModelBuilder, PieceType};
+///
+/// let spp = ModelBuilder::new()
+///     .piece("<unk>", 0.0, PieceType::Unknown)
+///     .piece("<s>", 0.0, PieceType::Control)
+///     .piece("</s>", 0.0, PieceType::Control)
+///     .piece("▁", -2.0, PieceType::Normal)
+///     .piece("a", -3.0, PieceType::Normal)
+///     .piece("b", -3.0, PieceType::Normal)
+///     .piece("▁ab", -1.0, PieceType::Normal)
+///     .build()
+///     .unwrap();
+///
+/// let pieces = spp.encode("ab ba").unwrap()
+///   .into_iter().map(|p| p.piece).collect::<Vec<_>>();
+/// assert_eq!(pieces, vec!["▁ab", "▁", "b", "a"]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelBuilder {
+    model_type: ModelType,
+    pieces: Vec<(String, f32, PieceType)>,
+    unk_piece: String,
+    bos_piece: String,
+    eos_piece: String,
+    pad_piece: String,
+    add_dummy_prefix: bool,
+    remove_extra_whitespaces: bool,
+    escape_whitespaces: bool,
+    treat_whitespace_as_suffix: bool,
+}
+
+impl Default for ModelBuilder {
+    fn default() -> Self {
+        ModelBuilder {
+            model_type: ModelType::Unigram,
+            pieces: Vec::new(),
+            unk_piece: "<unk>".to_string(),
+            bos_piece: "<s>".to_string(),
+            eos_piece: "</s>".to_string(),
+            pad_piece: "<pad>".to_string(),
+            add_dummy_prefix: true,
+            remove_extra_whitespaces: true,
+            escape_whitespaces: true,
+            treat_whitespace_as_suffix: false,
+        }
+    }
+}
+
+impl ModelBuilder {
+    /// Construct a builder for a unigram model without pieces.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a dummy whitespace to the beginning of the input.
+    ///
+    /// Default: `true`
+    pub fn add_dummy_prefix(mut self, add_dummy_prefix: bool) -> Self {
+        self.add_dummy_prefix = add_dummy_prefix;
+        self
+    }
+
+    /// Set the string of the beginning of sentence piece.
+    ///
+    /// Default: `<s>`
+    pub fn bos_piece(mut self, piece: impl Into<String>) -> Self {
+        self.bos_piece = piece.into();
+        self
+    }
+
+    /// Build the model and load it into a processor.
+    pub fn build(&self) -> Result<SentencePieceProcessor, SentencePieceError> {
+        SentencePieceProcessor::from_serialized_proto(&self.to_serialized_proto())
+    }
+
+    /// Set the string of the end of sentence piece.
+    ///
+    /// Default: `</s>`
+    pub fn eos_piece(mut self, piece: impl Into<String>) -> Self {
+        self.eos_piece = piece.into();
+        self
+    }
+
+    /// Replace whitespace by the meta symbol `▁`.
+    ///
+    /// Default: `true`
+    pub fn escape_whitespaces(mut self, escape_whitespaces: bool) -> Self {
+        self.escape_whitespaces = escape_whitespaces;
+        self
+    }
+
+    /// Set the model type.
+    ///
+    /// Default: `ModelType::Unigram`
+    pub fn model_type(mut self, model_type: ModelType) -> Self {
+        self.model_type = model_type;
+        self
+    }
+
+    /// Set the string of the padding piece.
+    ///
+    /// Default: `<pad>`
+    pub fn pad_piece(mut self, piece: impl Into<String>) -> Self {
+        self.pad_piece = piece.into();
+        self
+    }
+
+    /// Add a piece.
+    ///
+    /// Pieces get identifiers in the order in which they are added.
+    pub fn piece(mut self, piece: impl Into<String>, score: f32, piece_type: PieceType) -> Self {
+        self.pieces.push((piece.into(), score, piece_type));
+        self
+    }
+
+    /// Add pieces.
+    pub fn pieces<S>(mut self, pieces: impl IntoIterator<Item = (S, f32, PieceType)>) -> Self
+    where
+        S: Into<String>,
+    {
+        self.pieces.extend(
+            pieces
+                .into_iter()
+                .map(|(piece, score, piece_type)| (piece.into(), score, piece_type)),
+        );
+        self
+    }
+
+    /// Remove leading, trailing, and duplicate internal whitespace.
+    ///
+    /// Default: `true`
+    pub fn remove_extra_whitespaces(mut self, remove_extra_whitespaces: bool) -> Self {
+        self.remove_extra_whitespaces = remove_extra_whitespaces;
+        self
+    }
+
+    /// Attach whitespace to the end rather than the start of pieces.
+    ///
+    /// When enabled, the dummy whitespace is added to the end of the
+    /// input, e.g. `hello world` is encoded as `hello▁ world▁`.
+    ///
+    /// Default: `false`
+    pub fn treat_whitespace_as_suffix(mut self, treat_whitespace_as_suffix: bool) -> Self {
+        self.treat_whitespace_as_suffix = treat_whitespace_as_suffix;
+        self
+    }
+
+    /// Set the string of the unknown piece.
+    ///
+    /// Default: `<unk>`
+    pub fn unk_piece(mut self, piece: impl Into<String>) -> Self {
+        self.unk_piece = piece.into();
+        self
+    }
+
+    /// Serialize the model to protobuf.
+    pub fn to_serialized_proto(&self) -> Vec<u8> {
+        prost::Message::encode_to_vec(&self.to_model_proto())
+    }
+
+    pub(crate) fn to_model_proto(&self) -> ModelProto {
+        let piece_id = |special: &str| {
+            self.pieces
+                .iter()
+                .position(|(piece, _, _)| piece == special)
+                .map(|id| id as i32)
+                .unwrap_or(-1)
+        };
+
+        let trainer_spec = TrainerSpec {
+            model_type: Some(trainer_spec::ModelType::from(self.model_type) as i32),
+            vocab_size: Some(self.pieces.len() as i32),
+            byte_fallback: Some(
+                self.pieces
+                    .iter()
+                    .any(|(_, _, piece_type)| *piece_type == PieceType::Byte),
+            ),
+            unk_id: Some(piece_id(&self.unk_piece)),
+            bos_id: Some(piece_id(&self.bos_piece)),
+            eos_id: Some(piece_id(&self.eos_piece)),
+            pad_id: Some(piece_id(&self.pad_piece)),
+            unk_piece: Some(self.unk_piece.clone()),
+            bos_piece: Some(self.bos_piece.clone()),
+            eos_piece: Some(self.eos_piece.clone()),
+            pad_piece: Some(self.pad_piece.clone()),
+            treat_whitespace_as_suffix: Some(self.treat_whitespace_as_suffix),
+            ..Default::default()
+        };
+
+        let normalizer_spec = NormalizerSpec {
+            name: Some("identity".to_string()),
+            precompiled_charsmap: Some(Vec::new()),
+            add_dummy_prefix: Some(self.add_dummy_prefix),
+            remove_extra_whitespaces: Some(self.remove_extra_whitespaces),
+            escape_whitespaces: Some(self.escape_whitespaces),
+            normalization_rule_tsv: None,
+        };
+
+        ModelProto {
+            pieces: self
+                .pieces
+                .iter()
+                .map(|(piece, score, piece_type)| model_proto::SentencePiece {
+                    piece: Some(piece.clone()),
+                    score: Some(*score),
+                    r#type: Some(model_proto::sentence_piece::Type::from(*piece_type) as i32),
+                })
+                .collect(),
+            trainer_spec: Some(trainer_spec),
+            normalizer_spec: Some(normalizer_spec),
+            self_test_data: None,
+            denormalizer_spec: None,
+        }
+    }
+}
+
+/// Options for `SentencePieceProcessor::from_parts`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FromPartsOptions {
+    /// Type of the model.
+    pub model_type: ModelType,
+
+    /// String of the unknown piece.
+    pub unk_piece: String,
+
+    /// String of the beginning of sentence piece.
+    pub bos_piece: String,
+
+    /// String of the end of sentence piece.
+    pub eos_piece: String,
+
+    /// String of the padding piece.
+    pub pad_piece: String,
+
+    /// Treat pieces of the form `<0xNN>` as byte pieces.
+    pub byte_fallback: bool,
+
+    /// Add a dummy whitespace to the beginning of the input.
+    pub add_dummy_prefix: bool,
+
+    /// Remove leading, trailing, and duplicate internal whitespace.
+    pub remove_extra_whitespaces: bool,
+}
+
+impl Default for FromPartsOptions {
+    fn default() -> Self {
+        FromPartsOptions {
+            model_type: ModelType::Unigram,
+            unk_piece: "<unk>".to_string(),
+            bos_piece: "<s>".to_string(),
+            eos_piece: "</s>".to_string(),
+            pad_piece: "<pad>".to_string(),
+            byte_fallback: false,
+            add_dummy_prefix: true,
+            remove_extra_whitespaces: true,
+        }
+    }
+}
+
+impl FromPartsOptions {
+    pub(crate) fn to_builder(&self, pieces: &[(String, f32)]) -> ModelBuilder {
+        let piece_type = |piece: &str| {
+            if piece == self.unk_piece {
+                PieceType::Unknown
+            } else if piece == self.bos_piece || piece == self.eos_piece || piece == self.pad_piece
+            {
+                PieceType::Control
+            } else if self.byte_fallback && is_byte_piece(piece) {
+                PieceType::Byte
+            } else {
+                PieceType::Normal
+            }
+        };
+
+        ModelBuilder::new()
+            .model_type(self.model_type)
+            .unk_piece(self.unk_piece.clone())
+            .bos_piece(self.bos_piece.clone())
+            .eos_piece(self.eos_piece.clone())
+            .pad_piece(self.pad_piece.clone())
+            .add_dummy_prefix(self.add_dummy_prefix)
+            .remove_extra_whitespaces(self.remove_extra_whitespaces)
+            .pieces(
+                pieces
+                    .iter()
+                    .map(|(piece, score)| (piece.clone(), *score, piece_type(piece))),
+            )
+    }
+}
+
+/// Check whether a piece has the form of a byte piece (`<0xNN>`).
+pub(crate) fn is_byte_piece(piece: &str) -> bool {
+    piece.len() == 6
+        && piece.starts_with("<0x")
+        && piece.ends_with('>')
+        && piece[3..5]
+            .chars()
+            .all(|ch| ch.is_ascii_digit() || ('A'..='F').contains(&ch))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FromPartsOptions, ModelBuilder, PieceType, SentencePieceProcessor};
+
+    fn test_builder() -> ModelBuilder {
+        ModelBuilder::new()
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("<s>", 0.0, PieceType::Control)
+            .piece("</s>", 0.0, PieceType::Control)
+            .pieces(vec![
+                ("▁", -2.0, PieceType::Normal),
+                ("a", -3.0, PieceType::Normal),
+                ("b", -3.0, PieceType::Normal),
+                ("▁ab", -1.0, PieceType::Normal),
+            ])
+    }
+
+    #[test]
+    fn builds_model_with_special_pieces() {
+        let model = test_builder().build().unwrap();
+        assert_eq!(model.len(), 7);
+        assert_eq!(model.unk_id(), 0);
+        assert_eq!(model.bos_id(), Some(1));
+        assert_eq!(model.eos_id(), Some(2));
+        assert_eq!(model.pad_id(), None);
+    }
+
+    #[test]
+    fn built_model_encodes_and_decodes() {
+        let model = test_builder().build().unwrap();
+        let ids = model
+            .encode("ab ba")
+            .unwrap()
+            .into_iter()
+            .map(|piece| piece.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![6, 3, 5, 4]);
+        assert_eq!(model.decode_piece_ids(&ids).unwrap(), "ab ba");
+    }
+
+    #[test]
+    fn builds_model_from_parts() {
+        let pieces = ["<unk>", "<s>", "</s>", "<pad>", "▁", "a", "b", "▁ab"]
+            .iter()
+            .zip([0.0, 0.0, 0.0, 0.0, -2.0, -3.0, -3.0, -1.0])
+            .map(|(piece, score)| (piece.to_string(), score))
+            .collect::<Vec<_>>();
+        let model =
+            SentencePieceProcessor::from_parts(&pieces, &FromPartsOptions::default()).unwrap();
+        assert_eq!(model.len(), 8);
+        assert_eq!(model.unk_id(), 0);
+        assert_eq!(model.pad_id(), Some(3));
+        assert_eq!(model.piece_to_id("▁ab"), Ok(Some(7)));
+    }
+
+    #[test]
+    fn recognizes_byte_pieces() {
+        assert!(super::is_byte_piece("<0x0A>"));
+        assert!(!super::is_byte_piece("<0x0a>"));
+        assert!(!super::is_byte_piece("<0x0AB>"));
+        assert!(!super::is_byte_piece("<s>"));
+    }
+
+    #[test]
+    fn model_without_unknown_piece_fails() {
+        assert!(ModelBuilder::new()
+            .piece("a", 0.0, PieceType::Normal)
+            .build()
+            .is_err());
+    }
+}
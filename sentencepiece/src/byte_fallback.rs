@@ -0,0 +1,93 @@
+use crate::unknown::is_unknown;
+use crate::{PieceWithId, SentencePieceError};
+
+/// Split unknown pieces into byte pieces.
+///
+/// See [`EncodeOptions::unknown_bytes_base`](crate::EncodeOptions::unknown_bytes_base).
+pub(crate) fn split_unknown_bytes(
+    sentence: &str,
+    unk_id: u32,
+    base: u32,
+    pieces: Vec<PieceWithId>,
+) -> Result<Vec<PieceWithId>, SentencePieceError> {
+    if base.checked_add(u8::MAX as u32).is_none() {
+        return Err(SentencePieceError::InvalidPieceId(base));
+    }
+
+    let mut split = Vec::with_capacity(pieces.len());
+    for piece in pieces {
+        if !is_unknown(&piece, unk_id) {
+            split.push(piece);
+            continue;
+        }
+
+        let (begin, end) = piece.span;
+        let bytes = sentence
+            .as_bytes()
+            .get(begin as usize..end as usize)
+            .ok_or(SentencePieceError::InvalidSpan(begin, end))?;
+        split.extend(
+            bytes
+                .iter()
+                .zip(begin..)
+                .map(|(&byte, offset)| PieceWithId {
+                    piece: format!("<0x{:02X}>", byte),
+                    id: base + byte as u32,
+                    span: (offset, offset + 1),
+                }),
+        );
+    }
+
+    Ok(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::toy_model;
+    use crate::EncodeOptions;
+
+    #[test]
+    fn default_options_encode_unknown_piece() {
+        let model = toy_model();
+        assert_eq!(
+            model
+                .encode_with_options("I saw a ☃.", &EncodeOptions::default())
+                .unwrap(),
+            model.encode("I saw a ☃.").unwrap()
+        );
+    }
+
+    #[test]
+    fn splits_unknown_pieces_into_bytes() {
+        let model = toy_model();
+        let options = EncodeOptions {
+            unknown_bytes_base: Some(model.len() as u32),
+            ..Default::default()
+        };
+        let pieces = model.encode_with_options("I saw a ☃.", &options).unwrap();
+        assert!(pieces.iter().all(|piece| piece.id != model.unk_id()));
+
+        let bytes = pieces
+            .iter()
+            .filter(|piece| piece.id >= 1000)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bytes
+                .iter()
+                .map(|piece| piece.id - 1000)
+                .collect::<Vec<_>>(),
+            [0xe2, 0x98, 0x83]
+        );
+        assert_eq!(
+            bytes
+                .iter()
+                .map(|piece| piece.piece.as_str())
+                .collect::<Vec<_>>(),
+            ["<0xE2>", "<0x98>", "<0x83>"]
+        );
+        assert_eq!(
+            bytes.iter().map(|piece| piece.span).collect::<Vec<_>>(),
+            [(8, 9), (9, 10), (10, 11)]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use candle_core::{Device, Tensor};
+
+use crate::{BatchOptions, SentencePieceProcessor};
+
+impl SentencePieceProcessor {
+    /// Encode a batch of sentences as candle tensors.
+    ///
+    /// Returns the piece identifiers and the attention mask as `u32`
+    /// tensors with the shape *[batch_size, seq_len]* on `device`. The
+    /// identifiers are padded with the padding piece of the model, or
+    /// `0` if the model does not have a padding piece. Use
+    /// [`SentencePieceProcessor::encode_to_tensor_with_options`] to set
+    /// the padding identifier or maximum length.
+    pub fn encode_to_tensor(
+        &self,
+        sentences: &[impl AsRef<str>],
+        device: &Device,
+    ) -> candle_core::Result<(Tensor, Tensor)> {
+        self.encode_to_tensor_with_options(sentences, device, &BatchOptions::default())
+    }
+
+    /// Encode a batch of sentences as candle tensors with batch options.
+    ///
+    /// See [`SentencePieceProcessor::encode_to_tensor`], offsets are not
+    /// returned.
+    pub fn encode_to_tensor_with_options(
+        &self,
+        sentences: &[impl AsRef<str>],
+        device: &Device,
+        options: &BatchOptions,
+    ) -> candle_core::Result<(Tensor, Tensor)> {
+        let batch = self
+            .encode_batch(
+                sentences,
+                &BatchOptions {
+                    offsets: false,
+                    ..*options
+                },
+            )
+            .map_err(candle_core::Error::wrap)?;
+
+        let shape = (batch.batch_size, batch.seq_len);
+        Ok((
+            Tensor::from_vec(batch.ids, shape, device)?,
+            Tensor::from_vec(batch.attention_mask, shape, device)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::Device;
+
+    use crate::test_util::toy_model;
+    use crate::BatchOptions;
+
+    #[test]
+    fn encodes_batch_to_tensors() {
+        let model = toy_model();
+        let (ids, attention_mask) = model
+            .encode_to_tensor(&["I saw a girl.", "I saw"], &Device::Cpu)
+            .unwrap();
+
+        assert_eq!(ids.dims(), [2, 5]);
+        assert_eq!(
+            ids.to_vec2::<u32>().unwrap(),
+            [vec![8, 465, 10, 947, 4], vec![8, 465, 0, 0, 0]]
+        );
+        assert_eq!(
+            attention_mask.to_vec2::<u32>().unwrap(),
+            [vec![1, 1, 1, 1, 1], vec![1, 1, 0, 0, 0]]
+        );
+    }
+
+    #[test]
+    fn encodes_batch_to_tensors_with_options() {
+        let model = toy_model();
+        let (ids, _) = model
+            .encode_to_tensor_with_options(
+                &["I saw a girl.", "I saw"],
+                &Device::Cpu,
+                &BatchOptions {
+                    pad_id: Some(2),
+                    max_len: Some(3),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            ids.to_vec2::<u32>().unwrap(),
+            [vec![8, 465, 10], vec![8, 465, 2]]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use compact_str::CompactString;
+use smallvec::SmallVec;
+
+use crate::{PieceWithId, SentencePieceError, SentencePieceProcessor};
+
+/// Number of pieces or identifiers that compact encodings store inline.
+pub const COMPACT_INLINE_PIECES: usize = 64;
+
+/// Piece identifiers of an encoded sentence.
+///
+/// Up to [`COMPACT_INLINE_PIECES`] identifiers are stored without
+/// allocating memory.
+pub type CompactIds = SmallVec<[u32; COMPACT_INLINE_PIECES]>;
+
+/// Pieces of an encoded sentence.
+///
+/// Up to [`COMPACT_INLINE_PIECES`] pieces are stored without allocating
+/// memory.
+pub type CompactPieces = SmallVec<[CompactPieceWithId; COMPACT_INLINE_PIECES]>;
+
+/// Sentence piece with its identifier and string span.
+///
+/// In contrast to [`PieceWithId`], pieces of up to 24 bytes are stored
+/// inline, without allocating memory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompactPieceWithId {
+    /// The sentence piece as a string.
+    pub piece: CompactString,
+
+    /// The vocabulary identifier of the sentence piece.
+    pub id: u32,
+
+    /// The span of the sentence piece in the tokenized string.
+    ///
+    /// The span is encoded as the byte offsets *[begin, end)*.
+    pub span: (u32, u32),
+}
+
+impl From<PieceWithId> for CompactPieceWithId {
+    fn from(piece: PieceWithId) -> Self {
+        CompactPieceWithId {
+            piece: piece.piece.into(),
+            id: piece.id,
+            span: piece.span,
+        }
+    }
+}
+
+impl From<CompactPieceWithId> for PieceWithId {
+    fn from(piece: CompactPieceWithId) -> Self {
+        PieceWithId {
+            piece: piece.piece.into(),
+            id: piece.id,
+            span: piece.span,
+        }
+    }
+}
+
+impl SentencePieceProcessor {
+    /// Encode a sentence as compact sentence pieces.
+    ///
+    /// This is like [`SentencePieceProcessor::encode`], but short
+    /// pieces and the pieces of short sentences are stored inline, which
+    /// avoids most allocations when encoding short sentences.
+    pub fn encode_compact(&self, sentence: &str) -> Result<CompactPieces, SentencePieceError> {
+        self.encode_with(sentence, |piece, id, span| CompactPieceWithId {
+            piece: CompactString::new(piece),
+            id,
+            span,
+        })
+    }
+
+    /// Encode a sentence as compact piece identifiers.
+    ///
+    /// This is like [`SentencePieceProcessor::encode_as_ids`], but the
+    /// identifiers of short sentences are stored inline.
+    pub fn encode_as_ids_compact(&self, sentence: &str) -> Result<CompactIds, SentencePieceError> {
+        self.encode_ids_with(sentence, CompactIds::from_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::toy_model;
+    use crate::{CompactPieceWithId, PieceWithId};
+
+    #[test]
+    fn encodes_compact_like_encode() {
+        let model = toy_model();
+        for sentence in &["I saw a girl with a telescope.", "  ｆｕｌｌ  width 🤗", ""] {
+            let pieces = model.encode_compact(sentence).unwrap();
+            assert!(!pieces.spilled());
+            assert!(pieces.iter().all(|piece| !piece.piece.is_heap_allocated()));
+            assert_eq!(
+                pieces
+                    .into_iter()
+                    .map(PieceWithId::from)
+                    .collect::<Vec<_>>(),
+                model.encode(sentence).unwrap()
+            );
+
+            let ids = model.encode_as_ids_compact(sentence).unwrap();
+            assert!(!ids.spilled());
+            assert_eq!(ids.as_slice(), &*model.encode_as_ids(sentence).unwrap());
+        }
+    }
+
+    #[test]
+    fn spills_long_sentences() {
+        let model = toy_model();
+        let sentence = "I saw a girl with a telescope. ".repeat(10);
+        let pieces = model.encode_compact(&sentence).unwrap();
+        assert!(pieces.spilled());
+        assert_eq!(
+            pieces.as_slice(),
+            &*model
+                .encode(&sentence)
+                .unwrap()
+                .into_iter()
+                .map(CompactPieceWithId::from)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crate::{CSentencePieceError, PieceWithId, SentencePieceError, SentencePieceProcessor};
+
+type Job = Box<dyn FnOnce(&SentencePieceProcessor) + Send>;
+
+/// Processor that fails operations that exceed a deadline.
+///
+/// Operations are run on a fixed number of worker threads. The caller
+/// waits until the operation finishes or the deadline is exceeded,
+/// whichever comes first. In the latter case, a
+/// `SentencePieceError::CError` with the code
+/// `CSentencePieceError::DeadlineExceeded` is returned.
+///
+/// sentencepiece operations cannot be interrupted. An operation that
+/// exceeds its deadline while running keeps its worker busy until it
+/// finishes, its result is discarded. Operations that exceed their
+/// deadline before a worker picks them up are never started. The number
+/// of pending operations (queued or running) is limited. When the limit
+/// is reached, new operations fail immediately with `DeadlineExceeded`,
+/// so that abandoned operations cannot accumulate without bounds.
+pub struct DeadlineProcessor {
+    spp: Arc<SentencePieceProcessor>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    available: Condvar,
+}
+
+struct State {
+    jobs: VecDeque<(Instant, Job)>,
+    max_pending: usize,
+    pending: usize,
+    shutdown: bool,
+}
+
+impl DeadlineProcessor {
+    /// Construct a deadline processor.
+    ///
+    /// Operations are run on `n_workers` threads. At most `max_pending`
+    /// operations can be queued or running at the same time. Returns
+    /// `SentencePieceError::InvalidArgument` when `n_workers` is `0` or
+    /// `max_pending` is smaller than `n_workers`.
+    pub fn new(
+        spp: Arc<SentencePieceProcessor>,
+        n_workers: usize,
+        max_pending: usize,
+    ) -> Result<Self, SentencePieceError> {
+        if n_workers == 0 {
+            return Err(SentencePieceError::InvalidArgument(
+                "number of workers must be at least 1".to_string(),
+            ));
+        }
+        if max_pending < n_workers {
+            return Err(SentencePieceError::InvalidArgument(format!(
+                "maximum number of pending operations must be at least {}",
+                n_workers
+            )));
+        }
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                jobs: VecDeque::new(),
+                max_pending,
+                pending: 0,
+                shutdown: false,
+            }),
+            available: Condvar::new(),
+        });
+
+        for _ in 0..n_workers {
+            let spp = spp.clone();
+            let shared = shared.clone();
+            thread::spawn(move || shared.work(&spp));
+        }
+
+        Ok(DeadlineProcessor { spp, shared })
+    }
+
+    /// Encode a sentence, failing when the deadline is exceeded.
+    ///
+    /// See [`SentencePieceProcessor::encode`].
+    pub fn encode(
+        &self,
+        sentence: &str,
+        deadline: Instant,
+    ) -> Result<Vec<PieceWithId>, SentencePieceError> {
+        let sentence = sentence.to_owned();
+        self.with_deadline(deadline, move |spp| spp.encode(&sentence))
+    }
+
+    /// Get the `n_best` segmentations, failing when the deadline is exceeded.
+    ///
+    /// See [`SentencePieceProcessor::nbest_encode`].
+    pub fn nbest_encode(
+        &self,
+        sentence: &str,
+        n_best: usize,
+        deadline: Instant,
+    ) -> Result<Vec<(Vec<PieceWithId>, f32)>, SentencePieceError> {
+        let sentence = sentence.to_owned();
+        self.with_deadline(deadline, move |spp| spp.nbest_encode(&sentence, n_best))
+    }
+
+    /// Get the wrapped processor.
+    pub fn processor(&self) -> &SentencePieceProcessor {
+        &self.spp
+    }
+
+    /// Encode a sentence using sampling, failing when the deadline is exceeded.
+    ///
+    /// See [`SentencePieceProcessor::sample_encode`] for the sampling
+    /// parameters.
+    pub fn sample_encode(
+        &self,
+        sentence: &str,
+        n_best: usize,
+        alpha: f32,
+        deadline: Instant,
+    ) -> Result<Vec<PieceWithId>, SentencePieceError> {
+        let sentence = sentence.to_owned();
+        self.with_deadline(deadline, move |spp| {
+            spp.sample_encode(&sentence, n_best, alpha)
+        })
+    }
+
+    /// Run an operation on the processor, failing when the deadline is exceeded.
+    ///
+    /// If the operation panics, the panic is resumed in the calling thread.
+    pub fn with_deadline<T, F>(&self, deadline: Instant, f: F) -> Result<T, SentencePieceError>
+    where
+        F: FnOnce(&SentencePieceProcessor) -> Result<T, SentencePieceError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(deadline_exceeded(
+                "deadline exceeded before the operation started",
+            ));
+        }
+
+        let (sender, receiver) = mpsc::channel::<Result<_, Box<dyn Any + Send>>>();
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            if state.pending == state.max_pending {
+                return Err(deadline_exceeded("too many pending operations"));
+            }
+            state.pending += 1;
+            state.jobs.push_back((
+                deadline,
+                Box::new(move |spp: &SentencePieceProcessor| {
+                    // Fails when the deadline was exceeded, which is fine.
+                    let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(|| f(spp))));
+                }),
+            ));
+        }
+        self.shared.available.notify_one();
+
+        match receiver.recv_timeout(deadline - now) {
+            Ok(Ok(result)) => result,
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            // The sender is dropped without sending when the deadline
+            // was exceeded before the operation was started.
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => Err(
+                deadline_exceeded("deadline exceeded before the operation finished"),
+            ),
+        }
+    }
+}
+
+impl Drop for DeadlineProcessor {
+    fn drop(&mut self) {
+        // Workers finish their current operation and then stop. They are
+        // not joined, since an abandoned operation can run for long.
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.available.notify_all();
+    }
+}
+
+impl Shared {
+    fn work(&self, spp: &SentencePieceProcessor) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.shutdown {
+                return;
+            }
+
+            let (deadline, job) = match state.jobs.pop_front() {
+                Some(job) => job,
+                None => {
+                    state = self.available.wait(state).unwrap();
+                    continue;
+                }
+            };
+            drop(state);
+
+            // Skip operations whose caller does not wait anymore.
+            if Instant::now() < deadline {
+                job(spp);
+            }
+
+            state = self.state.lock().unwrap();
+            state.pending -= 1;
+        }
+    }
+}
+
+fn deadline_exceeded(message: &str) -> SentencePieceError {
+    SentencePieceError::CError {
+        code: CSentencePieceError::DeadlineExceeded,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::test_util::toy_model;
+    use crate::{CSentencePieceError, DeadlineProcessor, SentencePieceError};
+
+    fn is_deadline_exceeded<T>(result: Result<T, SentencePieceError>) -> bool {
+        matches!(
+            result,
+            Err(SentencePieceError::CError {
+                code: CSentencePieceError::DeadlineExceeded,
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn encodes_before_deadline() {
+        let model = Arc::new(toy_model());
+        let processor = DeadlineProcessor::new(model.clone(), 2, 4).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(
+            processor.encode("I saw a girl.", deadline).unwrap(),
+            model.encode("I saw a girl.").unwrap()
+        );
+        assert_eq!(
+            processor
+                .nbest_encode("I saw a girl.", 2, deadline)
+                .unwrap(),
+            model.nbest_encode("I saw a girl.", 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn fails_after_deadline() {
+        let processor = DeadlineProcessor::new(Arc::new(toy_model()), 1, 2).unwrap();
+        assert!(is_deadline_exceeded(
+            processor.encode("I saw a girl.", Instant::now())
+        ));
+
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert!(is_deadline_exceeded(processor.with_deadline(
+            deadline,
+            |spp| {
+                std::thread::sleep(Duration::from_millis(500));
+                spp.encode("I saw a girl.")
+            }
+        )));
+    }
+
+    #[test]
+    fn limits_pending_operations() {
+        let processor = DeadlineProcessor::new(Arc::new(toy_model()), 1, 1).unwrap();
+
+        // Occupies the only worker after the deadline is exceeded.
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert!(is_deadline_exceeded(processor.with_deadline(
+            deadline,
+            |spp| {
+                std::thread::sleep(Duration::from_millis(500));
+                spp.encode("I saw a girl.")
+            }
+        )));
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        match processor.encode("I saw a girl.", deadline) {
+            Err(SentencePieceError::CError {
+                code: CSentencePieceError::DeadlineExceeded,
+                message,
+            }) => assert_eq!(message, "too many pending operations"),
+            result => panic!("Expected deadline exceeded error, got: {:?}", result),
+        }
+
+        // The worker becomes available when the abandoned operation finishes.
+        std::thread::sleep(Duration::from_millis(700));
+        assert!(processor.encode("I saw a girl.", deadline).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        assert!(matches!(
+            DeadlineProcessor::new(Arc::new(toy_model()), 0, 1),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            DeadlineProcessor::new(Arc::new(toy_model()), 2, 1),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,160 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{PieceWithId, SentencePieceError};
+
+/// Encoded sentence.
+///
+/// An encoding wraps the pieces that `SentencePieceProcessor::encode`
+/// returns and provides helpers that relate the pieces to the
+/// original input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Encoding {
+    /// The sentence pieces of the encoded sentence.
+    pub pieces: Vec<PieceWithId>,
+}
+
+impl Encoding {
+    /// Get the spans of the pieces as character offsets.
+    ///
+    /// The spans of the pieces are byte offsets. This method converts
+    /// them to offsets in Unicode scalar values, encoded as
+    /// *[begin, end)*. `input` must be the sentence that was encoded. An
+    /// error is returned when a span does not fall on character
+    /// boundaries of `input` or exceeds its length.
+    pub fn char_spans(&self, input: &str) -> Result<Vec<(u32, u32)>, SentencePieceError> {
+        // Character offsets of byte offsets, `None` inside characters.
+        let mut char_offsets = vec![None; input.len() + 1];
+        let mut n_chars = 0;
+        for (byte_offset, _) in input.char_indices() {
+            char_offsets[byte_offset] = Some(n_chars);
+            n_chars += 1;
+        }
+        char_offsets[input.len()] = Some(n_chars);
+
+        self.pieces
+            .iter()
+            .map(|piece| {
+                let (begin, end) = piece.span;
+                let char_offset =
+                    |offset: u32| char_offsets.get(offset as usize).copied().flatten();
+                char_offset(begin)
+                    .zip(char_offset(end))
+                    .ok_or(SentencePieceError::InvalidSpan(begin, end))
+            })
+            .collect()
+    }
+
+    /// Get the input substrings that correspond to the pieces.
+    ///
+    /// `input` must be the sentence that was encoded. Pieces that do not
+    /// cover any input, such as the dummy prefix piece `▁`, map to the
+    /// empty string. An error is returned when a span does not fall on
+    /// character boundaries of `input` or exceeds its length.
+    pub fn surfaces<'a>(&self, input: &'a str) -> Result<Vec<&'a str>, SentencePieceError> {
+        self.pieces
+            .iter()
+            .map(|piece| {
+                let (begin, end) = piece.span;
+                input
+                    .get(begin as usize..end as usize)
+                    .ok_or(SentencePieceError::InvalidSpan(begin, end))
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<PieceWithId>> for Encoding {
+    fn from(pieces: Vec<PieceWithId>) -> Self {
+        Encoding { pieces }
+    }
+}
+
+impl From<Encoding> for Vec<PieceWithId> {
+    fn from(encoding: Encoding) -> Self {
+        encoding.pieces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::toy_model;
+    use crate::{Encoding, PieceWithId, SentencePieceError};
+
+    #[test]
+    fn surfaces_slice_input() {
+        let model = toy_model();
+        let sentence = "I saw a girl with a telescope.";
+        let encoding = Encoding::from(model.encode(sentence).unwrap());
+        assert_eq!(
+            encoding.surfaces(sentence).unwrap(),
+            vec!["I", " saw", " a", " girl", " with", " a", " t", "el", "es", "c", "o", "pe", "."]
+        );
+    }
+
+    #[test]
+    fn converts_spans_to_char_offsets() {
+        let model = toy_model();
+        let sentence = "Ö saw a girl.";
+        let encoding = Encoding::from(model.encode(sentence).unwrap());
+        let char_spans = encoding.char_spans(sentence).unwrap();
+        assert_eq!(char_spans.last(), Some(&(12, 13)));
+        assert_eq!(
+            char_spans,
+            encoding
+                .pieces
+                .iter()
+                .map(|piece| {
+                    let (begin, end) = piece.span;
+                    let begin = sentence[..begin as usize].chars().count() as u32;
+                    let end = sentence[..end as usize].chars().count() as u32;
+                    (begin, end)
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn char_spans_reject_spans_inside_characters() {
+        let encoding = Encoding::from(vec![PieceWithId {
+            piece: "ö".to_string(),
+            id: 0,
+            span: (0, 1),
+        }]);
+        assert_eq!(
+            encoding.char_spans("ö"),
+            Err(SentencePieceError::InvalidSpan(0, 1))
+        );
+    }
+
+    #[test]
+    fn surfaces_handle_empty_spans() {
+        let encoding = Encoding::from(vec![
+            PieceWithId {
+                piece: "▁".to_string(),
+                id: 7,
+                span: (0, 0),
+            },
+            PieceWithId {
+                piece: "ö".to_string(),
+                id: 0,
+                span: (0, 2),
+            },
+        ]);
+        assert_eq!(encoding.surfaces("ö").unwrap(), vec!["", "ö"]);
+    }
+
+    #[test]
+    fn surfaces_reject_spans_inside_characters() {
+        let encoding = Encoding::from(vec![PieceWithId {
+            piece: "ö".to_string(),
+            id: 0,
+            span: (0, 1),
+        }]);
+        assert_eq!(
+            encoding.surfaces("ö"),
+            Err(SentencePieceError::InvalidSpan(0, 1))
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::thread;
+
+use crate::{IoError, SentencePieceProcessor};
+
+/// A corpus to evaluate a model on.
+///
+/// The corpus is read from a text file with one sentence per line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Corpus {
+    name: String,
+    language: Option<String>,
+    path: PathBuf,
+}
+
+impl Corpus {
+    /// Construct a corpus with the given name from a text file.
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Corpus {
+            name: name.into(),
+            language: None,
+            path: path.into(),
+        }
+    }
+
+    /// Tag the corpus with a language.
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+}
+
+/// Distribution of sentence lengths in pieces.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LengthDistribution {
+    histogram: BTreeMap<usize, usize>,
+}
+
+impl LengthDistribution {
+    /// Add a sentence length to the distribution.
+    pub fn add(&mut self, len: usize) {
+        *self.histogram.entry(len).or_insert(0) += 1;
+    }
+
+    /// Get the number of sentences for each length.
+    pub fn histogram(&self) -> &BTreeMap<usize, usize> {
+        &self.histogram
+    }
+
+    /// Get the length of the longest sentence.
+    pub fn max(&self) -> Option<usize> {
+        self.histogram.keys().next_back().copied()
+    }
+
+    /// Get the mean sentence length.
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let total: usize = self.histogram.iter().map(|(len, n)| len * n).sum();
+        Some(total as f64 / count as f64)
+    }
+
+    /// Merge the lengths of another distribution into this distribution.
+    pub fn merge(&mut self, other: &LengthDistribution) {
+        for (&len, &n) in &other.histogram {
+            *self.histogram.entry(len).or_insert(0) += n;
+        }
+    }
+
+    /// Get the length of the shortest sentence.
+    pub fn min(&self) -> Option<usize> {
+        self.histogram.keys().next().copied()
+    }
+
+    /// Get the length at the given percentile (nearest-rank).
+    ///
+    /// `percentile` is clamped to *[0, 100]*.
+    pub fn percentile(&self, percentile: f64) -> Option<usize> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((percentile.clamp(0., 100.) / 100.) * count as f64).ceil() as usize;
+        let rank = rank.max(1);
+
+        let mut seen = 0;
+        for (&len, &n) in &self.histogram {
+            seen += n;
+            if seen >= rank {
+                return Some(len);
+            }
+        }
+
+        self.max()
+    }
+
+    fn count(&self) -> usize {
+        self.histogram.values().sum()
+    }
+}
+
+/// Evaluation statistics of a corpus.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CorpusReport {
+    /// Name of the corpus.
+    pub name: String,
+
+    /// Language of the corpus.
+    pub language: Option<String>,
+
+    /// Number of non-empty sentences.
+    pub sentences: usize,
+
+    /// Number of whitespace-separated words.
+    pub words: usize,
+
+    /// Number of characters (Unicode scalar values).
+    pub chars: usize,
+
+    /// Number of pieces.
+    pub pieces: usize,
+
+    /// Number of unknown pieces.
+    pub unknown_pieces: usize,
+
+    /// Distribution of sentence lengths in pieces.
+    pub lengths: LengthDistribution,
+}
+
+impl CorpusReport {
+    fn new(name: impl Into<String>, language: Option<String>) -> Self {
+        CorpusReport {
+            name: name.into(),
+            language,
+            ..Default::default()
+        }
+    }
+
+    /// Average number of characters per piece.
+    pub fn chars_per_piece(&self) -> f64 {
+        ratio(self.chars, self.pieces)
+    }
+
+    /// Average number of pieces per word.
+    pub fn fertility(&self) -> f64 {
+        ratio(self.pieces, self.words)
+    }
+
+    /// Merge the statistics of another report into this report.
+    pub fn merge(&mut self, other: &CorpusReport) {
+        self.sentences += other.sentences;
+        self.words += other.words;
+        self.chars += other.chars;
+        self.pieces += other.pieces;
+        self.unknown_pieces += other.unknown_pieces;
+        self.lengths.merge(&other.lengths);
+    }
+
+    /// Fraction of pieces that are unknown.
+    pub fn unknown_rate(&self) -> f64 {
+        ratio(self.unknown_pieces, self.pieces)
+    }
+
+    fn add_sentence(
+        &mut self,
+        spp: &SentencePieceProcessor,
+        unk_id: u32,
+        sentence: &str,
+    ) -> Result<(), IoError> {
+        let pieces = spp.encode(sentence)?;
+
+        self.sentences += 1;
+        self.words += sentence.split_whitespace().count();
+        self.chars += sentence.chars().count();
+        self.pieces += pieces.len();
+        self.unknown_pieces += pieces.iter().filter(|piece| piece.id == unk_id).count();
+        self.lengths.add(pieces.len());
+
+        Ok(())
+    }
+}
+
+/// Evaluation report of multiple corpora.
+///
+/// The `Display` implementation formats the report as a tab-separated
+/// table with one row per corpus and a final row with the totals.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EvaluationReport {
+    /// Reports of the individual corpora.
+    pub corpora: Vec<CorpusReport>,
+}
+
+impl EvaluationReport {
+    /// Combine the reports of corpora with the same language.
+    ///
+    /// The reports are named after their language, corpora without a
+    /// language are combined in a report with an empty name.
+    pub fn by_language(&self) -> Vec<CorpusReport> {
+        let mut by_language: BTreeMap<Option<&str>, CorpusReport> = BTreeMap::new();
+        for report in &self.corpora {
+            let language = report.language.as_deref();
+            by_language
+                .entry(language)
+                .or_insert_with(|| {
+                    CorpusReport::new(
+                        language.unwrap_or_default(),
+                        language.map(ToOwned::to_owned),
+                    )
+                })
+                .merge(report);
+        }
+
+        by_language.into_values().collect()
+    }
+
+    /// Combine the reports of all corpora.
+    pub fn total(&self) -> CorpusReport {
+        let mut total = CorpusReport::new("total", None);
+        for report in &self.corpora {
+            total.merge(report);
+        }
+        total
+    }
+}
+
+impl fmt::Display for EvaluationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "corpus\tlanguage\tsentences\twords\tpieces\tfertility\tunk_rate\tmean_len\tmedian_len\tp90_len\tmax_len"
+        )?;
+
+        for report in self.corpora.iter().chain(Some(&self.total())) {
+            writeln!(
+                f,
+                "{}\t{}\t{}\t{}\t{}\t{:.4}\t{:.6}\t{:.2}\t{}\t{}\t{}",
+                report.name,
+                report.language.as_deref().unwrap_or("-"),
+                report.sentences,
+                report.words,
+                report.pieces,
+                report.fertility(),
+                report.unknown_rate(),
+                report.lengths.mean().unwrap_or(0.),
+                report.lengths.percentile(50.).unwrap_or(0),
+                report.lengths.percentile(90.).unwrap_or(0),
+                report.lengths.max().unwrap_or(0),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Evaluate a model on several corpora.
+///
+/// Every corpus is tokenized in a separate thread. Empty lines are
+/// skipped.
+pub fn evaluate(
+    spp: &SentencePieceProcessor,
+    corpora: &[Corpus],
+) -> Result<EvaluationReport, IoError> {
+    let unk_id = spp.unk_id();
+
+    let reports = thread::scope(|scope| {
+        let handles = corpora
+            .iter()
+            .map(|corpus| scope.spawn(move || evaluate_corpus(spp, unk_id, corpus)))
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Evaluation thread panicked"))
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+
+    Ok(EvaluationReport { corpora: reports })
+}
+
+fn evaluate_corpus(
+    spp: &SentencePieceProcessor,
+    unk_id: u32,
+    corpus: &Corpus,
+) -> Result<CorpusReport, IoError> {
+    let read_error = |error| IoError::Io {
+        desc: format!("Cannot read corpus {}", corpus.path.display()),
+        error,
+    };
+
+    let reader = BufReader::new(File::open(&corpus.path).map_err(read_error)?);
+
+    let mut report = CorpusReport::new(corpus.name.clone(), corpus.language.clone());
+    for line in reader.lines() {
+        let line = line.map_err(read_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        report.add_sentence(spp, unk_id, &line)?;
+    }
+
+    Ok(report)
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::test_util::toy_model;
+    use crate::{evaluate, Corpus, LengthDistribution};
+
+    #[test]
+    fn evaluates_corpora() {
+        let path = env::temp_dir().join(format!("sentencepiece-eval-{}.txt", process::id()));
+        fs::write(&path, "I saw a girl with a telescope.\n\nI saw a girl.\n").unwrap();
+
+        let model = toy_model();
+        let report = evaluate(
+            &model,
+            &[
+                Corpus::new("first", &path).with_language("en"),
+                Corpus::new("second", &path).with_language("en"),
+            ],
+        )
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(report.corpora.len(), 2);
+        let corpus = &report.corpora[0];
+        assert_eq!(corpus.sentences, 2);
+        assert_eq!(corpus.words, 11);
+        assert_eq!(corpus.pieces, 18);
+        assert_eq!(corpus.unknown_pieces, 0);
+        assert_eq!(corpus.lengths.max(), Some(13));
+
+        let by_language = report.by_language();
+        assert_eq!(by_language.len(), 1);
+        assert_eq!(by_language[0].sentences, 4);
+        assert_eq!(report.total().pieces, 36);
+    }
+
+    #[test]
+    fn fails_on_missing_corpus() {
+        let model = toy_model();
+        assert!(evaluate(&model, &[Corpus::new("missing", "non-existing")]).is_err());
+    }
+
+    #[test]
+    fn length_distribution_percentiles() {
+        let mut lengths = LengthDistribution::default();
+        for len in 1..=10 {
+            lengths.add(len);
+        }
+
+        assert_eq!(lengths.min(), Some(1));
+        assert_eq!(lengths.max(), Some(10));
+        assert_eq!(lengths.mean(), Some(5.5));
+        assert_eq!(lengths.percentile(50.), Some(5));
+        assert_eq!(lengths.percentile(90.), Some(9));
+        assert_eq!(LengthDistribution::default().percentile(50.), None);
+    }
+}
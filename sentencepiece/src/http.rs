@@ -0,0 +1,175 @@
+use std::io::{self, Read};
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+
+use crate::{IoError, SentencePieceProcessor};
+
+/// Timeout for connecting to the server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Timeout for individual reads of the response.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum size of a downloaded model (1 GiB).
+const MAX_MODEL_LEN: u64 = 1 << 30;
+
+impl SentencePieceProcessor {
+    /// Download and load a model.
+    ///
+    /// The model is downloaded with a blocking HTTP(S) request. The
+    /// download fails when connecting takes longer than 30 seconds,
+    /// when the server does not send data for 60 seconds, or when the
+    /// model is larger than 1 GiB.
+    pub fn open_url(url: &str) -> Result<Self, IoError> {
+        Ok(Self::from_serialized_proto(&download(url, MAX_MODEL_LEN)?)?)
+    }
+
+    /// Download and load a model, verifying its SHA-256 checksum.
+    ///
+    /// `sha256` is the expected checksum as a hexadecimal string. An
+    /// `IoError::Format` error is returned when the checksum of the
+    /// downloaded model does not match. The download is limited in the
+    /// same way as in [`SentencePieceProcessor::open_url`].
+    pub fn open_url_with_sha256(url: &str, sha256: &str) -> Result<Self, IoError> {
+        let data = download(url, MAX_MODEL_LEN)?;
+        verify_sha256(&data, sha256)?;
+        Ok(Self::from_serialized_proto(&data)?)
+    }
+}
+
+fn download(url: &str, max_len: u64) -> Result<Vec<u8>, IoError> {
+    let download_error = |error| IoError::Io {
+        desc: format!("Cannot download model {}", url),
+        error,
+    };
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|err| download_error(io::Error::new(io::ErrorKind::Other, err)))?;
+
+    // Read one byte more than the maximum to detect larger models.
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .take(max_len + 1)
+        .read_to_end(&mut data)
+        .map_err(download_error)?;
+
+    if data.len() as u64 > max_len {
+        return Err(IoError::Format(format!(
+            "Model {} is larger than {} bytes",
+            url, max_len
+        )));
+    }
+
+    Ok(data)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn verify_sha256(data: &[u8], sha256: &str) -> Result<(), IoError> {
+    let checksum = sha256_hex(data);
+    if checksum.eq_ignore_ascii_case(sha256.trim()) {
+        Ok(())
+    } else {
+        Err(IoError::Format(format!(
+            "Model checksum {} does not match expected checksum {}",
+            checksum, sha256
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::{download, sha256_hex, verify_sha256};
+    use crate::{IoError, SentencePieceProcessor};
+
+    const TOY_MODEL: &[u8] = include_bytes!("../testdata/toy.model");
+
+    /// Serve `body` to the given number of requests, returning the URL.
+    fn serve(body: &'static [u8], n_requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/toy.model", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming().take(n_requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn verifies_sha256() {
+        let checksum = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_sha256(b"abc", checksum).is_ok());
+        assert!(verify_sha256(b"abc", &checksum.to_uppercase()).is_ok());
+        assert!(matches!(
+            verify_sha256(b"abd", checksum),
+            Err(IoError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn opens_model_from_url() {
+        let url = serve(TOY_MODEL, 3);
+
+        let model = SentencePieceProcessor::open_url(&url).unwrap();
+        assert_eq!(model.len(), 1000);
+
+        let checksum = sha256_hex(TOY_MODEL);
+        assert!(SentencePieceProcessor::open_url_with_sha256(&url, &checksum).is_ok());
+        assert!(matches!(
+            SentencePieceProcessor::open_url_with_sha256(&url, &"0".repeat(64)),
+            Err(IoError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn limits_model_size() {
+        let url = serve(TOY_MODEL, 2);
+        assert!(matches!(
+            download(&url, TOY_MODEL.len() as u64 - 1),
+            Err(IoError::Format(_))
+        ));
+        assert_eq!(download(&url, TOY_MODEL.len() as u64).unwrap(), TOY_MODEL);
+    }
+
+    #[test]
+    fn reports_download_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/toy.model", listener.local_addr().unwrap());
+        drop(listener);
+
+        assert!(matches!(
+            SentencePieceProcessor::open_url(&url),
+            Err(IoError::Io { .. })
+        ));
+    }
+}
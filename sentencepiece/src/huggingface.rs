@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::builder::is_byte_piece;
+use crate::json::{self, Value};
+use crate::model::model_proto::sentence_piece::Type;
+use crate::model::ModelProto;
+use crate::{
+    IoError, ModelBuilder, ModelType, PieceType, SentencePieceError, SentencePieceProcessor,
+};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl SentencePieceProcessor {
+    /// Read a unigram model from a HuggingFace `tokenizer.json`.
+    ///
+    /// This supports tokenizers that were converted from sentencepiece
+    /// unigram models, which consist of a `Unigram` model and a
+    /// `Metaspace` pre-tokenizer. The normalization rules are read from
+    /// the `Precompiled` normalizer and extra whitespace is removed when
+    /// the normalizer replaces runs of spaces. Special added tokens
+    /// become control pieces, other added tokens become user-defined
+    /// pieces.
+    pub fn read_tokenizer_json(mut reader: impl Read) -> Result<Self, IoError> {
+        let mut json = String::new();
+        reader
+            .read_to_string(&mut json)
+            .map_err(|error| IoError::Io {
+                desc: "Cannot read tokenizer.json".to_string(),
+                error,
+            })?;
+
+        let tokenizer = json::parse(&json)
+            .map_err(|err| IoError::Format(format!("Invalid tokenizer.json: {}", err)))?;
+
+        Ok(Self::from_model_proto(&tokenizer_model_proto(&tokenizer)?)?)
+    }
+
+    /// Write a unigram model as a HuggingFace `tokenizer.json`.
+    ///
+    /// The tokenizer consists of a `Unigram` model with the pieces and
+    /// their scores, a `Precompiled` normalizer with the character map
+    /// of the model, and a `Metaspace` pre-tokenizer and decoder. The
+    /// unknown, control, and user-defined pieces are written as added
+    /// tokens, so that they are not split by the tokenizer.
+    ///
+    /// Returns `SentencePieceError::UnsupportedModelType` if the model
+    /// is not a unigram model and `SentencePieceError::InvalidArgument`
+    /// if the whitespace handling of the model cannot be expressed by
+    /// the `Metaspace` pre-tokenizer or if a piece has a score that is
+    /// not finite, which cannot be represented in JSON.
+    pub fn write_tokenizer_json(&self, mut writer: impl Write) -> Result<(), IoError> {
+        let model_type = self.model_type();
+        if model_type != ModelType::Unigram {
+            return Err(SentencePieceError::UnsupportedModelType(model_type).into());
+        }
+
+        if !self.escape_whitespaces() || self.treat_whitespace_as_suffix() {
+            return Err(SentencePieceError::InvalidArgument(
+                "tokenizer.json only supports whitespace that is escaped as a prefix".to_string(),
+            )
+            .into());
+        }
+
+        let model = self.model_proto()?;
+        if let Some(piece) = model.pieces.iter().find(|piece| !piece.score().is_finite()) {
+            return Err(SentencePieceError::InvalidArgument(format!(
+                "tokenizer.json does not support the score {} of piece {}",
+                piece.score(),
+                piece.piece()
+            ))
+            .into());
+        }
+
+        self.write_tokenizer(&model, &mut writer)
+            .map_err(|error| IoError::Io {
+                desc: "Cannot write tokenizer.json".to_string(),
+                error,
+            })
+    }
+
+    fn write_tokenizer(&self, model: &ModelProto, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(b"{\n  \"version\": \"1.0\",\n")?;
+        writer.write_all(b"  \"truncation\": null,\n  \"padding\": null,\n")?;
+
+        writer.write_all(b"  \"added_tokens\": [")?;
+        let added_tokens = model
+            .pieces
+            .iter()
+            .enumerate()
+            .filter(|(_, piece)| {
+                matches!(
+                    piece.r#type(),
+                    Type::Unknown | Type::Control | Type::UserDefined
+                )
+            })
+            .collect::<Vec<_>>();
+        for (idx, (id, piece)) in added_tokens.iter().enumerate() {
+            writer.write_all(if idx == 0 { b"\n    " } else { b",\n    " })?;
+            write!(writer, "{{\"id\": {}, \"content\": ", id)?;
+            json::write_string(writer, piece.piece())?;
+            write!(
+                writer,
+                ", \"single_word\": false, \"lstrip\": false, \"rstrip\": false, \
+                 \"normalized\": false, \"special\": {}}}",
+                piece.r#type() != Type::UserDefined
+            )?;
+        }
+        writer.write_all(b"\n  ],\n")?;
+
+        self.write_normalizer_json(writer)?;
+
+        let prepend_scheme = if self.add_dummy_prefix() {
+            "always"
+        } else {
+            "never"
+        };
+        // Older versions of tokenizers require add_prefix_space, newer
+        // versions use prepend_scheme.
+        for key in &["pre_tokenizer", "decoder"] {
+            writeln!(
+                writer,
+                "  \"{}\": {{\"type\": \"Metaspace\", \"replacement\": \"▁\", \
+                 \"add_prefix_space\": {}, \"prepend_scheme\": \"{}\", \"split\": true}},",
+                key,
+                self.add_dummy_prefix(),
+                prepend_scheme
+            )?;
+        }
+
+        writer.write_all(b"  \"post_processor\": null,\n")?;
+
+        write!(
+            writer,
+            "  \"model\": {{\n    \"type\": \"Unigram\",\n    \"unk_id\": {},\n    \"vocab\": [",
+            self.unk_id()
+        )?;
+        for (id, piece) in model.pieces.iter().enumerate() {
+            writer.write_all(if id == 0 { b"\n      [" } else { b",\n      [" })?;
+            json::write_string(writer, piece.piece())?;
+            write!(writer, ", {:?}]", piece.score())?;
+        }
+        writeln!(
+            writer,
+            "\n    ],\n    \"byte_fallback\": {}\n  }}\n}}",
+            self.byte_fallback()
+        )?;
+
+        writer.flush()
+    }
+
+    fn write_normalizer_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut normalizers = Vec::new();
+
+        let charsmap = self.precompiled_charsmap();
+        if !charsmap.is_empty() {
+            normalizers.push(format!(
+                "{{\"type\": \"Precompiled\", \"precompiled_charsmap\": \"{}\"}}",
+                base64_encode(&charsmap)
+            ));
+        }
+
+        if self.remove_extra_whitespaces() {
+            normalizers.push(
+                "{\"type\": \"Strip\", \"strip_left\": true, \"strip_right\": true}".to_string(),
+            );
+            normalizers.push(
+                "{\"type\": \"Replace\", \"pattern\": {\"Regex\": \" {2,}\"}, \"content\": \" \"}"
+                    .to_string(),
+            );
+        }
+
+        if normalizers.is_empty() {
+            writer.write_all(b"  \"normalizer\": null,\n")
+        } else {
+            writeln!(
+                writer,
+                "  \"normalizer\": {{\"type\": \"Sequence\", \"normalizers\": [\n    {}\n  ]}},",
+                normalizers.join(",\n    ")
+            )
+        }
+    }
+}
+
+/// Synthesize a model from a parsed `tokenizer.json`.
+fn tokenizer_model_proto(tokenizer: &Value) -> Result<ModelProto, IoError> {
+    let unsupported =
+        |message: &str| IoError::Format(format!("Unsupported tokenizer.json: {}", message));
+
+    let model = tokenizer
+        .get("model")
+        .ok_or_else(|| unsupported("missing model"))?;
+    if component_type(model) != Some("Unigram") {
+        return Err(unsupported("model is not a Unigram model"));
+    }
+
+    let mut vocab = model
+        .get("vocab")
+        .and_then(Value::as_array)
+        .ok_or_else(|| unsupported("missing vocabulary"))?
+        .iter()
+        .map(|entry| match entry.as_array() {
+            Some([piece, score]) => Some((piece.as_str()?.to_owned(), score.as_f64()? as f32)),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| unsupported("vocabulary entry is not a piece with a score"))?;
+
+    let unk_id = model
+        .get("unk_id")
+        .and_then(Value::as_f64)
+        .map(|id| id as usize)
+        .filter(|&id| id < vocab.len())
+        .ok_or_else(|| unsupported("missing unknown piece"))?;
+    let byte_fallback = model
+        .get("byte_fallback")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
+    // Map the identifiers of added tokens to their content and whether
+    // they are special.
+    let added_tokens = tokenizer
+        .get("added_tokens")
+        .and_then(Value::as_array)
+        .unwrap_or(&[])
+        .iter()
+        .map(|token| {
+            Some((
+                token.get("id")?.as_f64()? as usize,
+                (
+                    token.get("content")?.as_str()?,
+                    token
+                        .get("special")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false),
+                ),
+            ))
+        })
+        .collect::<Option<HashMap<_, _>>>()
+        .ok_or_else(|| unsupported("added token without identifier or content"))?;
+
+    // Added tokens that are not in the vocabulary are appended to it, so
+    // their identifiers must follow the vocabulary without gaps.
+    let mut appended_ids = added_tokens
+        .keys()
+        .copied()
+        .filter(|&id| id >= vocab.len())
+        .collect::<Vec<_>>();
+    appended_ids.sort_unstable();
+    for id in appended_ids {
+        if id != vocab.len() {
+            return Err(unsupported(
+                "added token identifiers do not follow the vocabulary",
+            ));
+        }
+        vocab.push((added_tokens[&id].0.to_owned(), 0.0));
+    }
+
+    let metaspace = components(tokenizer.get("pre_tokenizer"), "pretokenizers")
+        .into_iter()
+        .find(|component| component_type(component) == Some("Metaspace"))
+        .ok_or_else(|| unsupported("pre-tokenizer is not Metaspace"))?;
+    if metaspace.get("replacement").and_then(Value::as_str) != Some("▁") {
+        return Err(unsupported("Metaspace replacement is not ▁"));
+    }
+    let add_dummy_prefix = match metaspace.get("prepend_scheme").and_then(Value::as_str) {
+        Some(prepend_scheme) => prepend_scheme != "never",
+        // Older versions of tokenizers use a boolean.
+        None => metaspace
+            .get("add_prefix_space")
+            .and_then(Value::as_bool)
+            .unwrap_or(true),
+    };
+
+    let normalizers = components(tokenizer.get("normalizer"), "normalizers");
+    let precompiled_charsmap = normalizers
+        .iter()
+        .find(|normalizer| component_type(normalizer) == Some("Precompiled"))
+        .map(|normalizer| {
+            normalizer
+                .get("precompiled_charsmap")
+                .and_then(Value::as_str)
+                .and_then(base64_decode)
+                .ok_or_else(|| unsupported("invalid precompiled character map"))
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let remove_extra_whitespaces = normalizers.iter().any(|normalizer| {
+        component_type(normalizer) == Some("Replace")
+            && normalizer
+                .get("pattern")
+                .and_then(|pattern| pattern.get("Regex"))
+                .and_then(Value::as_str)
+                == Some(" {2,}")
+    });
+
+    let unk_piece = vocab[unk_id].0.clone();
+    let pieces = vocab.into_iter().enumerate().map(|(id, (piece, score))| {
+        let piece_type = match added_tokens.get(&id) {
+            _ if id == unk_id => PieceType::Unknown,
+            Some((_, true)) => PieceType::Control,
+            Some((_, false)) => PieceType::UserDefined,
+            None if byte_fallback && is_byte_piece(&piece) => PieceType::Byte,
+            None => PieceType::Normal,
+        };
+        (piece, score, piece_type)
+    });
+
+    let mut model = ModelBuilder::new()
+        .unk_piece(unk_piece)
+        .add_dummy_prefix(add_dummy_prefix)
+        .remove_extra_whitespaces(remove_extra_whitespaces)
+        .pieces(pieces)
+        .to_model_proto();
+
+    if !precompiled_charsmap.is_empty() {
+        if let Some(normalizer_spec) = model.normalizer_spec.as_mut() {
+            normalizer_spec.name = Some("user_defined".to_string());
+            normalizer_spec.precompiled_charsmap = Some(precompiled_charsmap);
+        }
+    }
+
+    Ok(model)
+}
+
+/// Get the components of a normalizer or pre-tokenizer.
+///
+/// The components of a `Sequence` are stored under `sequence_key`.
+fn components<'a>(component: Option<&'a Value>, sequence_key: &str) -> Vec<&'a Value> {
+    match component {
+        None | Some(Value::Null) => Vec::new(),
+        Some(component) if component_type(component) == Some("Sequence") => component
+            .get(sequence_key)
+            .and_then(Value::as_array)
+            .unwrap_or(&[])
+            .iter()
+            .collect(),
+        Some(component) => vec![component],
+    }
+}
+
+fn component_type(component: &Value) -> Option<&str> {
+    component.get("type").and_then(Value::as_str)
+}
+
+/// Decode base64 data, the padding is optional.
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=');
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut n_bits = 0;
+    for byte in encoded.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        n_bits += 6;
+        if n_bits >= 8 {
+            n_bits -= 8;
+            decoded.push((bits >> n_bits) as u8);
+            bits &= (1 << n_bits) - 1;
+        }
+    }
+    Some(decoded)
+}
+
+/// Encode data as base64 with padding.
+fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(4 * (data.len() / 3 + 1));
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (idx, &byte)| {
+            bits | ((byte as u32) << (16 - 8 * idx))
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[((bits >> (18 - 6 * idx)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64_decode, base64_encode};
+    use crate::json;
+    use crate::test_util::toy_model;
+    use crate::{
+        IoError, ModelBuilder, ModelType, PieceType, SentencePieceError, SentencePieceProcessor,
+    };
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foob"), "Zm9vYg==");
+        assert_eq!(base64_encode(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn decodes_base64() {
+        for data in &[&b""[..], b"f", b"fo", b"foo", b"foob", &[0xfb, 0xff]] {
+            assert_eq!(base64_decode(&base64_encode(data)).unwrap(), *data);
+        }
+        assert_eq!(base64_decode("Zm8").unwrap(), b"fo");
+        assert_eq!(base64_decode("Zm8*"), None);
+    }
+
+    #[test]
+    fn reads_written_tokenizer_json() {
+        let model = toy_model();
+        let mut json = Vec::new();
+        model.write_tokenizer_json(&mut json).unwrap();
+
+        let read = SentencePieceProcessor::read_tokenizer_json(&*json).unwrap();
+        assert_eq!(read.len(), model.len());
+        assert_eq!(read.precompiled_charsmap(), model.precompiled_charsmap());
+        assert_eq!(read.bos_id(), model.bos_id());
+        assert_eq!(read.eos_id(), model.eos_id());
+        for sentence in &["I saw a girl with a telescope.", "  ｆｕｌｌ  width 🤗"] {
+            assert_eq!(
+                read.encode(sentence).unwrap(),
+                model.encode(sentence).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn reads_tokenizer_json() {
+        let json = r#"{
+          "added_tokens": [
+            {"id": 0, "content": "<unk>", "special": true},
+            {"id": 1, "content": "</s>", "special": true},
+            {"id": 2, "content": "<sep>", "special": false}
+          ],
+          "normalizer": null,
+          "pre_tokenizer": {"type": "Sequence", "pretokenizers": [
+            {"type": "WhitespaceSplit"},
+            {"type": "Metaspace", "replacement": "▁", "add_prefix_space": true}
+          ]},
+          "model": {
+            "type": "Unigram",
+            "unk_id": 0,
+            "vocab": [["<unk>", 0.0], ["</s>", 0.0], ["<sep>", 0.0], ["▁a", -1.0], ["b", -2.0]]
+          }
+        }"#;
+
+        let model = SentencePieceProcessor::read_tokenizer_json(json.as_bytes()).unwrap();
+        assert!(model.is_control(1));
+        assert_eq!(model.eos_id(), Some(1));
+        assert_eq!(model.encode_as_ids("ab<sep>").unwrap(), [3, 4, 2]);
+        assert!(model.precompiled_charsmap().is_empty());
+        assert!(!model.remove_extra_whitespaces());
+    }
+
+    #[test]
+    fn appends_added_tokens_to_vocabulary() {
+        let json = r#"{
+          "added_tokens": [
+            {"id": 0, "content": "<unk>", "special": true},
+            {"id": 4, "content": "<mask>", "special": false},
+            {"id": 3, "content": "<pad>", "special": true}
+          ],
+          "pre_tokenizer": {"type": "Metaspace", "replacement": "▁", "prepend_scheme": "always"},
+          "model": {
+            "type": "Unigram",
+            "unk_id": 0,
+            "vocab": [["<unk>", 0.0], ["▁a", -1.0], ["b", -2.0]]
+          }
+        }"#;
+
+        let model = SentencePieceProcessor::read_tokenizer_json(json.as_bytes()).unwrap();
+        assert_eq!(model.len(), 5);
+        assert_eq!(model.id_to_piece(3), Ok("<pad>"));
+        assert!(model.is_control(3));
+        assert_eq!(model.id_to_piece(4), Ok("<mask>"));
+        assert_eq!(model.encode_as_ids("ab<mask>").unwrap(), [1, 2, 4]);
+
+        let gap = json.replace("\"id\": 3", "\"id\": 5");
+        assert!(matches!(
+            SentencePieceProcessor::read_tokenizer_json(gap.as_bytes()),
+            Err(IoError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_tokenizer_json() {
+        for json in &[
+            r#"{"model": {"type": "BPE", "vocab": {}, "merges": []}}"#,
+            r#"{"model": {"type": "Unigram", "unk_id": 0, "vocab": [["<unk>", 0.0]]}}"#,
+            r#"{"model": {"type": "Unigram", "unk_id": 0, "vocab": [["<unk>"]]}}"#,
+            "{",
+        ] {
+            assert!(matches!(
+                SentencePieceProcessor::read_tokenizer_json(json.as_bytes()),
+                Err(IoError::Format(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn writes_tokenizer_json() {
+        let model = toy_model();
+        let mut json = Vec::new();
+        model.write_tokenizer_json(&mut json).unwrap();
+        let tokenizer = json::parse(std::str::from_utf8(&json).unwrap()).unwrap();
+
+        let added_tokens = tokenizer.get("added_tokens").unwrap().as_array().unwrap();
+        assert_eq!(added_tokens[0].get("id").unwrap().as_f64(), Some(0.));
+        assert_eq!(
+            added_tokens[0].get("content").unwrap().as_str(),
+            Some("<unk>")
+        );
+        assert_eq!(
+            added_tokens[0].get("special").unwrap().as_bool(),
+            Some(true)
+        );
+
+        let normalizers = tokenizer
+            .get("normalizer")
+            .and_then(|normalizer| normalizer.get("normalizers"))
+            .unwrap()
+            .as_array()
+            .unwrap();
+        assert_eq!(
+            normalizers[0].get("precompiled_charsmap").unwrap().as_str(),
+            Some(&*base64_encode(&model.precompiled_charsmap()))
+        );
+
+        for key in &["pre_tokenizer", "decoder"] {
+            let metaspace = tokenizer.get(key).unwrap();
+            assert_eq!(metaspace.get("type").unwrap().as_str(), Some("Metaspace"));
+            assert_eq!(
+                metaspace.get("add_prefix_space").unwrap().as_bool(),
+                Some(true)
+            );
+            assert_eq!(
+                metaspace.get("prepend_scheme").unwrap().as_str(),
+                Some("always")
+            );
+        }
+
+        let unigram = tokenizer.get("model").unwrap();
+        assert_eq!(unigram.get("type").unwrap().as_str(), Some("Unigram"));
+        assert_eq!(unigram.get("unk_id").unwrap().as_f64(), Some(0.));
+        assert_eq!(unigram.get("byte_fallback").unwrap().as_bool(), Some(false));
+
+        let vocab = unigram.get("vocab").unwrap().as_array().unwrap();
+        let pieces = model.model_proto().unwrap().pieces;
+        assert_eq!(vocab.len(), pieces.len());
+        for (entry, piece) in vocab.iter().zip(&pieces) {
+            let entry = entry.as_array().unwrap();
+            assert_eq!(entry[0].as_str(), Some(piece.piece()));
+            assert_eq!(
+                entry[1].as_f64().map(|score| score as f32),
+                Some(piece.score())
+            );
+        }
+    }
+
+    #[cfg(feature = "tokenizers")]
+    #[test]
+    fn written_tokenizer_json_loads_in_tokenizers() {
+        let model = toy_model();
+        let mut json = Vec::new();
+        model.write_tokenizer_json(&mut json).unwrap();
+
+        let tokenizer = tokenizers::Tokenizer::from_bytes(&json).unwrap();
+        let sentence = "I saw a girl with a telescope.";
+        let encoding = tokenizer.encode(sentence, false).unwrap();
+        assert_eq!(encoding.get_ids(), &*model.encode_as_ids(sentence).unwrap());
+    }
+
+    #[test]
+    fn rejects_scores_that_are_not_finite() {
+        let model = ModelBuilder::new()
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("▁a", f32::NEG_INFINITY, PieceType::Normal)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            model.write_tokenizer_json(Vec::new()),
+            Err(IoError::SentencePiece(SentencePieceError::InvalidArgument(
+                _
+            )))
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_models() {
+        let model = ModelBuilder::new()
+            .model_type(ModelType::Bpe)
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            model.write_tokenizer_json(Vec::new()),
+            Err(IoError::SentencePiece(
+                SentencePieceError::UnsupportedModelType(ModelType::Bpe)
+            ))
+        ));
+
+        let model = ModelBuilder::new()
+            .treat_whitespace_as_suffix(true)
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            model.write_tokenizer_json(Vec::new()),
+            Err(IoError::SentencePiece(SentencePieceError::InvalidArgument(
+                _
+            )))
+        ));
+    }
+}
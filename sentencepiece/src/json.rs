@@ -0,0 +1,330 @@
+use std::io::{self, Write};
+
+/// Write a string as a JSON string literal.
+pub(crate) fn write_string(writer: &mut impl Write, s: &str) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+
+    for ch in s.chars() {
+        match ch {
+            '"' => writer.write_all(b"\\\"")?,
+            '\\' => writer.write_all(b"\\\\")?,
+            '\n' => writer.write_all(b"\\n")?,
+            '\r' => writer.write_all(b"\\r")?,
+            '\t' => writer.write_all(b"\\t")?,
+            ch if ch < ' ' => write!(writer, "\\u{:04x}", ch as u32)?,
+            ch => writer.write_all(ch.encode_utf8(&mut [0; 4]).as_bytes())?,
+        }
+    }
+
+    writer.write_all(b"\"")
+}
+
+/// JSON value.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub(crate) fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a key of an object.
+    ///
+    /// Returns `None` if the value is not an object or does not have
+    /// the key.
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+/// Maximum nesting depth of arrays and objects.
+///
+/// Parsing is recursive, the depth is limited to avoid stack overflows
+/// on untrusted data.
+const MAX_DEPTH: usize = 128;
+
+/// Parse a JSON document.
+pub(crate) fn parse(json: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        json,
+        pos: 0,
+        depth: 0,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos != json.len() {
+        return Err(parser.error("Trailing data"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    json: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.pos)
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.json[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected `{}`", literal)))
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.json.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.expect("null").map(|_| Value::Null),
+            Some(b't') => self.expect("true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some(b'"') => self.parse_string().map(Value::String),
+            Some(b'[') => self.nested(Self::parse_array),
+            Some(b'{') => self.nested(Self::parse_object),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of data")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Value, String>,
+    ) -> Result<Value, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("Maximum nesting depth exceeded"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_array(&mut self) -> Result<Value, String> {
+        self.expect("[")?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.error("Expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value, String> {
+        self.expect("{")?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(entries));
+                }
+                _ => return Err(self.error("Expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while let Some(b'+' | b'-' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.json[start..self.pos]
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| self.error("Invalid number"))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut s = String::new();
+        loop {
+            let rest = &self.json[self.pos..];
+            let end = rest
+                .find(&['"', '\\'][..])
+                .ok_or_else(|| self.error("Unterminated string"))?;
+            s.push_str(&rest[..end]);
+            self.pos += end + 1;
+            if rest.as_bytes()[end] == b'"' {
+                return Ok(s);
+            }
+
+            let escaped = match self.peek() {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\u{8}',
+                Some(b'f') => '\u{c}',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    self.pos += 1;
+                    let mut code_point = self.parse_hex4()?;
+                    // Characters outside the basic multilingual plane are
+                    // encoded as surrogate pairs.
+                    if (0xd800..0xdc00).contains(&code_point)
+                        && self.json[self.pos..].starts_with("\\u")
+                    {
+                        self.pos += 2;
+                        let low = self.parse_hex4()?;
+                        if !(0xdc00..0xe000).contains(&low) {
+                            return Err(self.error("Invalid surrogate pair"));
+                        }
+                        code_point = 0x10000 + ((code_point - 0xd800) << 10) + (low - 0xdc00);
+                    }
+                    s.push(
+                        std::char::from_u32(code_point)
+                            .ok_or_else(|| self.error("Invalid code point"))?,
+                    );
+                    continue;
+                }
+                _ => return Err(self.error("Invalid escape sequence")),
+            };
+            s.push(escaped);
+            self.pos += 1;
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let hex = self
+            .json
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("Invalid unicode escape"))?;
+        let code_point =
+            u32::from_str_radix(hex, 16).map_err(|_| self.error("Invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(code_point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, write_string, Value, MAX_DEPTH};
+
+    #[test]
+    fn escapes_strings() {
+        let mut json = Vec::new();
+        write_string(&mut json, "▁\"a\\b\"\n\u{1}").unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(), r#""▁\"a\\b\"\n\u0001""#);
+    }
+
+    #[test]
+    fn parses_json() {
+        let value =
+            parse(r#" {"a": [1, -2.5e1, true, null], "b": "▁\"\u00e9\ud83e\udd17", "c": {}} "#)
+                .unwrap();
+        assert_eq!(
+            value.get("a").and_then(Value::as_array).unwrap(),
+            [
+                Value::Number(1.),
+                Value::Number(-25.),
+                Value::Bool(true),
+                Value::Null
+            ]
+        );
+        assert_eq!(value.get("b").and_then(Value::as_str), Some("▁\"é🤗"));
+        assert_eq!(value.get("c"), Some(&Value::Object(Vec::new())));
+        assert_eq!(value.get("d"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        for json in &["", "[1,]", "{\"a\" 1}", "\"a", "[1] 2", "nul", "\"\\x\""] {
+            assert!(parse(json).is_err(), "{} should be rejected", json);
+        }
+    }
+
+    #[test]
+    fn limits_nesting_depth() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse(&"[".repeat(1_000_000)),
+            Err(format!(
+                "Maximum nesting depth exceeded at byte {}",
+                MAX_DEPTH
+            ))
+        );
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+    }
+}
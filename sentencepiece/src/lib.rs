@@ -15,28 +15,170 @@
 //!   "▁a", "▁t", "el", "es", "c", "o", "pe", "."]);
 //! ```
 
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::ffi::{c_void, CString, NulError};
+use std::io::{self, Read};
+use std::iter::FromIterator;
 use std::ops::{Deref, Drop};
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_int};
 use std::path::{Path, PathBuf};
 use std::slice;
+use std::sync::{Arc, Mutex};
 
 use num_derive::FromPrimitive;
 use num_traits::{FromPrimitive, Signed};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use sentencepiece_sys::{
-    spp_bos_id, spp_decode_piece_ids, spp_decode_pieces, spp_encode_as_serialized_proto,
-    spp_eos_id, spp_free, spp_from_serialized_proto, spp_is_unknown, spp_load, spp_new, spp_pad_id,
-    spp_piece_size, spp_piece_to_id, spp_sample_encode_as_serialized_proto,
-    spp_to_serialized_proto, spp_unk_id, SentencePieceProcessor as CSentencePieceProcessor,
+    spp_bos_id, spp_decode_piece_ids_as_serialized_proto, spp_decode_piece_ids_into,
+    spp_decode_pieces, spp_encode, spp_encode_as_ids, spp_encode_as_pieces,
+    spp_encode_as_serialized_proto_into, spp_eos_id, spp_exception_message, spp_free,
+    spp_free_buffer, spp_from_serialized_proto, spp_get_score, spp_id_to_piece, spp_is_byte,
+    spp_is_control, spp_is_unknown, spp_is_unused, spp_load, spp_load_vocabulary,
+    spp_nbest_encode_as_serialized_proto, spp_new, spp_pad_id, spp_piece_size, spp_piece_to_id,
+    spp_pieces_to_ids, spp_reset_vocabulary, spp_sample_encode_and_score_as_serialized_proto,
+    spp_sample_encode_as_serialized_proto, spp_set_decode_extra_options,
+    spp_set_encode_extra_options, spp_set_vocabulary, spp_status_message, spp_to_serialized_proto,
+    spp_unk_id, SentencePieceProcessor as CSentencePieceProcessor, SentencePieceWithId,
+    SPP_EXCEPTION,
 };
 
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "arrow")]
+pub use crate::arrow::ArrowEncoding;
+
+#[cfg(feature = "async")]
+mod async_processor;
+#[cfg(feature = "async")]
+pub use crate::async_processor::AsyncProcessor;
+
+mod batch;
+pub use crate::batch::{BatchOptions, PaddedBatch};
+
+mod bpe;
+
+mod builder;
+pub use crate::builder::{FromPartsOptions, ModelBuilder};
+
+mod byte_fallback;
+
+#[cfg(feature = "candle")]
+mod candle;
+
+#[cfg(feature = "compact")]
+mod compact;
+#[cfg(feature = "compact")]
+pub use crate::compact::{CompactIds, CompactPieceWithId, CompactPieces, COMPACT_INLINE_PIECES};
+
+mod deadline;
+pub use crate::deadline::DeadlineProcessor;
+
+mod encoding;
+pub use crate::encoding::Encoding;
+
+mod eval;
+pub use crate::eval::{evaluate, Corpus, CorpusReport, EvaluationReport, LengthDistribution};
+
+#[cfg(feature = "http")]
+mod http;
+
+mod huggingface;
+
+mod json;
+
+mod merge;
+pub use crate::merge::{merge_models, MergedModel, ScorePolicy};
+
+mod metadata;
+use crate::metadata::ModelSpecs;
+
+mod model;
+use crate::model::ModelProto;
+pub use crate::model::{ModelType, PieceType};
+
+mod normalize;
+pub use crate::normalize::NormalizedText;
+
+mod normalizer;
+pub use crate::normalizer::SentencePieceNormalizer;
+
+mod onnx;
+pub use crate::onnx::OnnxExportOptions;
+
+mod options;
+pub use crate::options::{DecodeOptions, EncodeOptions, LoadOptions};
+
+pub mod proto;
+
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "python")]
+pub use crate::python::python_module;
+
+mod random;
+pub use crate::random::set_random_seed;
+
+mod raw;
+
+mod remap;
+pub use crate::remap::{IdTranslation, MissingPiecePolicy};
+
+mod retokenize;
+pub use crate::retokenize::{RetokenizeStats, Retokenizer, TokenFormat};
+
+mod router;
+pub use crate::router::Router;
+
+mod scratch;
+use crate::scratch::{buffer_ctx, reserve_buffer, with_scratch};
+
 mod sentencepiece;
-use crate::sentencepiece::SentencePieceText;
+use crate::proto::{NBestSentencePieceText, SentencePieceText};
+
+mod stream;
+pub use crate::stream::{EncodeIter, EncodeLines, StreamingDecoder};
+
+mod subword_nmt;
+pub use crate::subword_nmt::{ParityMismatch, SubwordNmtCodes};
+
+#[cfg(test)]
+mod test_util;
+
+#[cfg(feature = "tokenizers")]
+mod tokenizers_model;
+#[cfg(feature = "tokenizers")]
+pub use crate::tokenizers_model::{TokenizersModel, TokenizersTrainer};
+
+#[cfg(feature = "tch")]
+mod torch;
+
+mod trainer;
+pub use crate::trainer::TrainerBuilder;
+
+#[cfg(feature = "datafusion")]
+mod udf;
+#[cfg(feature = "datafusion")]
+pub use crate::udf::{register_udfs, sp_count_tokens_udf, sp_decode_udf, sp_encode_udf};
+
+mod truncate;
+pub use crate::truncate::TruncatedEncoding;
+
+mod unknown;
+pub use crate::unknown::CheckedEncoding;
+
+mod vocab;
+pub use crate::vocab::{
+    IdChange, PieceTypeCounts, ScoreChange, SpecialIdChange, SpecialPiece, TypeChange, Vocab,
+    VocabDiff, VocabPiece,
+};
 
 /// Sentence piece with its identifier and string span.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct PieceWithId {
     /// The sentence piece as a string.
     pub piece: String,
@@ -53,8 +195,11 @@ pub struct PieceWithId {
 #[derive(Clone, Debug, Eq, Error, PartialEq)]
 #[non_exhaustive]
 pub enum SentencePieceError {
-    #[error("sentencepiece error: {0}")]
-    CError(CSentencePieceError),
+    #[error("sentencepiece error: {code}: {message}")]
+    CError {
+        code: CSentencePieceError,
+        message: String,
+    },
 
     #[error("sentencepiece could not encode the text")]
     EncodeError,
@@ -65,11 +210,59 @@ pub enum SentencePieceError {
     #[error("Filename is not valid UTF-8: {0}")]
     FilenameNotUtf8(PathBuf),
 
+    #[error("sentencepiece raised an exception: {0}")]
+    Internal(String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Cannot decode protobuf message: {0}")]
+    InvalidProtobuf(String),
+
+    #[error("Piece identifier {0} is out of range")]
+    InvalidPieceId(u32),
+
+    #[error("Span ({0}, {1}) is not a valid range of the input")]
+    InvalidSpan(u32, u32),
+
+    #[error("Text is not valid UTF-8")]
+    InvalidUtf8(Vec<u8>),
+
     #[error("Encoded text did not contain {0}")]
     MissingData(String),
 
+    #[error("Piece is not in the vocabulary: {0}")]
+    MissingPiece(String),
+
     #[error("Piece contains nul byte")]
     PieceContainsNul,
+
+    #[error("Cannot determine the language of the sentence")]
+    UndeterminedLanguage,
+
+    #[error("No model for language: {0}")]
+    UnknownLanguage(String),
+
+    #[error("Operation is not supported for {0:?} models")]
+    UnsupportedModelType(ModelType),
+}
+
+/// Errors of operations that read or write data.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum IoError {
+    #[error("{desc}: {error}")]
+    Io {
+        desc: String,
+        #[source]
+        error: io::Error,
+    },
+
+    #[error("Invalid data: {0}")]
+    Format(String),
+
+    #[error(transparent)]
+    SentencePiece(#[from] SentencePieceError),
 }
 
 /// Errors that returned by the `sentencepiece` library.
@@ -110,23 +303,96 @@ pub enum CSentencePieceError {
     DataLoss = 15,
 }
 
+/// Convert a non-zero status of the wrapper into an error.
+fn c_error(status: c_int) -> SentencePieceError {
+    if status == SPP_EXCEPTION {
+        return SentencePieceError::Internal(exception_message());
+    }
+
+    match FromPrimitive::from_i32(status) {
+        Some(code) => SentencePieceError::CError {
+            code,
+            message: status_message(),
+        },
+        None => unreachable!(),
+    }
+}
+
+/// Convert a path to a filename for sentencepiece.
+fn path_to_c_filename(path: &Path) -> Result<CString, SentencePieceError> {
+    #[cfg(unix)]
+    let filename = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes()
+    };
+    #[cfg(not(unix))]
+    let filename = path
+        .to_str()
+        .ok_or_else(|| SentencePieceError::FilenameNotUtf8(path.to_owned()))?;
+
+    CString::new(filename).map_err(|_| SentencePieceError::FilenameContainsNul(path.to_owned()))
+}
+
+/// Get the message of the last C++ exception caught in this thread.
+fn exception_message() -> String {
+    let mut len = 0;
+    let data = unsafe { spp_exception_message(&mut len) };
+    String::from_utf8_lossy(unsafe { slice::from_raw_parts(data as *const u8, len) }).into_owned()
+}
+
+/// Get the message of the last failed sentencepiece status in this thread.
+fn status_message() -> String {
+    let mut len = 0;
+    let data = unsafe { spp_status_message(&mut len) };
+    String::from_utf8_lossy(unsafe { slice::from_raw_parts(data as *const u8, len) }).into_owned()
+}
+
 /// Small wrapper struct to deallocate data automatically.
-struct CData {
-    data: *const u8,
+struct CData<T = u8> {
+    data: *const T,
     len: usize,
 }
 
-impl Deref for CData {
-    type Target = [u8];
+impl<T> Deref for CData<T> {
+    type Target = [T];
 
     fn deref(&self) -> &Self::Target {
+        // Allocations of zero bytes can return null.
+        if self.data.is_null() {
+            return &[];
+        }
+
         unsafe { slice::from_raw_parts(self.data, self.len) }
     }
 }
 
-impl Drop for CData {
+impl<T> Drop for CData<T> {
+    fn drop(&mut self) {
+        unsafe { spp_free_buffer(self.data as *mut c_void) }
+    }
+}
+
+/// Owner of a sentencepiece processor, which is freed on drop.
+#[derive(Debug)]
+struct OwnedProcessor {
+    spp: *mut CSentencePieceProcessor,
+
+    /// The normalizer and trainer specifications, parsed on first use.
+    specs: Mutex<Option<Arc<ModelSpecs>>>,
+}
+
+impl OwnedProcessor {
+    fn new(spp: *mut CSentencePieceProcessor) -> Self {
+        OwnedProcessor {
+            spp,
+            specs: Mutex::new(None),
+        }
+    }
+}
+
+impl Drop for OwnedProcessor {
     fn drop(&mut self) {
-        unsafe { libc::free(self.data as *mut c_void) }
+        unsafe { spp_free(self.spp) }
     }
 }
 
@@ -134,22 +400,98 @@ impl Drop for CData {
 ///
 /// Instances of `SentencePieceProcessor` can be used to tokenizer a
 /// sentence using a sentencepiece model.
-#[derive(Debug)]
+///
+/// Cloning a processor is cheap, since clones share the underlying
+/// sentencepiece processor. A clone gets its own copy of the model when
+/// it is modified, e.g. using [`SentencePieceProcessor::set_vocabulary`].
+#[derive(Clone, Debug)]
 pub struct SentencePieceProcessor {
     inner: *mut CSentencePieceProcessor,
+    owner: Arc<OwnedProcessor>,
+    decode_extra_options: String,
+    encode_extra_options: String,
+    piece_ids: Option<Arc<HashMap<String, u32>>>,
 }
 
-impl Drop for SentencePieceProcessor {
-    fn drop(&mut self) {
-        unsafe { spp_free(self.inner) }
+impl SentencePieceProcessor {
+    /// Construct a processor without a model.
+    fn new_empty() -> Result<Self, SentencePieceError> {
+        let inner = unsafe { spp_new() };
+        if inner.is_null() {
+            return Err(SentencePieceError::Internal(exception_message()));
+        }
+
+        Ok(SentencePieceProcessor {
+            inner,
+            owner: Arc::new(OwnedProcessor::new(inner)),
+            decode_extra_options: String::new(),
+            encode_extra_options: String::new(),
+            piece_ids: None,
+        })
+    }
+
+    /// Ensure that the sentencepiece processor is not shared with clones.
+    ///
+    /// This must be called before the sentencepiece processor is
+    /// modified. A shared processor is replaced by a copy of the model.
+    fn make_unique(&mut self) -> Result<(), SentencePieceError> {
+        if Arc::strong_count(&self.owner) == 1 {
+            return Ok(());
+        }
+
+        // The vocabulary restriction is part of the serialized model,
+        // the extra options are not.
+        let mut spp = Self::from_serialized_proto(&self.to_serialized_proto())?;
+        if !self.decode_extra_options.is_empty() {
+            spp.set_decode_extra_options(&self.decode_extra_options)?;
+        }
+        if !self.encode_extra_options.is_empty() {
+            spp.set_encode_extra_options(&self.encode_extra_options)?;
+        }
+        spp.piece_ids = self.piece_ids.clone();
+
+        *self = spp;
+
+        Ok(())
+    }
+
+    /// Load a model from a protobuf message.
+    pub fn from_model_proto(model: &ModelProto) -> Result<Self, SentencePieceError> {
+        Self::from_serialized_proto(&model.to_bytes())
+    }
+
+    /// Get a copy of this model with a different special piece.
+    ///
+    /// See [`ModelProto::set_special_piece`] for details. Returns
+    /// `SentencePieceError::InvalidProtobuf` when the model contains a
+    /// piece that is not valid UTF-8.
+    pub fn with_special_piece(
+        &self,
+        special: SpecialPiece,
+        piece: impl Into<String>,
+    ) -> Result<Self, SentencePieceError> {
+        let mut model = self.model_proto()?;
+        model.set_special_piece(special, piece)?;
+        Self::from_model_proto(&model)
+    }
+
+    /// Get a copy of this model with a pruned vocabulary.
+    ///
+    /// Returns the pruned model and the mapping from old to new
+    /// identifiers. See [`ModelProto::prune`] for details. Returns
+    /// `SentencePieceError::InvalidProtobuf` when the model contains a
+    /// piece that is not valid UTF-8.
+    pub fn prune_vocab(
+        &self,
+        vocab_size: usize,
+    ) -> Result<(Self, Vec<Option<u32>>), SentencePieceError> {
+        let mut model = self.model_proto()?;
+        let id_map = model.prune(vocab_size)?;
+        Ok((Self::from_model_proto(&model)?, id_map))
     }
-}
 
-impl SentencePieceProcessor {
     pub fn from_serialized_proto(data: &[u8]) -> Result<Self, SentencePieceError> {
-        let spp = SentencePieceProcessor {
-            inner: unsafe { spp_new() },
-        };
+        let spp = Self::new_empty()?;
 
         let result = unsafe {
             spp_from_serialized_proto(spp.inner, data.as_ptr() as *const c_char, data.len())
@@ -158,18 +500,48 @@ impl SentencePieceProcessor {
         if result == 0 {
             Ok(spp)
         } else {
-            let c_error = match FromPrimitive::from_i32(result) {
-                Some(error) => error,
-                None => unreachable!(),
-            };
-            Err(SentencePieceError::CError(c_error))
+            Err(c_error(result))
         }
     }
 
+    /// Load a serialized model from a reader.
+    ///
+    /// The model is read until the end of the reader.
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, IoError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).map_err(|error| IoError::Io {
+            desc: "Cannot read model".to_string(),
+            error,
+        })?;
+        Ok(Self::from_serialized_proto(&data)?)
+    }
+
+    /// Construct a processor from pieces and their scores.
+    ///
+    /// The piece types are derived from the piece strings: the unknown
+    /// piece, control pieces, and (optionally) byte pieces are
+    /// identified using `options`, all other pieces are normal pieces.
+    /// The model uses the identity normalizer.
+    pub fn from_parts(
+        pieces: &[(String, f32)],
+        options: &FromPartsOptions,
+    ) -> Result<Self, SentencePieceError> {
+        options.to_builder(pieces).build()
+    }
+
     /// Serialize the model to protobuf.
+    ///
+    /// Panics when sentencepiece raises an exception, which only happens
+    /// when memory cannot be allocated.
     pub fn to_serialized_proto(&self) -> Vec<u8> {
         let mut len = 0;
         let data = unsafe { spp_to_serialized_proto(self.inner, &mut len) };
+        if data.is_null() {
+            panic!(
+                "sentencepiece raised an exception during serialization: {}",
+                exception_message()
+            );
+        }
 
         let c_str = CData { data, len };
 
@@ -178,34 +550,14 @@ impl SentencePieceProcessor {
 
     /// Open a sentencepiece model.
     pub fn open(path: impl AsRef<Path>) -> Result<Self, SentencePieceError> {
-        let spp = SentencePieceProcessor {
-            inner: unsafe { spp_new() },
-        };
+        let spp = Self::new_empty()?;
 
-        let result;
-        #[cfg(unix)]
-        {
-            use std::os::unix::ffi::OsStrExt;
-            let c_filename = CString::new(path.as_ref().as_os_str().as_bytes())
-                .map_err(|_| SentencePieceError::FilenameContainsNul(path.as_ref().to_owned()))?;
-            result = unsafe { spp_load(spp.inner, c_filename.as_ptr()) };
-        }
-        #[cfg(not(unix))]
-        {
-            let c_filename = CString::new(path.as_ref().to_str().ok_or(
-                SentencePieceError::FilenameNotUtf8(path.as_ref().to_owned()),
-            )?)
-            .map_err(|_| SentencePieceError::FilenameContainsNul(path.as_ref().to_owned()))?;
-            result = unsafe { spp_load(spp.inner, c_filename.as_ptr()) };
-        }
+        let c_filename = path_to_c_filename(path.as_ref())?;
+        let result = unsafe { spp_load(spp.inner, c_filename.as_ptr()) };
         if result == 0 {
             Ok(spp)
         } else {
-            let c_error = match FromPrimitive::from_i32(result) {
-                Some(error) => error,
-                None => unreachable!(),
-            };
-            Err(SentencePieceError::CError(c_error))
+            Err(c_error(result))
         }
     }
 
@@ -219,37 +571,91 @@ impl SentencePieceProcessor {
     }
 
     /// Decode a sentence from piece identifiers.
+    ///
+    /// Returns `SentencePieceError::InvalidUtf8` with the decoded bytes
+    /// when the decoded sentence is not valid UTF-8. See
+    /// [`DecodeOptions::lossy_utf8`] to replace invalid sequences instead.
     pub fn decode_piece_ids(&self, pieces: &[u32]) -> Result<String, SentencePieceError> {
-        let mut decoded = std::ptr::null_mut::<u8>();
+        let decoded = self.decode_piece_ids_bytes(pieces)?;
+        String::from_utf8(decoded).map_err(|err| SentencePieceError::InvalidUtf8(err.into_bytes()))
+    }
+
+    /// Decode a sentence from signed 64-bit piece identifiers.
+    ///
+    /// This avoids conversion of model outputs, which are typically
+    /// `i64` tensors. Returns `SentencePieceError::InvalidArgument` when
+    /// an identifier is negative or does not fit in a `u32`.
+    pub fn decode_piece_ids_i64(&self, pieces: &[i64]) -> Result<String, SentencePieceError> {
+        let pieces = pieces
+            .iter()
+            .map(|&id| {
+                u32::try_from(id).map_err(|_| {
+                    SentencePieceError::InvalidArgument(format!(
+                        "piece identifier {} is out of range",
+                        id
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.decode_piece_ids(&pieces)
+    }
+
+    /// Decode a sentence from piece identifiers as raw bytes.
+    ///
+    /// In contrast to [`SentencePieceProcessor::decode_piece_ids`], the
+    /// decoded sentence is not required to be valid UTF-8. This is useful
+    /// for models with pieces that are not valid UTF-8, such as some
+    /// user-defined symbols.
+    pub fn decode_piece_ids_bytes(&self, pieces: &[u32]) -> Result<Vec<u8>, SentencePieceError> {
+        // The sentence is decoded into the returned vector, since the
+        // scratch buffers would have to be copied.
+        let mut decoded = Vec::new();
         let mut decoded_len = 0;
 
         let status = unsafe {
-            spp_decode_piece_ids(
+            spp_decode_piece_ids_into(
                 self.inner,
                 pieces.as_ptr(),
                 pieces.len(),
-                &mut decoded,
+                Some(reserve_buffer),
+                buffer_ctx(&mut decoded),
                 &mut decoded_len,
             )
         };
 
-        let c_str = CData {
-            data: decoded,
-            len: decoded_len,
-        };
+        if status != 0 {
+            return Err(c_error(status));
+        }
 
-        if status == 0 {
-            let decoded_string = String::from_utf8(c_str.to_owned())
-                .expect("Decoded sentence is not UTF-8, please report this bug.");
+        unsafe { decoded.set_len(decoded_len) };
 
-            Ok(decoded_string)
-        } else {
-            let c_error = match FromPrimitive::from_i32(status) {
-                Some(error) => error,
-                None => unreachable!(),
-            };
-            Err(SentencePieceError::CError(c_error))
+        Ok(decoded)
+    }
+
+    /// Decode a sentence from piece identifiers as a protobuf message.
+    ///
+    /// In contrast to [`SentencePieceProcessor::decode_piece_ids`], the
+    /// message also contains the pieces, with their surface strings and
+    /// their byte spans in the decoded sentence.
+    pub fn decode_piece_ids_with_offsets(
+        &self,
+        pieces: &[u32],
+    ) -> Result<SentencePieceText, SentencePieceError> {
+        if let Some(&id) = pieces.iter().find(|&&id| id as usize >= self.len()) {
+            return Err(SentencePieceError::InvalidPieceId(id));
         }
+
+        let mut len = 0usize;
+        let c_proto = unsafe {
+            spp_decode_piece_ids_as_serialized_proto(
+                self.inner,
+                pieces.as_ptr(),
+                pieces.len(),
+                &mut len,
+            )
+        };
+
+        Self::decode_encode_protobuf(CData { data: c_proto, len })
     }
 
     pub fn decode_pieces(&self, pieces: &[impl AsRef<str>]) -> Result<String, SentencePieceError> {
@@ -282,32 +688,185 @@ impl SentencePieceProcessor {
         };
 
         if status == 0 {
-            let decoded_string = String::from_utf8(c_str.to_owned())
-                .expect("Decoded sentence is not UTF-8, please report this bug.");
-
-            Ok(decoded_string)
+            String::from_utf8(c_str.to_owned())
+                .map_err(|err| SentencePieceError::InvalidUtf8(err.into_bytes()))
         } else {
-            let c_error = match FromPrimitive::from_i32(status) {
-                Some(error) => error,
-                None => unreachable!(),
-            };
-            Err(SentencePieceError::CError(c_error))
+            Err(c_error(status))
         }
     }
 
     /// Encode a sentence as sentence pieces and their identifiers.
     pub fn encode(&self, sentence: &str) -> Result<Vec<PieceWithId>, SentencePieceError> {
-        let mut len = 0usize;
-        let c_proto = unsafe {
-            spp_encode_as_serialized_proto(
+        self.encode_with(sentence, |piece, id, span| PieceWithId {
+            piece: piece.to_owned(),
+            id,
+            span,
+        })
+    }
+
+    /// Encode a sentence, constructing pieces with `make_piece`.
+    ///
+    /// `make_piece` is called with the piece, its identifier, and its
+    /// span for every piece of the encoding.
+    fn encode_with<C, T>(
+        &self,
+        sentence: &str,
+        mut make_piece: impl FnMut(&str, u32, (u32, u32)) -> T,
+    ) -> Result<C, SentencePieceError>
+    where
+        C: FromIterator<T>,
+    {
+        with_scratch(|scratch| {
+            let mut n_pieces = 0;
+            let mut piece_data_len = 0;
+
+            let status = unsafe {
+                spp_encode(
+                    self.inner,
+                    sentence.as_ptr() as *const c_char,
+                    sentence.len(),
+                    Some(reserve_buffer),
+                    buffer_ctx(&mut scratch.pieces),
+                    &mut n_pieces,
+                    buffer_ctx(&mut scratch.data),
+                    &mut piece_data_len,
+                )
+            };
+
+            if status != 0 {
+                return Err(c_error(status));
+            }
+
+            unsafe {
+                scratch
+                    .pieces
+                    .set_len(n_pieces * std::mem::size_of::<SentencePieceWithId>());
+                scratch.data.set_len(piece_data_len);
+            }
+
+            // The piece strings are concatenated, so the pieces are read
+            // without decoding a protobuf message.
+            let mut offset = 0;
+            scratch
+                .pieces
+                .chunks_exact(std::mem::size_of::<SentencePieceWithId>())
+                .map(|c_piece| {
+                    // The buffer is not aligned for the piece structs.
+                    let c_piece = unsafe {
+                        std::ptr::read_unaligned(c_piece.as_ptr() as *const SentencePieceWithId)
+                    };
+                    let piece_data = &scratch.data[offset..offset + c_piece.piece_len];
+                    let piece = std::str::from_utf8(piece_data)
+                        .map_err(|_| SentencePieceError::InvalidUtf8(piece_data.to_vec()))?;
+                    offset += c_piece.piece_len;
+                    Ok(make_piece(piece, c_piece.id, (c_piece.begin, c_piece.end)))
+                })
+                .collect()
+        })
+    }
+
+    /// Encode a sentence as piece identifiers.
+    ///
+    /// This is faster than [`SentencePieceProcessor::encode`] when only
+    /// the identifiers are needed, since the encoding is not passed
+    /// through a protobuf message.
+    pub fn encode_as_ids(&self, sentence: &str) -> Result<Vec<u32>, SentencePieceError> {
+        let mut ids = Vec::new();
+        self.encode_ids_into(sentence, &mut ids)?;
+        Ok(ids)
+    }
+
+    /// Encode a sentence as piece identifiers into a buffer.
+    ///
+    /// The buffer is cleared before the identifiers are added. Reusing a
+    /// buffer avoids allocating a vector for every sentence.
+    pub fn encode_ids_into(
+        &self,
+        sentence: &str,
+        out: &mut Vec<u32>,
+    ) -> Result<(), SentencePieceError> {
+        self.encode_ids_with(sentence, |ids| {
+            out.clear();
+            out.extend_from_slice(ids);
+        })
+    }
+
+    /// Encode a sentence as piece identifiers and pass them to `f`.
+    fn encode_ids_with<R>(
+        &self,
+        sentence: &str,
+        f: impl FnOnce(&[u32]) -> R,
+    ) -> Result<R, SentencePieceError> {
+        let mut ids = std::ptr::null_mut::<u32>();
+        let mut ids_len = 0;
+
+        let status = unsafe {
+            spp_encode_as_ids(
                 self.inner,
                 sentence.as_ptr() as *const c_char,
                 sentence.as_bytes().len(),
-                &mut len,
+                &mut ids,
+                &mut ids_len,
             )
         };
 
-        Self::process_encode_protobuf(CData { data: c_proto, len })
+        let c_ids = CData {
+            data: ids,
+            len: ids_len,
+        };
+
+        if status == 0 {
+            Ok(f(&c_ids))
+        } else {
+            Err(c_error(status))
+        }
+    }
+
+    /// Encode a sentence as piece strings.
+    ///
+    /// This is faster than [`SentencePieceProcessor::encode`] when only
+    /// the pieces are needed, since the encoding is not passed through a
+    /// protobuf message. Returns `SentencePieceError::InvalidUtf8` when a
+    /// piece is not valid UTF-8.
+    pub fn encode_as_pieces(&self, sentence: &str) -> Result<Vec<String>, SentencePieceError> {
+        let mut pieces = std::ptr::null_mut::<u8>();
+        let mut piece_lens = std::ptr::null_mut::<usize>();
+        let mut n_pieces = 0;
+
+        let status = unsafe {
+            spp_encode_as_pieces(
+                self.inner,
+                sentence.as_ptr() as *const c_char,
+                sentence.as_bytes().len(),
+                &mut pieces,
+                &mut piece_lens,
+                &mut n_pieces,
+            )
+        };
+
+        let c_piece_lens = CData {
+            data: piece_lens,
+            len: n_pieces,
+        };
+        let c_pieces = CData {
+            data: pieces,
+            len: c_piece_lens.iter().sum(),
+        };
+
+        if status != 0 {
+            return Err(c_error(status));
+        }
+
+        let mut rest = &*c_pieces;
+        c_piece_lens
+            .iter()
+            .map(|&len| {
+                let (piece, tail) = rest.split_at(len);
+                rest = tail;
+                String::from_utf8(piece.to_owned())
+                    .map_err(|err| SentencePieceError::InvalidUtf8(err.into_bytes()))
+            })
+            .collect()
     }
 
     pub fn eos_id(&self) -> Option<u32> {
@@ -319,14 +878,62 @@ impl SentencePieceProcessor {
         }
     }
 
+    /// Check whether a piece is a byte fallback piece, such as `<0x41>`.
+    ///
+    /// Returns `false` when the identifier is out of range.
+    pub fn is_byte(&self, id: u32) -> bool {
+        (id as usize) < self.len() && unsafe { spp_is_byte(self.inner, id as c_int) }
+    }
+
+    /// Check whether a piece is a control piece, such as `<s>`.
+    ///
+    /// Control pieces are decoded as the empty string. Returns `false`
+    /// when the identifier is out of range.
+    pub fn is_control(&self, id: u32) -> bool {
+        (id as usize) < self.len() && unsafe { spp_is_control(self.inner, id as c_int) }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Get the number of pieces in the vocabulary.
+    ///
+    /// Panics when sentencepiece reports a negative size. Use
+    /// [`SentencePieceProcessor::try_len`] to handle this case.
     pub fn len(&self) -> usize {
+        self.try_len()
+            .expect("sentencepiece returned a negative vocabulary size")
+    }
+
+    /// Get the number of pieces in the vocabulary.
+    ///
+    /// Returns `None` when sentencepiece reports a negative size, which
+    /// indicates a corrupted model.
+    pub fn try_len(&self) -> Option<usize> {
         let len = unsafe { spp_piece_size(self.inner) };
-        assert!(len >= 0);
-        len as usize
+        if len < 0 {
+            None
+        } else {
+            Some(len as usize)
+        }
+    }
+
+    /// Get the model as a protobuf message.
+    ///
+    /// Returns `SentencePieceError::InvalidProtobuf` when the model
+    /// contains a piece that is not valid UTF-8, since pieces of a
+    /// [`ModelProto`] are strings.
+    pub fn model_proto(&self) -> Result<ModelProto, SentencePieceError> {
+        ModelProto::parse(&self.to_serialized_proto())
+    }
+
+    /// Check whether a piece is unused.
+    ///
+    /// Unused pieces are never produced by encoding. Returns `false`
+    /// when the identifier is out of range.
+    pub fn is_unused(&self, id: u32) -> bool {
+        (id as usize) < self.len() && unsafe { spp_is_unused(self.inner, id as c_int) }
     }
 
     pub fn pad_id(&self) -> Option<u32> {
@@ -338,10 +945,77 @@ impl SentencePieceProcessor {
         }
     }
 
+    /// Get the piece of an identifier as raw bytes.
+    ///
+    /// The bytes are the piece as stored in the model. Some pieces, such
+    /// as user-defined symbols, are not guaranteed to be valid UTF-8.
+    /// Byte pieces are stored in their textual form (e.g. `<0x0A>`).
+    ///
+    /// Returns `None` when the identifier is out of range or when
+    /// sentencepiece raises an exception.
+    pub fn piece_bytes(&self, id: u32) -> Option<&[u8]> {
+        if id as usize >= self.len() {
+            return None;
+        }
+
+        let mut len = 0;
+        let data = unsafe { spp_id_to_piece(self.inner, id as c_int, &mut len) };
+        if data.is_null() {
+            return None;
+        }
+
+        // The piece is owned by the model, so it lives as long as `self`.
+        Some(unsafe { slice::from_raw_parts(data as *const u8, len) })
+    }
+
+    /// Cache the identifiers of pieces.
+    ///
+    /// After calling this method, [`SentencePieceProcessor::piece_to_id`]
+    /// and [`SentencePieceProcessor::pieces_to_ids`] look up pieces in a
+    /// hash table rather than calling into sentencepiece. This speeds up
+    /// repeated lookups at the cost of memory proportional to the size of
+    /// the vocabulary.
+    pub fn cache_piece_ids(&mut self) {
+        let piece_ids = (0..self.len() as u32)
+            .filter(|&id| !unsafe { spp_is_unknown(self.inner, id as c_int) })
+            .filter_map(|id| Some((self.id_to_piece(id).ok()?.to_owned(), id)))
+            .collect();
+        self.piece_ids = Some(Arc::new(piece_ids));
+    }
+
+    /// Get the piece of an identifier.
+    ///
+    /// Returns `SentencePieceError::InvalidPieceId` when the identifier
+    /// is out of range and `SentencePieceError::InvalidUtf8` when the
+    /// piece is not valid UTF-8. Use
+    /// [`SentencePieceProcessor::piece_bytes`] to get such pieces.
+    pub fn id_to_piece(&self, id: u32) -> Result<&str, SentencePieceError> {
+        let piece = self
+            .piece_bytes(id)
+            .ok_or(SentencePieceError::InvalidPieceId(id))?;
+        std::str::from_utf8(piece).map_err(|_| SentencePieceError::InvalidUtf8(piece.to_vec()))
+    }
+
     /// Get the identifier of a sentence piece.
+    ///
+    /// Panics when sentencepiece raises an exception, which only happens
+    /// when memory cannot be allocated.
     pub fn piece_to_id(&self, piece: &str) -> Result<Option<u32>, NulError> {
+        if let Some(piece_ids) = &self.piece_ids {
+            // Fall through to sentencepiece to report nul bytes.
+            if !piece.as_bytes().contains(&0) {
+                return Ok(piece_ids.get(piece).copied());
+            }
+        }
+
         let c_piece = CString::new(piece.as_bytes())?;
         let id = unsafe { spp_piece_to_id(self.inner, c_piece.as_ptr()) };
+        if id == SPP_EXCEPTION {
+            panic!(
+                "sentencepiece raised an exception during piece lookup: {}",
+                exception_message()
+            );
+        }
 
         if unsafe { spp_is_unknown(self.inner, id) } {
             Ok(None)
@@ -350,16 +1024,61 @@ impl SentencePieceProcessor {
         }
     }
 
-    fn process_encode_protobuf(c_proto: CData) -> Result<Vec<PieceWithId>, SentencePieceError> {
-        // Errors are communicated as empty data.
-        if c_proto.len() == 0 {
-            return Err(SentencePieceError::EncodeError);
+    /// Get the identifiers of sentence pieces.
+    ///
+    /// This converts all pieces in a single call into sentencepiece. The
+    /// identifier of a piece is `None` when the piece is not in the
+    /// vocabulary. In contrast to [`SentencePieceProcessor::piece_to_id`],
+    /// pieces can contain nul bytes.
+    pub fn pieces_to_ids(
+        &self,
+        pieces: &[impl AsRef<str>],
+    ) -> Result<Vec<Option<u32>>, SentencePieceError> {
+        if let Some(piece_ids) = &self.piece_ids {
+            return Ok(pieces
+                .iter()
+                .map(|piece| piece_ids.get(piece.as_ref()).copied())
+                .collect());
+        }
+
+        let concatenated = pieces
+            .iter()
+            .map(|piece| piece.as_ref())
+            .collect::<String>();
+        let piece_lens = pieces
+            .iter()
+            .map(|piece| piece.as_ref().len())
+            .collect::<Vec<_>>();
+
+        let mut ids = vec![0u32; pieces.len()];
+        let status = unsafe {
+            spp_pieces_to_ids(
+                self.inner,
+                concatenated.as_ptr() as *const c_char,
+                piece_lens.as_ptr(),
+                pieces.len(),
+                ids.as_mut_ptr(),
+            )
+        };
+
+        if status != 0 {
+            return Err(c_error(status));
         }
 
-        let proto: Vec<u8> = c_proto.to_owned();
-        let sp_text: SentencePieceText = prost::Message::decode(proto.as_slice())
-            .expect("Received invalid protobuf from sentencepiece");
+        let unk_id = self.unk_id();
+        Ok(ids
+            .into_iter()
+            .map(|id| if id == unk_id { None } else { Some(id) })
+            .collect())
+    }
 
+    fn process_encode_protobuf(c_proto: CData) -> Result<Vec<PieceWithId>, SentencePieceError> {
+        Self::pieces_from_proto(Self::decode_encode_protobuf(c_proto)?)
+    }
+
+    fn pieces_from_proto(
+        sp_text: SentencePieceText,
+    ) -> Result<Vec<PieceWithId>, SentencePieceError> {
         // Most fields in the sentencepiece protobuf are optionals. Let's be
         // defensive about absent fields for a piece.
         sp_text
@@ -386,42 +1105,445 @@ impl SentencePieceProcessor {
             .collect::<Result<_, _>>()
     }
 
-    /// Encode a sentence using sampling (subword regularization).
+    /// Encode a sentence as a protobuf message.
+    ///
+    /// In contrast to [`SentencePieceProcessor::encode`], the message
+    /// also contains the surface strings of the pieces.
+    pub fn encode_as_proto(&self, sentence: &str) -> Result<SentencePieceText, SentencePieceError> {
+        self.decode_encode_protobuf_into(sentence.as_bytes())
+    }
+
+    /// Encode a sentence as pieces with the score of the segmentation.
+    ///
+    /// The score is the log probability of the segmentation. Only
+    /// unigram models score segmentations, the score is `None` for other
+    /// models.
+    pub fn encode_with_score(
+        &self,
+        sentence: &str,
+    ) -> Result<(Vec<PieceWithId>, Option<f32>), SentencePieceError> {
+        if self.checked_model_type()? != ModelType::Unigram {
+            return Ok((self.encode(sentence)?, None));
+        }
+
+        // Regular encoding does not return a score and 1-best encoding
+        // returns the score 0: `Model::NBestEncode` in unigram_model.cc
+        // falls back to `Encode` when `nbest_size <= 1`. The best
+        // segmentation of 2-best encoding is the Viterbi segmentation.
+        let mut nbest = self.nbest_encode(sentence, 2)?;
+        if nbest.is_empty() {
+            return Ok((self.encode(sentence)?, None));
+        }
+
+        let (pieces, score) = nbest.swap_remove(0);
+        Ok((pieces, Some(score)))
+    }
+
+    /// Score a segmentation.
+    ///
+    /// The score is the sum of the scores of the pieces, which is the log
+    /// probability of the segmentation in a unigram model. This can be
+    /// used to compare alternative segmentations of a sentence.
+    ///
+    /// Returns `SentencePieceError::UnsupportedModelType` if the model
+    /// is not a unigram model and `SentencePieceError::MissingPiece` if a
+    /// piece is not in the vocabulary.
+    pub fn score_pieces(&self, pieces: &[impl AsRef<str>]) -> Result<f32, SentencePieceError> {
+        let model_type = self.checked_model_type()?;
+        if model_type != ModelType::Unigram {
+            return Err(SentencePieceError::UnsupportedModelType(model_type));
+        }
+
+        pieces.iter().try_fold(0., |score, piece| {
+            let piece = piece.as_ref();
+            let id = self
+                .piece_to_id(piece)
+                .map_err(|_| SentencePieceError::PieceContainsNul)?
+                .ok_or_else(|| SentencePieceError::MissingPiece(piece.to_string()))?;
+            let piece_score = unsafe { spp_get_score(self.inner, id as c_int) };
+            if piece_score.is_nan() {
+                return Err(SentencePieceError::Internal(exception_message()));
+            }
+            Ok(score + piece_score)
+        })
+    }
+
+    /// Encode a sentence as pieces with byte fallback flags.
+    ///
+    /// The flag of a piece is `true` when the piece is a byte fallback
+    /// piece, such as `<0x41>`. Consecutive byte pieces encode the UTF-8
+    /// bytes of a character that is not in the vocabulary.
+    pub fn encode_with_byte_flags(
+        &self,
+        sentence: &str,
+    ) -> Result<Vec<(PieceWithId, bool)>, SentencePieceError> {
+        Ok(self
+            .encode(sentence)?
+            .into_iter()
+            .map(|piece| {
+                let is_byte = self.is_byte(piece.id);
+                (piece, is_byte)
+            })
+            .collect())
+    }
+
+    /// Encode a sentence as pieces with their surface strings.
+    ///
+    /// The surface string of a piece is the substring of the input that
+    /// the piece covers.
+    pub fn encode_with_surfaces(
+        &self,
+        sentence: &str,
+    ) -> Result<Vec<(PieceWithId, String)>, SentencePieceError> {
+        let mut sp_text = self.encode_as_proto(sentence)?;
+        let surfaces = sp_text
+            .pieces
+            .iter_mut()
+            .map(|piece| {
+                piece
+                    .surface
+                    .take()
+                    .ok_or_else(|| SentencePieceError::MissingData("surface".to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::pieces_from_proto(sp_text)?
+            .into_iter()
+            .zip(surfaces)
+            .collect())
+    }
+
+    fn decode_encode_protobuf<M>(c_proto: CData) -> Result<M, SentencePieceError>
+    where
+        M: prost::Message + Default,
+    {
+        // Exceptions are communicated as null data.
+        if c_proto.data.is_null() {
+            return Err(SentencePieceError::Internal(exception_message()));
+        }
+
+        // Errors are communicated as empty data.
+        if c_proto.len() == 0 {
+            return Err(SentencePieceError::EncodeError);
+        }
+
+        Ok(
+            prost::Message::decode(&*c_proto)
+                .expect("Received invalid protobuf from sentencepiece"),
+        )
+    }
+
+    /// Encode a sentence and decode the resulting protobuf message.
+    ///
+    /// The serialized message is stored in the scratch buffers of the
+    /// current thread.
+    fn decode_encode_protobuf_into<M>(&self, sentence: &[u8]) -> Result<M, SentencePieceError>
+    where
+        M: prost::Message + Default,
+    {
+        with_scratch(|scratch| {
+            let mut serialized_len = 0;
+
+            let status = unsafe {
+                spp_encode_as_serialized_proto_into(
+                    self.inner,
+                    sentence.as_ptr() as *const c_char,
+                    sentence.len(),
+                    Some(reserve_buffer),
+                    buffer_ctx(&mut scratch.data),
+                    &mut serialized_len,
+                )
+            };
+
+            match status {
+                0 => (),
+                SPP_EXCEPTION => return Err(c_error(status)),
+                _ => return Err(SentencePieceError::EncodeError),
+            }
+
+            unsafe { scratch.data.set_len(serialized_len) };
+
+            Ok(prost::Message::decode(&*scratch.data)
+                .expect("Received invalid protobuf from sentencepiece"))
+        })
+    }
+
+    /// Encode a sentence as its `n_best` best segmentations.
+    ///
+    /// Returns the segmentations with their scores, from best to worst.
+    /// Only unigram models support n-best encoding.
+    pub fn nbest_encode(
+        &self,
+        sentence: &str,
+        n_best: usize,
+    ) -> Result<Vec<(Vec<PieceWithId>, f32)>, SentencePieceError> {
+        let mut serialized = std::ptr::null_mut::<u8>();
+        let mut serialized_len = 0;
+        let status = unsafe {
+            spp_nbest_encode_as_serialized_proto(
+                self.inner,
+                sentence.as_ptr() as *const c_char,
+                sentence.as_bytes().len(),
+                n_best,
+                &mut serialized,
+                &mut serialized_len,
+            )
+        };
+
+        let c_proto = CData {
+            data: serialized,
+            len: serialized_len,
+        };
+
+        if status != 0 {
+            return Err(c_error(status));
+        }
+
+        Self::nbest_from_proto(
+            prost::Message::decode(&*c_proto)
+                .expect("Received invalid protobuf from sentencepiece"),
+        )
+    }
+
+    fn process_nbest_encode_protobuf(
+        c_proto: CData,
+    ) -> Result<Vec<(Vec<PieceWithId>, f32)>, SentencePieceError> {
+        Self::nbest_from_proto(Self::decode_encode_protobuf(c_proto)?)
+    }
+
+    fn nbest_from_proto(
+        nbest_text: NBestSentencePieceText,
+    ) -> Result<Vec<(Vec<PieceWithId>, f32)>, SentencePieceError> {
+        nbest_text
+            .nbests
+            .into_iter()
+            .map(|sp_text| {
+                let score = sp_text
+                    .score
+                    .ok_or_else(|| SentencePieceError::MissingData("score".to_string()))?;
+                Ok((Self::pieces_from_proto(sp_text)?, score))
+            })
+            .collect()
+    }
+
+    /// Encode a sentence using sampling (subword regularization).
+    ///
+    /// Sample for the `n_best` segmentations, where alpha controls the
+    /// smoothness of the distribution.
+    ///
+    /// Returns `SentencePieceError::InvalidArgument` when `n_best > 512` or
+    /// when alpha is not a (normal) positive floating point number.
+    pub fn sample_encode(
+        &self,
+        sentence: &str,
+        n_best: usize,
+        alpha: f32,
+    ) -> Result<Vec<PieceWithId>, SentencePieceError> {
+        if n_best > 512 {
+            return Err(SentencePieceError::InvalidArgument(format!(
+                "n_best must be at most 512, was {}",
+                n_best
+            )));
+        }
+        if !(alpha.is_normal() && alpha.is_positive()) {
+            return Err(SentencePieceError::InvalidArgument(format!(
+                "alpha must be a positive normal number, was {}",
+                alpha
+            )));
+        }
+
+        let mut len = 0usize;
+        let c_proto = unsafe {
+            spp_sample_encode_as_serialized_proto(
+                self.inner,
+                sentence.as_ptr() as *const c_char,
+                sentence.as_bytes().len(),
+                &mut len,
+                n_best,
+                alpha,
+            )
+        };
+
+        Self::process_encode_protobuf(CData { data: c_proto, len })
+    }
+
+    /// Set extra decoding options.
+    ///
+    /// The options are passed as-is to sentencepiece, which makes it
+    /// possible to use options that are not covered by this crate. The
+    /// options are separated by colons, e.g. `reverse`.
+    pub fn set_decode_extra_options(&mut self, options: &str) -> Result<(), SentencePieceError> {
+        self.make_unique()?;
+
+        let status = unsafe {
+            spp_set_decode_extra_options(
+                self.inner,
+                options.as_ptr() as *const c_char,
+                options.len(),
+            )
+        };
+
+        if status == 0 {
+            self.decode_extra_options = options.to_owned();
+            Ok(())
+        } else {
+            Err(c_error(status))
+        }
+    }
+
+    /// Set extra encoding options.
+    ///
+    /// The options are passed as-is to sentencepiece, which makes it
+    /// possible to use options that are not covered by this crate. The
+    /// options are separated by colons, e.g. `bos:eos:reverse`.
+    pub fn set_encode_extra_options(&mut self, options: &str) -> Result<(), SentencePieceError> {
+        self.make_unique()?;
+
+        let status = unsafe {
+            spp_set_encode_extra_options(
+                self.inner,
+                options.as_ptr() as *const c_char,
+                options.len(),
+            )
+        };
+
+        if status == 0 {
+            self.encode_extra_options = options.to_owned();
+            Ok(())
+        } else {
+            Err(c_error(status))
+        }
+    }
+
+    /// Restrict the vocabulary that is used for encoding.
+    ///
+    /// Normal pieces that are not in `pieces` are not used for encoding,
+    /// so that the input is encoded with smaller pieces instead. This is
+    /// the same as the `--vocabulary` option of `spm_encode`. Only
+    /// unigram and BPE models support vocabulary restriction.
+    pub fn set_vocabulary(&mut self, pieces: &[impl AsRef<str>]) -> Result<(), SentencePieceError> {
+        self.make_unique()?;
+
+        let concatenated = pieces
+            .iter()
+            .map(|piece| piece.as_ref())
+            .collect::<String>();
+        let piece_lens = pieces
+            .iter()
+            .map(|piece| piece.as_ref().len())
+            .collect::<Vec<_>>();
+
+        let status = unsafe {
+            spp_set_vocabulary(
+                self.inner,
+                concatenated.as_ptr() as *const c_char,
+                piece_lens.as_ptr(),
+                pieces.len(),
+            )
+        };
+
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(c_error(status))
+        }
+    }
+
+    /// Restrict the vocabulary to the pieces of a vocabulary file.
+    ///
+    /// Every line of the file contains a piece and its frequency,
+    /// separated by a tab, as generated by `spm_encode
+    /// --generate_vocabulary`. Pieces with a frequency below `threshold`
+    /// are not used for encoding. See
+    /// [`SentencePieceProcessor::set_vocabulary`] for details.
+    pub fn load_vocabulary(
+        &mut self,
+        path: impl AsRef<Path>,
+        threshold: u32,
+    ) -> Result<(), SentencePieceError> {
+        self.make_unique()?;
+
+        let c_filename = path_to_c_filename(path.as_ref())?;
+        let threshold = threshold.min(c_int::MAX as u32) as c_int;
+        let status = unsafe { spp_load_vocabulary(self.inner, c_filename.as_ptr(), threshold) };
+
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(c_error(status))
+        }
+    }
+
+    /// Remove the vocabulary restriction.
     ///
-    /// Sample for the `n_best` segmentations, where alpha controls the
-    /// smoothness of the distribution.
+    /// See [`SentencePieceProcessor::set_vocabulary`].
+    pub fn reset_vocabulary(&mut self) -> Result<(), SentencePieceError> {
+        self.make_unique()?;
+
+        let status = unsafe { spp_reset_vocabulary(self.inner) };
+
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(c_error(status))
+        }
+    }
+
+    /// Sample segmentations of a sentence with their scores.
     ///
-    /// This method panics when `n_best > 512` or when alpha is not a (normal)
-    /// positive floating point number.
-    pub fn sample_encode(
+    /// Samples `num_samples` segmentations, where `alpha` controls the
+    /// smoothness of the distribution. When `wor` is `true`, the
+    /// segmentations are sampled without replacement. When
+    /// `include_best` is `true`, the best segmentation is always
+    /// included in the samples. Sampling without replacement and
+    /// including the best segmentation are only supported by unigram
+    /// models.
+    pub fn sample_encode_and_score(
         &self,
         sentence: &str,
-        n_best: usize,
+        num_samples: usize,
         alpha: f32,
-    ) -> Result<Vec<PieceWithId>, SentencePieceError> {
-        assert!(n_best <= 512);
-        assert!(alpha.is_normal() && alpha.is_positive());
-
+        wor: bool,
+        include_best: bool,
+    ) -> Result<Vec<(Vec<PieceWithId>, f32)>, SentencePieceError> {
         let mut len = 0usize;
         let c_proto = unsafe {
-            spp_sample_encode_as_serialized_proto(
+            spp_sample_encode_and_score_as_serialized_proto(
                 self.inner,
                 sentence.as_ptr() as *const c_char,
                 sentence.as_bytes().len(),
                 &mut len,
-                n_best,
+                num_samples,
                 alpha,
+                wor,
+                include_best,
             )
         };
 
-        Self::process_encode_protobuf(CData { data: c_proto, len })
+        Self::process_nbest_encode_protobuf(CData { data: c_proto, len })
     }
 
+    /// Get the identifier of the unknown piece.
+    ///
+    /// Panics when the model does not have an unknown piece. Use
+    /// [`SentencePieceProcessor::try_unk_id`] to handle this case.
     pub fn unk_id(&self) -> u32 {
-        let unk_id = unsafe { spp_unk_id(self.inner) };
         // unk_id must always be present.
-        assert!(unk_id >= 0);
-        unk_id as u32
+        self.try_unk_id()
+            .expect("sentencepiece returned a negative unknown piece identifier")
+    }
+
+    /// Get the identifier of the unknown piece.
+    ///
+    /// Returns `None` when sentencepiece reports a negative identifier,
+    /// which indicates a corrupted model.
+    pub fn try_unk_id(&self) -> Option<u32> {
+        let unk_id = unsafe { spp_unk_id(self.inner) };
+        if unk_id < 0 {
+            None
+        } else {
+            Some(unk_id as u32)
+        }
     }
 }
 
@@ -432,11 +1554,23 @@ unsafe impl Send for SentencePieceProcessor {}
 
 unsafe impl Sync for SentencePieceProcessor {}
 
+unsafe impl Send for OwnedProcessor {}
+
+unsafe impl Sync for OwnedProcessor {}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
+    use std::{env, fs, process};
 
-    use crate::{CSentencePieceError, PieceWithId, SentencePieceError, SentencePieceProcessor};
+    use sentencepiece_sys::SPP_EXCEPTION;
+
+    use crate::proto::ModelProto;
+    use crate::test_util::{byte_fallback_model, invalid_utf8_model, INVALID_UTF8_PIECE};
+    use crate::{
+        c_error, CSentencePieceError, IoError, ModelBuilder, ModelType, PieceType, PieceWithId,
+        SentencePieceError, SentencePieceProcessor, SpecialPiece,
+    };
 
     fn toy_model_proto() -> &'static [u8] {
         include_bytes!("../testdata/toy.model")
@@ -455,6 +1589,71 @@ mod tests {
         assert_eq!(decoded, "I saw a girl with a telescope.");
     }
 
+    #[test]
+    fn decode_reports_invalid_utf8() {
+        let model = invalid_utf8_model();
+        assert_eq!(
+            model.decode_piece_ids(&[3]),
+            Err(SentencePieceError::InvalidUtf8(INVALID_UTF8_PIECE.to_vec()))
+        );
+        assert_eq!(
+            model.decode_piece_ids_bytes(&[3]).unwrap(),
+            INVALID_UTF8_PIECE
+        );
+
+        // sentencepiece replaces byte pieces that do not form valid UTF-8.
+        let model = byte_fallback_model();
+        assert_eq!(model.decode_piece_ids(&[3 + 0xE2]).unwrap(), "\u{FFFD}");
+    }
+
+    #[test]
+    fn decodes_piece_ids_as_bytes_with_toy_model() {
+        let model = toy_model().unwrap();
+        assert_eq!(
+            model.decode_piece_ids_bytes(&[8, 465, 10, 947, 4]).unwrap(),
+            b"I saw a girl."
+        );
+        assert!(matches!(
+            model.decode_piece_ids_bytes(&[8, 1000]),
+            Err(SentencePieceError::CError {
+                code: CSentencePieceError::OutOfRange,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn decodes_i64_piece_ids_with_toy_model() {
+        let model = toy_model().unwrap();
+        assert_eq!(
+            model.decode_piece_ids_i64(&[8, 465, 10, 947, 4]).unwrap(),
+            "I saw a girl."
+        );
+        assert!(matches!(
+            model.decode_piece_ids_i64(&[8, -1]),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            model.decode_piece_ids_i64(&[8, 1 << 32]),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn maps_exception_status_to_internal_error() {
+        assert_eq!(
+            c_error(SPP_EXCEPTION),
+            SentencePieceError::Internal(String::new())
+        );
+        assert!(matches!(
+            c_error(3),
+            SentencePieceError::CError {
+                code: CSentencePieceError::InvalidArgument,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn decodes_pieces_with_toy_model() {
         let model = toy_model().unwrap();
@@ -468,10 +1667,15 @@ mod tests {
     #[test]
     fn decode_with_incorrect_identifier_fails() {
         let model = toy_model().unwrap();
-        assert_eq!(
-            model.decode_piece_ids(&[8, 1000]),
-            Err(SentencePieceError::CError(CSentencePieceError::OutOfRange))
-        );
+        match model.decode_piece_ids(&[8, 1000]) {
+            Err(SentencePieceError::CError {
+                code: CSentencePieceError::OutOfRange,
+                message,
+            }) => {
+                assert!(!message.is_empty())
+            }
+            result => panic!("Expected out of range error, got: {:?}", result),
+        }
     }
 
     #[test]
@@ -557,6 +1761,258 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encodes_sentence_as_proto_with_toy_model() {
+        let model = toy_model().unwrap();
+        let proto = model.encode_as_proto("I saw a girl.").unwrap();
+        assert_eq!(proto.text.as_deref(), Some("I saw a girl."));
+        assert_eq!(
+            proto
+                .pieces
+                .iter()
+                .map(|piece| piece.id.unwrap())
+                .collect::<Vec<_>>(),
+            [8, 465, 10, 947, 4]
+        );
+        assert_eq!(proto.pieces[1].surface.as_deref(), Some(" saw"));
+    }
+
+    #[test]
+    fn clones_share_processor_until_modified() {
+        let mut model = toy_model().unwrap();
+        model.set_encode_extra_options("bos").unwrap();
+        model.cache_piece_ids();
+
+        let clone = model.clone();
+        assert_eq!(clone.inner, model.inner);
+        assert_eq!(clone.encode_as_ids("I saw").unwrap(), [1, 8, 465]);
+
+        model.set_vocabulary(&["▁I"]).unwrap();
+        assert_ne!(clone.inner, model.inner);
+        let restricted = model.encode_as_ids("I saw").unwrap();
+        assert_eq!(restricted[0], 1);
+        assert!(!restricted.contains(&465));
+        assert_eq!(model.piece_to_id("▁saw"), Ok(Some(465)));
+
+        assert_eq!(clone.encode_as_ids("I saw").unwrap(), [1, 8, 465]);
+    }
+
+    #[test]
+    fn looks_up_cached_piece_ids() {
+        let model = toy_model().unwrap();
+        let mut cached = toy_model().unwrap();
+        cached.cache_piece_ids();
+
+        let mut pieces = (0..model.len() as u32)
+            .map(|id| model.id_to_piece(id).unwrap().to_owned())
+            .collect::<Vec<_>>();
+        pieces.push("not-a-piece".to_string());
+        pieces.push("a\0b".to_string());
+
+        assert_eq!(
+            cached.pieces_to_ids(&pieces).unwrap(),
+            model.pieces_to_ids(&pieces).unwrap()
+        );
+        for piece in &pieces[..pieces.len() - 1] {
+            assert_eq!(cached.piece_to_id(piece), model.piece_to_id(piece));
+        }
+        assert_eq!(cached.piece_to_id("<unk>"), Ok(None));
+        assert!(cached.piece_to_id("a\0b").is_err());
+    }
+
+    #[test]
+    fn encodes_sentence_like_proto_encoding() {
+        let model = toy_model().unwrap();
+        for sentence in &["I saw a girl with a telescope.", "  ｆｕｌｌ  width 🤗", ""] {
+            let proto = model.encode_as_proto(sentence).unwrap();
+            assert_eq!(
+                model.encode(sentence).unwrap(),
+                SentencePieceProcessor::pieces_from_proto(proto).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn reuses_buffers_between_calls() {
+        let model = toy_model().unwrap();
+        let long = "I saw a girl with a telescope. ".repeat(100);
+        let short = "I saw";
+
+        let expected = model.encode(short).unwrap();
+        for _ in 0..2 {
+            let pieces = model.encode(&long).unwrap();
+            assert_eq!(
+                pieces.iter().map(|piece| piece.id).collect::<Vec<_>>(),
+                model.encode_as_ids(&long).unwrap()
+            );
+            assert_eq!(model.encode(short).unwrap(), expected);
+            assert_eq!(model.decode_piece_ids(&[8, 465]).unwrap(), short);
+            assert_eq!(model.encode_as_proto(short).unwrap().pieces.len(), 2);
+        }
+
+        let threads = (0..4)
+            .map(|_| {
+                let model = model.clone();
+                std::thread::spawn(move || model.encode(short).unwrap())
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn encodes_sentence_as_ids_with_toy_model() {
+        let model = toy_model().unwrap();
+        assert_eq!(
+            model
+                .encode_as_ids("I saw a girl with a telescope.")
+                .unwrap(),
+            [8, 465, 10, 947, 41, 10, 170, 168, 110, 28, 20, 143, 4]
+        );
+        assert_eq!(model.encode_as_ids("").unwrap(), []);
+    }
+
+    #[test]
+    fn encodes_ids_into_buffer() {
+        let model = toy_model().unwrap();
+        let mut ids = vec![42];
+        model.encode_ids_into("I saw a girl.", &mut ids).unwrap();
+        assert_eq!(ids, [8, 465, 10, 947, 4]);
+        model.encode_ids_into("I saw", &mut ids).unwrap();
+        assert_eq!(ids, [8, 465]);
+    }
+
+    #[test]
+    fn encodes_sentence_with_surfaces() {
+        let model = toy_model().unwrap();
+        let encoded = model.encode_with_surfaces("I saw a girl.").unwrap();
+        assert_eq!(
+            encoded
+                .iter()
+                .map(|(_, surface)| surface.as_str())
+                .collect::<Vec<_>>(),
+            ["I", " saw", " a", " girl", "."]
+        );
+        assert_eq!(
+            encoded
+                .into_iter()
+                .map(|(piece, _)| piece)
+                .collect::<Vec<_>>(),
+            model.encode("I saw a girl.").unwrap()
+        );
+    }
+
+    #[test]
+    fn encodes_sentence_with_score() {
+        let model = toy_model().unwrap();
+        let (pieces, score) = model.encode_with_score("I saw a girl.").unwrap();
+        assert_eq!(pieces, model.encode("I saw a girl.").unwrap());
+
+        assert!(score.unwrap() < 0.);
+
+        let (pieces, score) = invalid_utf8_model().encode_with_score("x").unwrap();
+        assert_eq!(pieces.len(), 1);
+        assert!(score.is_some());
+    }
+
+    #[test]
+    fn scores_segmentation() {
+        let model = toy_model().unwrap();
+        let (pieces, score) = model.encode_with_score("I saw a girl.").unwrap();
+        let pieces = pieces
+            .into_iter()
+            .map(|piece| piece.piece)
+            .collect::<Vec<_>>();
+        assert!((model.score_pieces(&pieces).unwrap() - score.unwrap()).abs() < 1e-4);
+
+        // The best segmentation has the highest score.
+        let alternative = ["▁I", "▁s", "a", "w", "▁a", "▁girl", "."];
+        assert!(model.score_pieces(&alternative).unwrap() < score.unwrap());
+
+        assert_eq!(
+            model.score_pieces(&["▁I", "☃"]),
+            Err(SentencePieceError::MissingPiece("☃".to_string()))
+        );
+        assert_eq!(invalid_utf8_model().score_pieces(&["<s>", "</s>"]), Ok(0.));
+    }
+
+    #[test]
+    fn encodes_sentence_as_pieces_with_toy_model() {
+        let model = toy_model().unwrap();
+        assert_eq!(
+            model.encode_as_pieces("I saw a girl.").unwrap(),
+            ["▁I", "▁saw", "▁a", "▁girl", "."]
+        );
+        assert!(model.encode_as_pieces("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn nbest_encodes_sentence_with_toy_model() {
+        let model = toy_model().unwrap();
+        let nbest = model.nbest_encode("I saw a girl.", 3).unwrap();
+        assert_eq!(nbest.len(), 3);
+        assert_eq!(nbest[0].0, model.encode("I saw a girl.").unwrap());
+        assert!(nbest.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    #[test]
+    fn nbest_encode_reports_failures() {
+        let model = ModelBuilder::new()
+            .model_type(ModelType::Bpe)
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("▁a", -1.0, PieceType::Normal)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            model.nbest_encode("a", 2),
+            Err(SentencePieceError::CError { .. })
+        ));
+        assert_eq!(model.encode_with_score("a").unwrap().1, None);
+    }
+
+    #[test]
+    fn sample_encodes_and_scores_sentence_with_toy_model() {
+        let model = toy_model().unwrap();
+        let samples = model
+            .sample_encode_and_score("I saw a girl.", 5, 0.5, true, true)
+            .unwrap();
+        assert_eq!(samples.len(), 5);
+
+        let best = model.encode("I saw a girl.").unwrap();
+        assert!(samples.iter().any(|(pieces, _)| *pieces == best));
+    }
+
+    #[test]
+    fn encodes_with_extra_options() {
+        let mut model = toy_model().unwrap();
+        model.set_encode_extra_options("bos:eos").unwrap();
+        assert_eq!(
+            model
+                .encode("I saw a girl.")
+                .unwrap()
+                .into_iter()
+                .map(|piece| piece.id)
+                .collect::<Vec<_>>(),
+            [1, 8, 465, 10, 947, 4, 2]
+        );
+
+        model.set_decode_extra_options("reverse").unwrap();
+        assert_eq!(
+            model.decode_piece_ids(&[4, 947, 10, 465, 8]).unwrap(),
+            "I saw a girl."
+        );
+
+        assert!(matches!(
+            model.set_encode_extra_options("bos:foo"),
+            Err(SentencePieceError::CError { .. })
+        ));
+        assert!(matches!(
+            model.set_decode_extra_options("foo"),
+            Err(SentencePieceError::CError { .. })
+        ));
+    }
+
     #[test]
     fn sample_encodes_sentence_with_toy_model() {
         let model = toy_model().unwrap();
@@ -590,6 +2046,91 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn sample_encode_rejects_invalid_arguments() {
+        let model = toy_model().unwrap();
+        assert!(matches!(
+            model.sample_encode("I saw a girl.", 10, f32::NAN),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            model.sample_encode("I saw a girl.", 10, -0.5),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            model.sample_encode("I saw a girl.", 513, 0.1),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn decodes_piece_ids_with_offsets() {
+        let model = toy_model().unwrap();
+        let sp_text = model
+            .decode_piece_ids_with_offsets(&[8, 465, 10, 947, 4])
+            .unwrap();
+        assert_eq!(sp_text.text.as_deref(), Some("I saw a girl."));
+        assert_eq!(
+            sp_text
+                .pieces
+                .iter()
+                .map(|piece| (piece.id.unwrap(), piece.surface.as_deref().unwrap()))
+                .collect::<Vec<_>>(),
+            [
+                (8, "I"),
+                (465, " saw"),
+                (10, " a"),
+                (947, " girl"),
+                (4, ".")
+            ]
+        );
+        assert_eq!(
+            sp_text
+                .pieces
+                .iter()
+                .map(|piece| (piece.begin.unwrap(), piece.end.unwrap()))
+                .collect::<Vec<_>>(),
+            [(0, 1), (1, 5), (5, 7), (7, 12), (12, 13)]
+        );
+
+        assert_eq!(
+            model.decode_piece_ids_with_offsets(&[8, 1000]),
+            Err(SentencePieceError::InvalidPieceId(1000))
+        );
+    }
+
+    #[test]
+    fn restricts_vocabulary() {
+        let mut model = toy_model().unwrap();
+        let unrestricted = model.encode_as_ids("I saw a girl.").unwrap();
+        assert!(unrestricted.contains(&465));
+
+        model.set_vocabulary(&["▁I", "▁a", "▁girl", "."]).unwrap();
+        let restricted = model.encode_as_ids("I saw a girl.").unwrap();
+        assert!(!restricted.contains(&465));
+        assert_eq!(
+            model.decode_piece_ids(&restricted).unwrap(),
+            "I saw a girl."
+        );
+
+        model.reset_vocabulary().unwrap();
+        assert_eq!(model.encode_as_ids("I saw a girl.").unwrap(), unrestricted);
+    }
+
+    #[test]
+    fn restricts_vocabulary_from_file() {
+        let path = env::temp_dir().join(format!("sentencepiece-vocab-{}.txt", process::id()));
+        fs::write(&path, "▁I\t10\n▁saw\t1\n▁a\t10\n▁girl\t10\n.\t10\n").unwrap();
+
+        let mut model = toy_model().unwrap();
+        model.load_vocabulary(&path, 1).unwrap();
+        assert!(model.encode_as_ids("I saw a girl.").unwrap().contains(&465));
+
+        model.load_vocabulary(&path, 2).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!model.encode_as_ids("I saw a girl.").unwrap().contains(&465));
+    }
+
     #[test]
     fn errors_on_path_with_nul() {
         let test_path = Path::new("test\0path");
@@ -601,10 +2142,15 @@ mod tests {
 
     #[test]
     fn fails_loading_nonexisting_model() {
-        assert_eq!(
-            SentencePieceProcessor::open("non-existing").unwrap_err(),
-            SentencePieceError::CError(CSentencePieceError::NotFound)
-        );
+        match SentencePieceProcessor::open("non-existing").unwrap_err() {
+            SentencePieceError::CError {
+                code: CSentencePieceError::NotFound,
+                message,
+            } => {
+                assert!(message.contains("non-existing"))
+            }
+            err => panic!("Expected not found error, got: {:?}", err),
+        }
     }
 
     #[test]
@@ -652,6 +2198,130 @@ mod tests {
         assert!(toy_model().is_ok());
     }
 
+    #[test]
+    fn loads_model_from_reader() {
+        let model = SentencePieceProcessor::from_reader(toy_model_proto()).unwrap();
+        assert_eq!(
+            model.model_proto().unwrap(),
+            toy_model().unwrap().model_proto().unwrap()
+        );
+
+        assert!(matches!(
+            SentencePieceProcessor::from_reader(&b"\xff"[..]),
+            Err(IoError::SentencePiece(_))
+        ));
+    }
+
+    #[test]
+    fn parses_model_protobuf() {
+        let model = ModelProto::parse(toy_model_proto()).unwrap();
+        assert_eq!(model.pieces.len(), 1000);
+        assert_eq!(model.pieces[5].piece.as_deref(), Some("▁the"));
+        assert!(model.trainer_spec.is_some());
+        assert!(model.normalizer_spec.is_some());
+        assert_eq!(model, toy_model().unwrap().model_proto().unwrap());
+        assert_eq!(ModelProto::parse(&model.to_bytes()).unwrap(), model);
+
+        assert!(matches!(
+            ModelProto::parse(b"\xff"),
+            Err(SentencePieceError::InvalidProtobuf(_))
+        ));
+    }
+
+    #[test]
+    fn adds_pieces_to_model() {
+        let mut model = ModelProto::parse(toy_model_proto()).unwrap();
+        assert_eq!(
+            model.add_piece("<mask>", 0.0, PieceType::UserDefined),
+            Ok(1000)
+        );
+        assert!(matches!(
+            model.add_piece("▁the", 0.0, PieceType::Normal),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+
+        let spp = SentencePieceProcessor::from_model_proto(&model).unwrap();
+        assert_eq!(spp.len(), 1001);
+        assert!(spp
+            .encode_as_ids("I saw a <mask>.")
+            .unwrap()
+            .contains(&1000));
+    }
+
+    #[test]
+    fn overrides_special_pieces() {
+        let model = toy_model().unwrap();
+        assert_eq!(model.pad_id(), None);
+
+        let padded = model
+            .with_special_piece(SpecialPiece::Pad, "<pad>")
+            .unwrap();
+        assert_eq!(padded.pad_id(), Some(1000));
+        assert_eq!(padded.id_to_piece(1000), Ok("<pad>"));
+
+        let renamed = model
+            .with_special_piece(SpecialPiece::Unknown, "[UNK]")
+            .unwrap();
+        assert_eq!(renamed.unk_id(), 0);
+        assert_eq!(renamed.id_to_piece(0), Ok("[UNK]"));
+
+        let bos = model.with_special_piece(SpecialPiece::Bos, "<s>").unwrap();
+        assert_eq!(bos.bos_id(), Some(1));
+
+        assert!(matches!(
+            model.with_special_piece(SpecialPiece::Pad, "▁the"),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn prunes_vocabulary() {
+        let model = toy_model().unwrap();
+        let (pruned, id_map) = model.prune_vocab(500).unwrap();
+        assert_eq!(pruned.len(), 500);
+        assert_eq!(id_map.len(), 1000);
+        assert_eq!(id_map.iter().flatten().count(), 500);
+        assert_eq!(id_map[..3], [Some(0), Some(1), Some(2)]);
+        assert_eq!(
+            (pruned.unk_id(), pruned.bos_id(), pruned.eos_id()),
+            (0, Some(1), Some(2))
+        );
+
+        for (old_id, new_id) in id_map.iter().enumerate() {
+            if let Some(new_id) = new_id {
+                assert_eq!(
+                    pruned.piece_bytes(*new_id),
+                    model.piece_bytes(old_id as u32)
+                );
+            }
+        }
+
+        let ids = pruned.encode_as_ids("I saw a girl.").unwrap();
+        assert_eq!(pruned.decode_piece_ids(&ids).unwrap(), "I saw a girl.");
+
+        assert!(matches!(
+            model.prune_vocab(2),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn model_surgery_rejects_invalid_utf8_pieces() {
+        let model = invalid_utf8_model();
+        assert!(matches!(
+            model.model_proto(),
+            Err(SentencePieceError::InvalidProtobuf(_))
+        ));
+        assert!(matches!(
+            model.with_special_piece(SpecialPiece::Pad, "<pad>"),
+            Err(SentencePieceError::InvalidProtobuf(_))
+        ));
+        assert!(matches!(
+            model.prune_vocab(3),
+            Err(SentencePieceError::InvalidProtobuf(_))
+        ));
+    }
+
     #[test]
     fn can_lookup_piece_id() {
         let toy_model = toy_model().unwrap();
@@ -659,6 +2329,86 @@ mod tests {
         assert_eq!(toy_model.piece_to_id("unknown"), Ok(None));
     }
 
+    #[test]
+    fn can_lookup_piece_of_id() {
+        let toy_model = toy_model().unwrap();
+        assert_eq!(toy_model.id_to_piece(5), Ok("▁the"));
+        assert_eq!(toy_model.id_to_piece(0), Ok("<unk>"));
+        assert_eq!(
+            toy_model.id_to_piece(1000),
+            Err(SentencePieceError::InvalidPieceId(1000))
+        );
+    }
+
+    #[test]
+    fn queries_piece_types() {
+        let model = ModelBuilder::new()
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("<s>", 0.0, PieceType::Control)
+            .piece("</s>", 0.0, PieceType::Control)
+            .pieces((0..=255u8).map(|byte| (format!("<0x{:02X}>", byte), 0.0, PieceType::Byte)))
+            .piece("▁", -2.0, PieceType::Normal)
+            .piece("a", -3.0, PieceType::Normal)
+            .piece("b", -3.0, PieceType::Unused)
+            .build()
+            .unwrap();
+        let a = model.piece_to_id("a").unwrap().unwrap();
+        let b = model.piece_to_id("b").unwrap().unwrap();
+
+        assert!(model.is_control(1));
+        assert!(!model.is_control(a));
+        assert!(model.is_byte(3 + 0x41));
+        assert!(!model.is_byte(a));
+        assert!(model.is_unused(b));
+        assert!(!model.is_unused(a));
+
+        let out_of_range = model.len() as u32;
+        assert!(!model.is_control(out_of_range));
+        assert!(!model.is_byte(out_of_range));
+        assert!(!model.is_unused(out_of_range));
+    }
+
+    #[test]
+    fn encodes_with_byte_flags() {
+        let model = byte_fallback_model();
+        let flags = model
+            .encode_with_byte_flags("a☃a")
+            .unwrap()
+            .into_iter()
+            .map(|(piece, is_byte)| (piece.piece, is_byte))
+            .filter(|(piece, _)| piece != "▁")
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            [
+                ("a".to_string(), false),
+                ("<0xE2>".to_string(), true),
+                ("<0x98>".to_string(), true),
+                ("<0x83>".to_string(), true),
+                ("a".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn can_lookup_ids_of_pieces() {
+        let toy_model = toy_model().unwrap();
+        assert_eq!(
+            toy_model
+                .pieces_to_ids(&["▁the", "▁girl", "☃", "<unk>", "\0"])
+                .unwrap(),
+            [Some(5), Some(947), None, None, None]
+        );
+        assert!(toy_model.pieces_to_ids(&[] as &[&str]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn can_lookup_piece_bytes() {
+        let toy_model = toy_model().unwrap();
+        assert_eq!(toy_model.piece_bytes(5), Some("▁the".as_bytes()));
+        assert_eq!(toy_model.piece_bytes(1000), None);
+    }
+
     #[test]
     fn can_lookup_bos_id() {
         let toy_model = toy_model().unwrap();
@@ -682,12 +2432,14 @@ mod tests {
     fn can_lookup_unk_id() {
         let toy_model = toy_model().unwrap();
         assert_eq!(toy_model.unk_id(), 0);
+        assert_eq!(toy_model.try_unk_id(), Some(0));
     }
 
     #[test]
     fn model_has_correct_len() {
         let model = toy_model().unwrap();
         assert_eq!(model.len(), 1000);
+        assert_eq!(model.try_len(), Some(1000));
     }
 
     #[test]
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use crate::model::model_proto::sentence_piece::Type;
+use crate::model::ModelType;
+use crate::proto::ModelProto;
+use crate::SentencePieceError;
+
+/// Policy for the scores of pieces that are in both models.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScorePolicy {
+    /// Use the score of the first model.
+    First,
+
+    /// Use the highest of the two scores.
+    Max,
+
+    /// Use the mean of the two scores.
+    Mean,
+}
+
+/// Model with the merged vocabularies of two models.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergedModel {
+    /// The merged model.
+    pub model: ModelProto,
+
+    /// Identifiers of the pieces of the first model in the merged model.
+    pub first_ids: Vec<u32>,
+
+    /// Identifiers of the pieces of the second model in the merged model.
+    pub second_ids: Vec<u32>,
+}
+
+/// Merge the vocabularies of two models.
+///
+/// The merged model contains the pieces of the first model, followed by
+/// the pieces of the second model that are not in the first model.
+/// Pieces are matched by their strings, the unknown pieces of the two
+/// models are always merged. The type of a piece that is in both models
+/// is the type in the first model, its score is determined by `policy`.
+/// All other settings, such as the normalizer, are taken from the first
+/// model.
+///
+/// Returns `SentencePieceError::InvalidArgument` when the models are of
+/// different types.
+pub fn merge_models(
+    first: &ModelProto,
+    second: &ModelProto,
+    policy: ScorePolicy,
+) -> Result<MergedModel, SentencePieceError> {
+    let (first_type, second_type) = (model_type(first), model_type(second));
+    if first_type != second_type {
+        return Err(SentencePieceError::InvalidArgument(format!(
+            "cannot merge {:?} and {:?} models",
+            first_type, second_type
+        )));
+    }
+
+    let mut model = first.clone();
+    let mut ids = model
+        .pieces
+        .iter()
+        .enumerate()
+        .map(|(id, piece)| (piece.piece().to_owned(), id as u32))
+        .collect::<HashMap<_, _>>();
+    let unk_id = model
+        .pieces
+        .iter()
+        .position(|piece| piece.r#type() == Type::Unknown)
+        .map(|id| id as u32);
+
+    let mut second_ids = Vec::with_capacity(second.pieces.len());
+    for piece in &second.pieces {
+        let existing = match unk_id {
+            Some(unk_id) if piece.r#type() == Type::Unknown => Some(unk_id),
+            _ => ids.get(piece.piece()).copied(),
+        };
+
+        let id = match existing {
+            Some(id) => {
+                let merged = &mut model.pieces[id as usize];
+                let (score, other_score) = (merged.score(), piece.score());
+                merged.score = Some(match policy {
+                    ScorePolicy::First => score,
+                    ScorePolicy::Max => score.max(other_score),
+                    ScorePolicy::Mean => (score + other_score) / 2.,
+                });
+                id
+            }
+            None => {
+                let id = model.pieces.len() as u32;
+                ids.insert(piece.piece().to_owned(), id);
+                model.pieces.push(piece.clone());
+                id
+            }
+        };
+
+        second_ids.push(id);
+    }
+
+    if let Some(trainer_spec) = model.trainer_spec.as_mut() {
+        trainer_spec.vocab_size = Some(model.pieces.len() as i32);
+    }
+
+    Ok(MergedModel {
+        first_ids: (0..first.pieces.len() as u32).collect(),
+        second_ids,
+        model,
+    })
+}
+
+fn model_type(model: &ModelProto) -> ModelType {
+    model
+        .trainer_spec
+        .as_ref()
+        .map(|spec| spec.model_type().into())
+        .unwrap_or(ModelType::Unigram)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::ModelProto;
+    use crate::{
+        merge_models, ModelBuilder, ModelType, PieceType, ScorePolicy, SentencePieceError,
+        SentencePieceProcessor,
+    };
+
+    fn toy_model() -> ModelProto {
+        ModelProto::parse(include_bytes!("../testdata/toy.model")).unwrap()
+    }
+
+    fn other_model() -> ModelBuilder {
+        ModelBuilder::new()
+            .unk_piece("[UNK]")
+            .piece("[UNK]", 0.0, PieceType::Unknown)
+            .piece("<s>", 0.0, PieceType::Control)
+            .piece("</s>", 0.0, PieceType::Control)
+            .piece("▁the", -1.0, PieceType::Normal)
+            .piece("▁xyzzy", -2.0, PieceType::Normal)
+            .piece("▁plugh", -3.0, PieceType::Normal)
+    }
+
+    #[test]
+    fn merges_vocabularies() {
+        let first = toy_model();
+        let second = other_model().to_model_proto();
+        let merged = merge_models(&first, &second, ScorePolicy::Max).unwrap();
+
+        assert_eq!(merged.model.pieces.len(), 1002);
+        assert_eq!(merged.first_ids, (0..1000).collect::<Vec<_>>());
+        assert_eq!(merged.second_ids, [0, 1, 2, 5, 1000, 1001]);
+        assert_eq!(merged.model.pieces[5].score, Some(-1.0));
+
+        let spp = SentencePieceProcessor::from_model_proto(&merged.model).unwrap();
+        assert_eq!(spp.len(), 1002);
+        assert_eq!(spp.piece_to_id("▁xyzzy"), Ok(Some(1000)));
+        assert_eq!(spp.piece_to_id("▁plugh"), Ok(Some(1001)));
+    }
+
+    #[test]
+    fn combines_scores() {
+        let first = toy_model();
+        let second = other_model().to_model_proto();
+        let toy_score = first.pieces[5].score.unwrap();
+
+        let merged = merge_models(&first, &second, ScorePolicy::First).unwrap();
+        assert_eq!(merged.model.pieces[5].score, Some(toy_score));
+
+        let merged = merge_models(&first, &second, ScorePolicy::Mean).unwrap();
+        assert_eq!(merged.model.pieces[5].score, Some((toy_score - 1.0) / 2.));
+    }
+
+    #[test]
+    fn rejects_models_of_different_types() {
+        let second = other_model().model_type(ModelType::Bpe).to_model_proto();
+        assert!(matches!(
+            merge_models(&toy_model(), &second, ScorePolicy::First),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+}
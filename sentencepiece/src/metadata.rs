@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use prost_derive::Message;
+
+use crate::proto::{NormalizerSpec, TrainerSpec};
+use crate::{ModelType, SentencePieceError, SentencePieceProcessor};
+
+impl SentencePieceProcessor {
+    /// Check whether a dummy whitespace is added to the start of the input.
+    pub fn add_dummy_prefix(&self) -> bool {
+        self.loaded_specs().normalizer.add_dummy_prefix()
+    }
+
+    /// Check whether the model falls back to byte pieces for unknown
+    /// characters.
+    pub fn byte_fallback(&self) -> bool {
+        self.loaded_specs().trainer.byte_fallback()
+    }
+
+    /// Check whether whitespace is replaced by the meta symbol `▁`.
+    pub fn escape_whitespaces(&self) -> bool {
+        self.loaded_specs().normalizer.escape_whitespaces()
+    }
+
+    /// Get the type of the model.
+    pub fn model_type(&self) -> ModelType {
+        self.loaded_specs().trainer.model_type().into()
+    }
+
+    /// Get the name of the normalization rule, e.g. `nmt_nfkc`.
+    pub fn normalizer_name(&self) -> String {
+        self.loaded_specs().normalizer.name().to_owned()
+    }
+
+    /// Get the precompiled character map of the normalizer.
+    ///
+    /// The character map contains the compiled normalization rules. It
+    /// is empty for the identity normalizer.
+    pub fn precompiled_charsmap(&self) -> Vec<u8> {
+        self.loaded_specs()
+            .normalizer
+            .precompiled_charsmap
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// Check whether leading, trailing, and duplicate internal
+    /// whitespace is removed from the input.
+    pub fn remove_extra_whitespaces(&self) -> bool {
+        self.loaded_specs().normalizer.remove_extra_whitespaces()
+    }
+
+    /// Check whether whitespace is attached to the end rather than the
+    /// start of pieces.
+    pub fn treat_whitespace_as_suffix(&self) -> bool {
+        self.loaded_specs().trainer.treat_whitespace_as_suffix()
+    }
+
+    /// Get the trainer specification of the model.
+    pub(crate) fn trainer_spec(&self) -> Result<TrainerSpec, SentencePieceError> {
+        Ok(self.specs()?.trainer.clone())
+    }
+
+    /// Get the type of the model.
+    ///
+    /// In contrast to [`SentencePieceProcessor::model_type`], an error is
+    /// returned when the specifications cannot be parsed.
+    pub(crate) fn checked_model_type(&self) -> Result<ModelType, SentencePieceError> {
+        Ok(self.specs()?.trainer.model_type().into())
+    }
+
+    /// Get the model specifications, which must be valid.
+    pub(crate) fn loaded_specs(&self) -> Arc<ModelSpecs> {
+        self.specs()
+            .expect("Received invalid model specifications from sentencepiece")
+    }
+
+    /// Get the model specifications.
+    ///
+    /// The specifications are parsed from the model once and shared by
+    /// clones of the processor. Only the specifications are parsed, the
+    /// pieces of the model are skipped.
+    pub(crate) fn specs(&self) -> Result<Arc<ModelSpecs>, SentencePieceError> {
+        let mut specs = self
+            .owner
+            .specs
+            .lock()
+            .expect("Model specifications lock is poisoned");
+        if let Some(specs) = specs.as_ref() {
+            return Ok(specs.clone());
+        }
+
+        let proto: SpecsProto = prost::Message::decode(self.to_serialized_proto().as_slice())
+            .map_err(|err| SentencePieceError::InvalidProtobuf(err.to_string()))?;
+        let parsed = Arc::new(ModelSpecs {
+            normalizer: proto.normalizer_spec.unwrap_or_default(),
+            trainer: proto.trainer_spec.unwrap_or_default(),
+        });
+        *specs = Some(parsed.clone());
+
+        Ok(parsed)
+    }
+}
+
+/// Specifications of a model.
+#[derive(Debug)]
+pub(crate) struct ModelSpecs {
+    pub(crate) normalizer: NormalizerSpec,
+    pub(crate) trainer: TrainerSpec,
+}
+
+/// Model message that only declares the specifications.
+///
+/// The pieces of the model are skipped while decoding, so decoding is
+/// cheap and does not fail on pieces that are not valid UTF-8.
+#[derive(Clone, PartialEq, Message)]
+struct SpecsProto {
+    #[prost(message, optional, tag = "2")]
+    trainer_spec: Option<TrainerSpec>,
+
+    #[prost(message, optional, tag = "3")]
+    normalizer_spec: Option<NormalizerSpec>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::test_util::{invalid_utf8_model, toy_model};
+    use crate::{ModelBuilder, ModelType, PieceType};
+
+    #[test]
+    fn reads_metadata_of_toy_model() {
+        let model = toy_model();
+        assert_eq!(model.model_type(), ModelType::Unigram);
+        assert_eq!(model.normalizer_name(), "nmt_nfkc");
+        assert!(model.add_dummy_prefix());
+        assert!(model.remove_extra_whitespaces());
+        assert!(model.escape_whitespaces());
+        assert!(!model.treat_whitespace_as_suffix());
+        assert!(!model.byte_fallback());
+        assert!(!model.precompiled_charsmap().is_empty());
+    }
+
+    #[test]
+    fn reads_metadata_of_built_model() {
+        let model = ModelBuilder::new()
+            .model_type(ModelType::Bpe)
+            .add_dummy_prefix(false)
+            .treat_whitespace_as_suffix(true)
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("a", -1.0, PieceType::Normal)
+            .build()
+            .unwrap();
+        assert_eq!(model.model_type(), ModelType::Bpe);
+        assert_eq!(model.normalizer_name(), "identity");
+        assert!(!model.add_dummy_prefix());
+        assert!(model.treat_whitespace_as_suffix());
+        assert!(model.precompiled_charsmap().is_empty());
+    }
+
+    #[test]
+    fn shares_metadata_with_clones() {
+        let model = toy_model();
+        assert!(model.add_dummy_prefix());
+
+        let clone = model.clone();
+        assert!(Arc::ptr_eq(
+            &model.specs().unwrap(),
+            &clone.specs().unwrap()
+        ));
+        assert_eq!(
+            clone.loaded_specs().normalizer,
+            model.loaded_specs().normalizer
+        );
+        assert_eq!(clone.trainer_spec().unwrap(), model.trainer_spec().unwrap());
+    }
+
+    #[test]
+    fn reads_metadata_of_model_with_invalid_utf8_piece() {
+        let model = invalid_utf8_model();
+        assert_eq!(model.model_type(), ModelType::Unigram);
+        assert_eq!(model.normalizer_name(), "identity");
+        assert!(model.add_dummy_prefix());
+        assert!(model.remove_extra_whitespaces());
+        assert!(!model.treat_whitespace_as_suffix());
+        assert!(!model.byte_fallback());
+        assert!(model.precompiled_charsmap().is_empty());
+    }
+}
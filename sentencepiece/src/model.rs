@@ -0,0 +1,581 @@
+use prost_derive::Message;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{SentencePieceError, SpecialPiece};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ModelProto {
+    /// Sentence pieces with scores.
+    #[prost(message, repeated, tag = "1")]
+    pub pieces: Vec<model_proto::SentencePiece>,
+
+    /// Spec used to generate this model file.
+    #[prost(message, optional, tag = "2")]
+    pub trainer_spec: Option<TrainerSpec>,
+
+    /// Spec for text normalization.
+    #[prost(message, optional, tag = "3")]
+    pub normalizer_spec: Option<NormalizerSpec>,
+
+    /// Stores sample input and its expected segmentation to verify the model.
+    #[prost(message, optional, tag = "4")]
+    pub self_test_data: Option<SelfTestData>,
+
+    /// Spec for text de-normalization.
+    #[prost(message, optional, tag = "5")]
+    pub denormalizer_spec: Option<NormalizerSpec>,
+}
+
+impl ModelProto {
+    /// Parse a serialized model.
+    ///
+    /// In contrast to [`crate::SentencePieceProcessor::from_serialized_proto`],
+    /// the model is only parsed and not loaded by sentencepiece, so the
+    /// model is not validated.
+    ///
+    /// Pieces are parsed as strings, so models with a piece that is not
+    /// valid UTF-8 are rejected with `SentencePieceError::InvalidProtobuf`,
+    /// even though sentencepiece can load them.
+    pub fn parse(data: &[u8]) -> Result<Self, SentencePieceError> {
+        prost::Message::decode(data)
+            .map_err(|err| SentencePieceError::InvalidProtobuf(err.to_string()))
+    }
+
+    /// Append a piece to the model.
+    ///
+    /// Returns the identifier of the new piece. This can be used to
+    /// extend a pretrained model, e.g. with user-defined symbols. Returns
+    /// `SentencePieceError::InvalidArgument` when the piece is empty or
+    /// already in the model.
+    pub fn add_piece(
+        &mut self,
+        piece: impl Into<String>,
+        score: f32,
+        piece_type: PieceType,
+    ) -> Result<u32, SentencePieceError> {
+        let piece = piece.into();
+        if piece.is_empty() {
+            return Err(SentencePieceError::InvalidArgument(
+                "piece must not be empty".to_string(),
+            ));
+        }
+        if self
+            .pieces
+            .iter()
+            .any(|existing| existing.piece.as_deref() == Some(piece.as_str()))
+        {
+            return Err(SentencePieceError::InvalidArgument(format!(
+                "piece is already in the model: {}",
+                piece
+            )));
+        }
+
+        let id = self.pieces.len() as u32;
+        self.pieces.push(model_proto::SentencePiece {
+            piece: Some(piece),
+            score: Some(score),
+            r#type: Some(model_proto::sentence_piece::Type::from(piece_type) as i32),
+        });
+
+        if let Some(trainer_spec) = self.trainer_spec.as_mut() {
+            trainer_spec.vocab_size = Some(self.pieces.len() as i32);
+        }
+
+        Ok(id)
+    }
+
+    /// Set the piece of a special piece.
+    ///
+    /// The unknown piece is renamed to `piece`. Other special pieces use
+    /// `piece` when it is a control piece in the model, otherwise `piece`
+    /// is appended as a control piece. Returns the identifier of the
+    /// special piece, or `SentencePieceError::InvalidArgument` when
+    /// `piece` is empty or already used by a piece of another type.
+    pub fn set_special_piece(
+        &mut self,
+        special: SpecialPiece,
+        piece: impl Into<String>,
+    ) -> Result<u32, SentencePieceError> {
+        let piece = piece.into();
+        if piece.is_empty() {
+            return Err(SentencePieceError::InvalidArgument(
+                "piece must not be empty".to_string(),
+            ));
+        }
+
+        let existing = self
+            .pieces
+            .iter()
+            .position(|existing| existing.piece.as_deref() == Some(piece.as_str()));
+
+        let id = if special == SpecialPiece::Unknown {
+            let unk_id = self
+                .pieces
+                .iter()
+                .position(|existing| {
+                    existing.r#type() == model_proto::sentence_piece::Type::Unknown
+                })
+                .ok_or_else(|| SentencePieceError::MissingData("unknown piece".to_string()))?;
+            if existing.map(|id| id != unk_id).unwrap_or(false) {
+                return Err(SentencePieceError::InvalidArgument(format!(
+                    "piece is already in the model: {}",
+                    piece
+                )));
+            }
+            self.pieces[unk_id].piece = Some(piece.clone());
+            unk_id as u32
+        } else {
+            match existing {
+                Some(id)
+                    if self.pieces[id].r#type() == model_proto::sentence_piece::Type::Control =>
+                {
+                    id as u32
+                }
+                Some(_) => {
+                    return Err(SentencePieceError::InvalidArgument(format!(
+                        "piece is not a control piece: {}",
+                        piece
+                    )))
+                }
+                None => self.add_piece(piece.clone(), 0.0, PieceType::Control)?,
+            }
+        };
+
+        let trainer_spec = self.trainer_spec.get_or_insert_with(Default::default);
+        let (spec_id, spec_piece) = match special {
+            SpecialPiece::Unknown => (&mut trainer_spec.unk_id, &mut trainer_spec.unk_piece),
+            SpecialPiece::Bos => (&mut trainer_spec.bos_id, &mut trainer_spec.bos_piece),
+            SpecialPiece::Eos => (&mut trainer_spec.eos_id, &mut trainer_spec.eos_piece),
+            SpecialPiece::Pad => (&mut trainer_spec.pad_id, &mut trainer_spec.pad_piece),
+        };
+        *spec_id = Some(id as i32);
+        *spec_piece = Some(piece);
+
+        Ok(id)
+    }
+
+    /// Prune the vocabulary to `vocab_size` pieces.
+    ///
+    /// All pieces that are not normal pieces, such as special pieces,
+    /// user-defined symbols, and byte pieces, are preserved. The
+    /// remaining space is used for the normal pieces with the highest
+    /// scores. The preserved pieces keep their relative order, so they
+    /// are renumbered consecutively.
+    ///
+    /// Returns the mapping from old to new identifiers, `None` for
+    /// pieces that were removed. Returns
+    /// `SentencePieceError::InvalidArgument` when `vocab_size` is smaller
+    /// than the number of pieces that must be preserved.
+    pub fn prune(&mut self, vocab_size: usize) -> Result<Vec<Option<u32>>, SentencePieceError> {
+        let is_normal = |piece: &model_proto::SentencePiece| {
+            piece.r#type() == model_proto::sentence_piece::Type::Normal
+        };
+
+        let n_preserved = self.pieces.iter().filter(|piece| !is_normal(piece)).count();
+        if n_preserved > vocab_size {
+            return Err(SentencePieceError::InvalidArgument(format!(
+                "vocabulary size {} is smaller than the number of preserved pieces ({})",
+                vocab_size, n_preserved
+            )));
+        }
+
+        let mut normal = self
+            .pieces
+            .iter()
+            .enumerate()
+            .filter(|(_, piece)| is_normal(piece))
+            .map(|(id, piece)| (id, piece.score.unwrap_or_default()))
+            .collect::<Vec<_>>();
+        normal.sort_by(|(id1, score1), (id2, score2)| score2.total_cmp(score1).then(id1.cmp(id2)));
+
+        let mut keep = self
+            .pieces
+            .iter()
+            .map(|piece| !is_normal(piece))
+            .collect::<Vec<_>>();
+        for &(id, _) in normal.iter().take(vocab_size - n_preserved) {
+            keep[id] = true;
+        }
+
+        let mut id_map = Vec::with_capacity(self.pieces.len());
+        let mut next_id = 0;
+        for &keep in &keep {
+            if keep {
+                id_map.push(Some(next_id));
+                next_id += 1;
+            } else {
+                id_map.push(None);
+            }
+        }
+
+        let mut keep = keep.into_iter();
+        self.pieces.retain(|_| keep.next().unwrap_or(false));
+
+        if let Some(trainer_spec) = self.trainer_spec.as_mut() {
+            trainer_spec.vocab_size = Some(self.pieces.len() as i32);
+            for special_id in [
+                &mut trainer_spec.unk_id,
+                &mut trainer_spec.bos_id,
+                &mut trainer_spec.eos_id,
+                &mut trainer_spec.pad_id,
+            ] {
+                if let Some(id) = special_id.filter(|&id| id >= 0) {
+                    *special_id = Some(
+                        id_map
+                            .get(id as usize)
+                            .copied()
+                            .flatten()
+                            .map(|id| id as i32)
+                            .unwrap_or(-1),
+                    );
+                }
+            }
+        }
+
+        Ok(id_map)
+    }
+
+    /// Serialize the model.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        prost::Message::encode_to_vec(self)
+    }
+}
+
+pub mod model_proto {
+    use prost_derive::Message;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct SentencePiece {
+        /// Piece must not be empty.
+        ///
+        /// sentencepiece does not require pieces to be valid UTF-8, but
+        /// a [`super::ModelProto`] can only represent UTF-8 pieces.
+        #[prost(string, optional, tag = "1")]
+        pub piece: Option<String>,
+
+        /// Score of the piece.
+        #[prost(float, optional, tag = "2")]
+        pub score: Option<f32>,
+
+        /// Type of the piece.
+        #[prost(
+            enumeration = "sentence_piece::Type",
+            optional,
+            tag = "3",
+            default = "Normal"
+        )]
+        pub r#type: Option<i32>,
+    }
+
+    pub mod sentence_piece {
+        use prost_derive::Enumeration;
+
+        #[derive(Clone, Copy, Debug, Eq, Enumeration, Hash, Ord, PartialEq, PartialOrd)]
+        #[repr(i32)]
+        pub enum Type {
+            Normal = 1,
+            Unknown = 2,
+            Control = 3,
+            UserDefined = 4,
+            Byte = 6,
+            Unused = 5,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TrainerSpec {
+    #[prost(string, repeated, tag = "1")]
+    pub input: Vec<String>,
+
+    #[prost(string, optional, tag = "7")]
+    pub input_format: Option<String>,
+
+    #[prost(string, optional, tag = "2")]
+    pub model_prefix: Option<String>,
+
+    #[prost(
+        enumeration = "trainer_spec::ModelType",
+        optional,
+        tag = "3",
+        default = "Unigram"
+    )]
+    pub model_type: Option<i32>,
+
+    #[prost(int32, optional, tag = "4", default = "8000")]
+    pub vocab_size: Option<i32>,
+
+    #[prost(string, repeated, tag = "5")]
+    pub accept_language: Vec<String>,
+
+    #[prost(int32, optional, tag = "6", default = "0")]
+    pub self_test_sample_size: Option<i32>,
+
+    #[prost(bool, optional, tag = "50", default = "false")]
+    pub enable_differential_privacy: Option<bool>,
+
+    #[prost(float, optional, tag = "51", default = "0")]
+    pub differential_privacy_noise_level: Option<f32>,
+
+    #[prost(uint64, optional, tag = "52", default = "0")]
+    pub differential_privacy_clipping_threshold: Option<u64>,
+
+    #[prost(float, optional, tag = "10", default = "0.9995")]
+    pub character_coverage: Option<f32>,
+
+    #[prost(uint64, optional, tag = "11", default = "0")]
+    pub input_sentence_size: Option<u64>,
+
+    #[prost(bool, optional, tag = "19", default = "true")]
+    pub shuffle_input_sentence: Option<bool>,
+
+    #[prost(int32, optional, tag = "12")]
+    pub mining_sentence_size: Option<i32>,
+
+    #[prost(int32, optional, tag = "13")]
+    pub training_sentence_size: Option<i32>,
+
+    #[prost(int32, optional, tag = "14", default = "1000000")]
+    pub seed_sentencepiece_size: Option<i32>,
+
+    #[prost(float, optional, tag = "15", default = "0.75")]
+    pub shrinking_factor: Option<f32>,
+
+    #[prost(int32, optional, tag = "18", default = "4192")]
+    pub max_sentence_length: Option<i32>,
+
+    #[prost(int32, optional, tag = "16", default = "16")]
+    pub num_threads: Option<i32>,
+
+    #[prost(int32, optional, tag = "17", default = "2")]
+    pub num_sub_iterations: Option<i32>,
+
+    #[prost(int32, optional, tag = "20", default = "16")]
+    pub max_sentencepiece_length: Option<i32>,
+
+    #[prost(bool, optional, tag = "21", default = "true")]
+    pub split_by_unicode_script: Option<bool>,
+
+    #[prost(bool, optional, tag = "23", default = "true")]
+    pub split_by_number: Option<bool>,
+
+    #[prost(bool, optional, tag = "22", default = "true")]
+    pub split_by_whitespace: Option<bool>,
+
+    #[prost(bool, optional, tag = "24", default = "false")]
+    pub treat_whitespace_as_suffix: Option<bool>,
+
+    #[prost(bool, optional, tag = "26", default = "false")]
+    pub allow_whitespace_only_pieces: Option<bool>,
+
+    #[prost(bool, optional, tag = "25", default = "false")]
+    pub split_digits: Option<bool>,
+
+    #[prost(string, optional, tag = "53", default = "")]
+    pub pretokenization_delimiter: Option<String>,
+
+    #[prost(string, repeated, tag = "30")]
+    pub control_symbols: Vec<String>,
+
+    #[prost(string, repeated, tag = "31")]
+    pub user_defined_symbols: Vec<String>,
+
+    #[prost(string, optional, tag = "36")]
+    pub required_chars: Option<String>,
+
+    #[prost(bool, optional, tag = "35", default = "false")]
+    pub byte_fallback: Option<bool>,
+
+    #[prost(bool, optional, tag = "32", default = "true")]
+    pub vocabulary_output_piece_score: Option<bool>,
+
+    #[prost(bool, optional, tag = "33", default = "true")]
+    pub hard_vocab_limit: Option<bool>,
+
+    #[prost(bool, optional, tag = "34", default = "false")]
+    pub use_all_vocab: Option<bool>,
+
+    #[prost(int32, optional, tag = "40", default = "0")]
+    pub unk_id: Option<i32>,
+
+    #[prost(int32, optional, tag = "41", default = "1")]
+    pub bos_id: Option<i32>,
+
+    #[prost(int32, optional, tag = "42", default = "2")]
+    pub eos_id: Option<i32>,
+
+    #[prost(int32, optional, tag = "43", default = "-1")]
+    pub pad_id: Option<i32>,
+
+    #[prost(string, optional, tag = "45", default = "<unk>")]
+    pub unk_piece: Option<String>,
+
+    #[prost(string, optional, tag = "46", default = "<s>")]
+    pub bos_piece: Option<String>,
+
+    #[prost(string, optional, tag = "47", default = "</s>")]
+    pub eos_piece: Option<String>,
+
+    #[prost(string, optional, tag = "48", default = "<pad>")]
+    pub pad_piece: Option<String>,
+
+    #[prost(string, optional, tag = "44", default = " \u{2047} ")]
+    pub unk_surface: Option<String>,
+
+    #[prost(bool, optional, tag = "49", default = "false")]
+    pub train_extremely_large_corpus: Option<bool>,
+
+    #[prost(string, optional, tag = "54", default = "")]
+    pub seed_sentencepieces_file: Option<String>,
+}
+
+pub mod trainer_spec {
+    use prost_derive::Enumeration;
+
+    #[derive(Clone, Copy, Debug, Eq, Enumeration, Hash, Ord, PartialEq, PartialOrd)]
+    #[repr(i32)]
+    pub enum ModelType {
+        Unigram = 1,
+        Bpe = 2,
+        Word = 3,
+        Char = 4,
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct NormalizerSpec {
+    /// Name of the normalization rule.
+    #[prost(string, optional, tag = "1")]
+    pub name: Option<String>,
+
+    /// Pre-compiled normalization rule.
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub precompiled_charsmap: Option<Vec<u8>>,
+
+    /// Adds dummy whitespace at the beginning of text.
+    #[prost(bool, optional, tag = "3", default = "true")]
+    pub add_dummy_prefix: Option<bool>,
+
+    /// Removes leading, trailing, and duplicate internal whitespace.
+    #[prost(bool, optional, tag = "4", default = "true")]
+    pub remove_extra_whitespaces: Option<bool>,
+
+    /// Replaces whitespace with meta symbol.
+    #[prost(bool, optional, tag = "5", default = "true")]
+    pub escape_whitespaces: Option<bool>,
+
+    /// Custom normalization rule file in TSV format.
+    #[prost(string, optional, tag = "6")]
+    pub normalization_rule_tsv: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SelfTestData {
+    #[prost(message, repeated, tag = "1")]
+    pub samples: Vec<self_test_data::Sample>,
+}
+
+pub mod self_test_data {
+    use prost_derive::Message;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Sample {
+        #[prost(string, optional, tag = "1")]
+        pub input: Option<String>,
+
+        #[prost(string, optional, tag = "2")]
+        pub expected: Option<String>,
+    }
+}
+
+/// Type of a sentencepiece model.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum ModelType {
+    /// Unigram language model.
+    Unigram,
+
+    /// Byte-pair encoding.
+    Bpe,
+
+    /// Whitespace-delimited words.
+    Word,
+
+    /// Characters.
+    Char,
+}
+
+impl From<trainer_spec::ModelType> for ModelType {
+    fn from(model_type: trainer_spec::ModelType) -> Self {
+        use trainer_spec::ModelType::*;
+        match model_type {
+            Unigram => ModelType::Unigram,
+            Bpe => ModelType::Bpe,
+            Word => ModelType::Word,
+            Char => ModelType::Char,
+        }
+    }
+}
+
+impl From<ModelType> for trainer_spec::ModelType {
+    fn from(model_type: ModelType) -> Self {
+        match model_type {
+            ModelType::Unigram => trainer_spec::ModelType::Unigram,
+            ModelType::Bpe => trainer_spec::ModelType::Bpe,
+            ModelType::Word => trainer_spec::ModelType::Word,
+            ModelType::Char => trainer_spec::ModelType::Char,
+        }
+    }
+}
+
+/// Type of a sentence piece.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum PieceType {
+    /// Normal piece.
+    Normal,
+
+    /// Unknown piece, a model has exactly one unknown piece.
+    Unknown,
+
+    /// Control piece, such as `<s>` and `</s>`.
+    Control,
+
+    /// User-defined piece, which is never split.
+    UserDefined,
+
+    /// Byte piece, used for byte fallback.
+    Byte,
+
+    /// Unused piece.
+    Unused,
+}
+
+impl From<model_proto::sentence_piece::Type> for PieceType {
+    fn from(piece_type: model_proto::sentence_piece::Type) -> Self {
+        use model_proto::sentence_piece::Type::*;
+        match piece_type {
+            Normal => PieceType::Normal,
+            Unknown => PieceType::Unknown,
+            Control => PieceType::Control,
+            UserDefined => PieceType::UserDefined,
+            Byte => PieceType::Byte,
+            Unused => PieceType::Unused,
+        }
+    }
+}
+
+impl From<PieceType> for model_proto::sentence_piece::Type {
+    fn from(piece_type: PieceType) -> Self {
+        use model_proto::sentence_piece::Type;
+        match piece_type {
+            PieceType::Normal => Type::Normal,
+            PieceType::Unknown => Type::Unknown,
+            PieceType::Control => Type::Control,
+            PieceType::UserDefined => Type::UserDefined,
+            PieceType::Byte => Type::Byte,
+            PieceType::Unused => Type::Unused,
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::os::raw::c_char;
+
+use sentencepiece_sys::{spp_normalize, spp_normalize_with_offsets};
+
+use crate::{c_error, CData, SentencePieceError, SentencePieceProcessor};
+
+/// Normalized sentence with its alignment to the original sentence.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NormalizedText {
+    /// The normalized sentence.
+    pub normalized: String,
+
+    /// Byte offsets in the original sentence.
+    ///
+    /// `offsets[i]` is the offset in the original sentence of byte `i`
+    /// of the normalized sentence. The last element is the offset of the
+    /// end of the normalized sentence, so there are
+    /// `normalized.len() + 1` offsets.
+    pub offsets: Vec<u32>,
+}
+
+impl NormalizedText {
+    /// Map a span in the normalized sentence to the original sentence.
+    ///
+    /// The span is encoded as the byte offsets *[begin, end)*. Returns
+    /// `None` when the span is out of bounds.
+    pub fn original_span(&self, span: (u32, u32)) -> Option<(u32, u32)> {
+        let begin = *self.offsets.get(span.0 as usize)?;
+        let end = *self.offsets.get(span.1 as usize)?;
+        Some((begin, end))
+    }
+}
+
+impl SentencePieceProcessor {
+    /// Normalize a sentence with the normalization rules of the model.
+    ///
+    /// This applies the same normalization as encoding, such as
+    /// `nmt_nfkc` and the replacement of whitespace by `▁`, without
+    /// splitting the sentence into pieces.
+    pub fn normalize(&self, sentence: &str) -> Result<String, SentencePieceError> {
+        let mut normalized = std::ptr::null_mut::<u8>();
+        let mut normalized_len = 0;
+
+        let status = unsafe {
+            spp_normalize(
+                self.inner,
+                sentence.as_ptr() as *const c_char,
+                sentence.len(),
+                &mut normalized,
+                &mut normalized_len,
+            )
+        };
+
+        let c_str = CData {
+            data: normalized,
+            len: normalized_len,
+        };
+
+        if status != 0 {
+            return Err(c_error(status));
+        }
+
+        String::from_utf8(c_str.to_owned())
+            .map_err(|err| SentencePieceError::InvalidUtf8(err.into_bytes()))
+    }
+
+    /// Normalize a sentence and align it to the original sentence.
+    ///
+    /// See [`SentencePieceProcessor::normalize`].
+    pub fn normalize_with_offsets(
+        &self,
+        sentence: &str,
+    ) -> Result<NormalizedText, SentencePieceError> {
+        let mut normalized = std::ptr::null_mut::<u8>();
+        let mut normalized_len = 0;
+        let mut offsets = std::ptr::null_mut::<usize>();
+        let mut offsets_len = 0;
+
+        let status = unsafe {
+            spp_normalize_with_offsets(
+                self.inner,
+                sentence.as_ptr() as *const c_char,
+                sentence.len(),
+                &mut normalized,
+                &mut normalized_len,
+                &mut offsets,
+                &mut offsets_len,
+            )
+        };
+
+        let c_str = CData {
+            data: normalized,
+            len: normalized_len,
+        };
+        let c_offsets = CData {
+            data: offsets,
+            len: offsets_len,
+        };
+
+        if status != 0 {
+            return Err(c_error(status));
+        }
+
+        if offsets_len != normalized_len + 1 {
+            return Err(SentencePieceError::MissingData(
+                "an offset for every byte of the normalized sentence".to_string(),
+            ));
+        }
+
+        Ok(NormalizedText {
+            normalized: String::from_utf8(c_str.to_owned())
+                .map_err(|err| SentencePieceError::InvalidUtf8(err.into_bytes()))?,
+            offsets: c_offsets.iter().map(|&offset| offset as u32).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::toy_model;
+    use crate::{ModelBuilder, PieceType};
+
+    #[test]
+    fn normalizes_with_model_rules() {
+        let model = toy_model();
+        assert_eq!(model.normalize("I saw a girl.").unwrap(), "▁I▁saw▁a▁girl.");
+        assert_eq!(
+            model.normalize("  ｆｕｌｌ  width ").unwrap(),
+            "▁full▁width"
+        );
+        assert_eq!(model.normalize("").unwrap(), "");
+    }
+
+    #[test]
+    fn aligns_normalized_sentence() {
+        let model = toy_model();
+        let sentence = " Ｉ saw";
+        let normalized = model.normalize_with_offsets(sentence).unwrap();
+        assert_eq!(normalized.normalized, "▁I▁saw");
+        assert_eq!(normalized.offsets.len(), normalized.normalized.len() + 1);
+
+        // "I" is the full-width "Ｉ" in the original sentence.
+        assert_eq!(normalized.original_span((3, 4)), Some((1, 4)));
+        // "saw"
+        assert_eq!(normalized.original_span((7, 10)), Some((5, 8)));
+        assert_eq!(normalized.original_span((7, 11)), None);
+    }
+
+    #[test]
+    fn normalizes_with_identity_rules() {
+        let model = ModelBuilder::new()
+            .add_dummy_prefix(false)
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("a", -1.0, PieceType::Normal)
+            .build()
+            .unwrap();
+        assert_eq!(model.normalize("ｆｕｌｌ").unwrap(), "ｆｕｌｌ");
+    }
+}
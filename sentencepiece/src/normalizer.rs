@@ -0,0 +1,231 @@
+use std::path::Path;
+
+use sentencepiece_sys::spt_compile_normalization_rules;
+
+use crate::model::{model_proto, trainer_spec, ModelProto, NormalizerSpec, TrainerSpec};
+use crate::{
+    c_error, path_to_c_filename, CData, NormalizedText, SentencePieceError, SentencePieceProcessor,
+};
+
+impl NormalizerSpec {
+    /// Compile normalization rules from a TSV file.
+    ///
+    /// Every line of the file contains a rule with the source and target
+    /// code points separated by a tab, e.g. `2019\t27` replaces
+    /// U+2019 by an apostrophe. The code points of a sequence are
+    /// hexadecimal numbers separated by spaces. The rules are compiled
+    /// into the precompiled character map of the specification, which
+    /// can be used with [`SentencePieceNormalizer::from_normalizer_spec`]
+    /// or stored in a [`ModelProto`].
+    pub fn from_rule_tsv(path: impl AsRef<Path>) -> Result<Self, SentencePieceError> {
+        let c_filename = path_to_c_filename(path.as_ref())?;
+
+        let mut model = std::ptr::null_mut::<u8>();
+        let mut model_len = 0;
+        let status = unsafe {
+            spt_compile_normalization_rules(c_filename.as_ptr(), &mut model, &mut model_len)
+        };
+
+        let c_model = CData {
+            data: model,
+            len: model_len,
+        };
+
+        if status != 0 {
+            return Err(c_error(status));
+        }
+
+        ModelProto::parse(&c_model)?
+            .normalizer_spec
+            .ok_or_else(|| SentencePieceError::MissingData("normalizer_spec".to_string()))
+    }
+}
+
+/// Normalizer that applies the normalization rules of a model.
+///
+/// The normalizer only contains the normalization settings of a model,
+/// so it can be used for consistent normalization without keeping the
+/// full model in memory.
+#[derive(Debug)]
+pub struct SentencePieceNormalizer {
+    spp: SentencePieceProcessor,
+}
+
+impl SentencePieceNormalizer {
+    /// Construct a normalizer with the normalization rules of a model.
+    pub fn from_model(model: &SentencePieceProcessor) -> Result<Self, SentencePieceError> {
+        let specs = model.specs()?;
+        Self::from_parts(
+            specs.normalizer.clone(),
+            specs.trainer.treat_whitespace_as_suffix(),
+        )
+    }
+
+    /// Construct a normalizer with the normalization rules of a model
+    /// protobuf message.
+    pub fn from_model_proto(model: &ModelProto) -> Result<Self, SentencePieceError> {
+        let treat_whitespace_as_suffix = model
+            .trainer_spec
+            .as_ref()
+            .map(TrainerSpec::treat_whitespace_as_suffix)
+            .unwrap_or(false);
+        Self::from_parts(
+            model.normalizer_spec.clone().unwrap_or_default(),
+            treat_whitespace_as_suffix,
+        )
+    }
+
+    /// Construct a normalizer from a normalizer specification.
+    ///
+    /// The normalization rules are read from the precompiled character
+    /// map of the specification, the name of the rules is not used to
+    /// look up rules.
+    pub fn from_normalizer_spec(spec: NormalizerSpec) -> Result<Self, SentencePieceError> {
+        Self::from_parts(spec, false)
+    }
+
+    /// Construct a normalizer from normalization rules in a TSV file.
+    ///
+    /// See [`NormalizerSpec::from_rule_tsv`].
+    pub fn from_rule_tsv(path: impl AsRef<Path>) -> Result<Self, SentencePieceError> {
+        Self::from_normalizer_spec(NormalizerSpec::from_rule_tsv(path)?)
+    }
+
+    /// Normalize a sentence.
+    ///
+    /// See [`SentencePieceProcessor::normalize`].
+    pub fn normalize(&self, sentence: &str) -> Result<String, SentencePieceError> {
+        self.spp.normalize(sentence)
+    }
+
+    /// Normalize a sentence and align it to the original sentence.
+    ///
+    /// See [`SentencePieceProcessor::normalize_with_offsets`].
+    pub fn normalize_with_offsets(
+        &self,
+        sentence: &str,
+    ) -> Result<NormalizedText, SentencePieceError> {
+        self.spp.normalize_with_offsets(sentence)
+    }
+
+    /// Get the normalizer specification.
+    pub fn normalizer_spec(&self) -> NormalizerSpec {
+        self.spp.loaded_specs().normalizer.clone()
+    }
+
+    /// Construct the normalizer as a model with only the unknown piece.
+    fn from_parts(
+        normalizer_spec: NormalizerSpec,
+        treat_whitespace_as_suffix: bool,
+    ) -> Result<Self, SentencePieceError> {
+        let trainer_spec = TrainerSpec {
+            model_type: Some(trainer_spec::ModelType::Unigram as i32),
+            vocab_size: Some(1),
+            unk_id: Some(0),
+            bos_id: Some(-1),
+            eos_id: Some(-1),
+            pad_id: Some(-1),
+            treat_whitespace_as_suffix: Some(treat_whitespace_as_suffix),
+            ..Default::default()
+        };
+
+        let model = ModelProto {
+            pieces: vec![model_proto::SentencePiece {
+                piece: Some("<unk>".to_string()),
+                score: Some(0.0),
+                r#type: Some(model_proto::sentence_piece::Type::Unknown as i32),
+            }],
+            trainer_spec: Some(trainer_spec),
+            normalizer_spec: Some(normalizer_spec),
+            self_test_data: None,
+            denormalizer_spec: None,
+        };
+
+        Ok(SentencePieceNormalizer {
+            spp: SentencePieceProcessor::from_model_proto(&model)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::proto::{ModelProto, NormalizerSpec};
+    use crate::test_util::{invalid_utf8_model, toy_model};
+    use crate::{ModelBuilder, PieceType, SentencePieceNormalizer, SentencePieceProcessor};
+
+    #[test]
+    fn normalizes_like_model() {
+        let model = toy_model();
+        let normalizer = SentencePieceNormalizer::from_model(&model).unwrap();
+        for sentence in &["I saw a girl.", "  ｆｕｌｌ  width ", ""] {
+            assert_eq!(
+                normalizer.normalize(sentence).unwrap(),
+                model.normalize(sentence).unwrap()
+            );
+            assert_eq!(
+                normalizer.normalize_with_offsets(sentence).unwrap(),
+                model.normalize_with_offsets(sentence).unwrap()
+            );
+        }
+        assert_eq!(normalizer.normalizer_spec().name(), "nmt_nfkc");
+    }
+
+    #[test]
+    fn keeps_whitespace_suffix_setting() {
+        let model = ModelBuilder::new()
+            .treat_whitespace_as_suffix(true)
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("a", -1.0, PieceType::Normal)
+            .build()
+            .unwrap();
+        let normalizer = SentencePieceNormalizer::from_model(&model).unwrap();
+        assert_eq!(normalizer.normalize("a a").unwrap(), "a▁a▁");
+    }
+
+    #[test]
+    fn normalizes_like_model_with_invalid_utf8_piece() {
+        let model = invalid_utf8_model();
+        let normalizer = SentencePieceNormalizer::from_model(&model).unwrap();
+        assert_eq!(
+            normalizer.normalize(" a  b").unwrap(),
+            model.normalize(" a  b").unwrap()
+        );
+    }
+
+    #[test]
+    fn normalizes_with_spec() {
+        let spec = NormalizerSpec {
+            add_dummy_prefix: Some(false),
+            ..toy_model().model_proto().unwrap().normalizer_spec.unwrap()
+        };
+        let normalizer = SentencePieceNormalizer::from_normalizer_spec(spec).unwrap();
+        assert_eq!(normalizer.normalize("Ｉ saw").unwrap(), "I▁saw");
+    }
+
+    #[test]
+    fn compiles_rule_tsv() {
+        let rules = env::temp_dir().join(format!("sentencepiece-rules-{}.tsv", process::id()));
+        fs::write(&rules, "41\t61\n2019\t27\n").unwrap();
+
+        let spec = NormalizerSpec::from_rule_tsv(&rules).unwrap();
+        assert!(!spec.precompiled_charsmap().is_empty());
+
+        let normalizer = SentencePieceNormalizer::from_normalizer_spec(spec).unwrap();
+        assert_eq!(normalizer.normalize("A’B").unwrap(), "▁a'B");
+
+        let model = ModelBuilder::new()
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("▁a", -1.0, PieceType::Normal)
+            .to_model_proto();
+        let model = SentencePieceProcessor::from_model_proto(&ModelProto {
+            normalizer_spec: Some(normalizer.normalizer_spec()),
+            ..model
+        })
+        .unwrap();
+        assert_eq!(model.encode_as_ids("A").unwrap(), [1]);
+
+        fs::remove_file(&rules).unwrap();
+    }
+}
@@ -0,0 +1,369 @@
+use prost::Message as _;
+use prost_derive::Message;
+
+use crate::SentencePieceProcessor;
+
+const CONTRIB_DOMAIN: &str = "ai.onnx.contrib";
+
+// ONNX tensor element types.
+const FLOAT: i32 = 1;
+const INT32: i32 = 6;
+const INT64: i32 = 7;
+const STRING: i32 = 8;
+const BOOL: i32 = 9;
+
+// ONNX attribute type of string attributes.
+const ATTRIBUTE_STRING: i32 = 3;
+
+/// Options of the exported ONNX tokenizer.
+///
+/// The options are stored as constant inputs of the
+/// `SentencepieceTokenizer` operator of onnxruntime-extensions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OnnxExportOptions {
+    /// Number of best segmentations to sample from, `0` disables sampling.
+    pub nbest_size: i64,
+
+    /// Smoothing parameter for sampling.
+    pub alpha: f32,
+
+    /// Add the beginning of sentence piece.
+    pub add_bos: bool,
+
+    /// Add the end of sentence piece.
+    pub add_eos: bool,
+
+    /// Reverse the order of the pieces.
+    pub reverse: bool,
+
+    /// Version of the default ONNX operator set.
+    pub opset_version: i64,
+}
+
+impl Default for OnnxExportOptions {
+    fn default() -> Self {
+        OnnxExportOptions {
+            nbest_size: 0,
+            alpha: 0.,
+            add_bos: false,
+            add_eos: false,
+            reverse: false,
+            opset_version: 17,
+        }
+    }
+}
+
+impl SentencePieceProcessor {
+    /// Export the model as an ONNX tokenizer graph.
+    ///
+    /// The graph consists of a single `SentencepieceTokenizer` node from
+    /// the `ai.onnx.contrib` domain of onnxruntime-extensions, with the
+    /// serialized model embedded as its `model` attribute. The graph has
+    /// one input:
+    ///
+    /// * `inputs`: string tensor of shape *[N]*.
+    ///
+    /// and two outputs:
+    ///
+    /// * `tokens`: int32 tensor with the piece identifiers of all inputs.
+    /// * `indices`: int64 tensor of shape *[N + 1]* with the offsets of
+    ///   the pieces of each input in `tokens`.
+    ///
+    /// Returns the serialized ONNX `ModelProto`.
+    pub fn to_onnx(&self, options: &OnnxExportOptions) -> Vec<u8> {
+        let initializer = vec![
+            TensorProto::int64("nbest_size", options.nbest_size),
+            TensorProto::float("alpha", options.alpha),
+            TensorProto::bool("add_bos", options.add_bos),
+            TensorProto::bool("add_eos", options.add_eos),
+            TensorProto::bool("reverse", options.reverse),
+        ];
+
+        let node = NodeProto {
+            input: [
+                "inputs",
+                "nbest_size",
+                "alpha",
+                "add_bos",
+                "add_eos",
+                "reverse",
+            ]
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+            output: vec!["tokens".to_string(), "indices".to_string()],
+            name: "SentencepieceTokenizer".to_string(),
+            op_type: "SentencepieceTokenizer".to_string(),
+            attribute: vec![AttributeProto {
+                name: "model".to_string(),
+                r#type: ATTRIBUTE_STRING,
+                s: self.to_serialized_proto(),
+            }],
+            domain: CONTRIB_DOMAIN.to_string(),
+        };
+
+        let graph = GraphProto {
+            node: vec![node],
+            name: "sentencepiece_tokenizer".to_string(),
+            initializer,
+            input: vec![ValueInfoProto::tensor("inputs", STRING, Some("N"))],
+            output: vec![
+                ValueInfoProto::tensor("tokens", INT32, None),
+                ValueInfoProto::tensor("indices", INT64, None),
+            ],
+        };
+
+        ModelProto {
+            ir_version: 8,
+            producer_name: env!("CARGO_PKG_NAME").to_string(),
+            producer_version: env!("CARGO_PKG_VERSION").to_string(),
+            graph: Some(graph),
+            opset_import: vec![
+                OperatorSetIdProto {
+                    domain: String::new(),
+                    version: options.opset_version,
+                },
+                OperatorSetIdProto {
+                    domain: CONTRIB_DOMAIN.to_string(),
+                    version: 1,
+                },
+            ],
+        }
+        .encode_to_vec()
+    }
+}
+
+// Minimal subset of the ONNX protobuf definitions (onnx.proto3).
+
+#[derive(Clone, PartialEq, Message)]
+struct ModelProto {
+    #[prost(int64, tag = "1")]
+    ir_version: i64,
+
+    #[prost(string, tag = "2")]
+    producer_name: String,
+
+    #[prost(string, tag = "3")]
+    producer_version: String,
+
+    #[prost(message, optional, tag = "7")]
+    graph: Option<GraphProto>,
+
+    #[prost(message, repeated, tag = "8")]
+    opset_import: Vec<OperatorSetIdProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct OperatorSetIdProto {
+    #[prost(string, tag = "1")]
+    domain: String,
+
+    #[prost(int64, tag = "2")]
+    version: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct GraphProto {
+    #[prost(message, repeated, tag = "1")]
+    node: Vec<NodeProto>,
+
+    #[prost(string, tag = "2")]
+    name: String,
+
+    #[prost(message, repeated, tag = "5")]
+    initializer: Vec<TensorProto>,
+
+    #[prost(message, repeated, tag = "11")]
+    input: Vec<ValueInfoProto>,
+
+    #[prost(message, repeated, tag = "12")]
+    output: Vec<ValueInfoProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct NodeProto {
+    #[prost(string, repeated, tag = "1")]
+    input: Vec<String>,
+
+    #[prost(string, repeated, tag = "2")]
+    output: Vec<String>,
+
+    #[prost(string, tag = "3")]
+    name: String,
+
+    #[prost(string, tag = "4")]
+    op_type: String,
+
+    #[prost(message, repeated, tag = "5")]
+    attribute: Vec<AttributeProto>,
+
+    #[prost(string, tag = "7")]
+    domain: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct AttributeProto {
+    #[prost(string, tag = "1")]
+    name: String,
+
+    #[prost(bytes = "vec", tag = "4")]
+    s: Vec<u8>,
+
+    #[prost(int32, tag = "20")]
+    r#type: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TensorProto {
+    #[prost(int64, repeated, tag = "1")]
+    dims: Vec<i64>,
+
+    #[prost(int32, tag = "2")]
+    data_type: i32,
+
+    #[prost(float, repeated, tag = "4")]
+    float_data: Vec<f32>,
+
+    #[prost(int32, repeated, tag = "5")]
+    int32_data: Vec<i32>,
+
+    #[prost(int64, repeated, tag = "7")]
+    int64_data: Vec<i64>,
+
+    #[prost(string, tag = "8")]
+    name: String,
+}
+
+impl TensorProto {
+    fn bool(name: &str, value: bool) -> Self {
+        TensorProto {
+            dims: vec![1],
+            data_type: BOOL,
+            int32_data: vec![value as i32],
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn float(name: &str, value: f32) -> Self {
+        TensorProto {
+            dims: vec![1],
+            data_type: FLOAT,
+            float_data: vec![value],
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn int64(name: &str, value: i64) -> Self {
+        TensorProto {
+            dims: vec![1],
+            data_type: INT64,
+            int64_data: vec![value],
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ValueInfoProto {
+    #[prost(string, tag = "1")]
+    name: String,
+
+    #[prost(message, optional, tag = "2")]
+    r#type: Option<TypeProto>,
+}
+
+impl ValueInfoProto {
+    /// Rank-1 tensor, with an unnamed dimension when `dim_param` is `None`.
+    fn tensor(name: &str, elem_type: i32, dim_param: Option<&str>) -> Self {
+        ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(TypeProto {
+                tensor_type: Some(type_proto::Tensor {
+                    elem_type,
+                    shape: Some(TensorShapeProto {
+                        dim: vec![tensor_shape_proto::Dimension {
+                            dim_param: dim_param.map(ToString::to_string),
+                        }],
+                    }),
+                }),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TypeProto {
+    // Member of the `value` oneof, the only member that we use.
+    #[prost(message, optional, tag = "1")]
+    tensor_type: Option<type_proto::Tensor>,
+}
+
+mod type_proto {
+    use prost_derive::Message;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Tensor {
+        #[prost(int32, tag = "1")]
+        pub elem_type: i32,
+
+        #[prost(message, optional, tag = "2")]
+        pub shape: Option<super::TensorShapeProto>,
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TensorShapeProto {
+    #[prost(message, repeated, tag = "1")]
+    dim: Vec<tensor_shape_proto::Dimension>,
+}
+
+mod tensor_shape_proto {
+    use prost_derive::Message;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Dimension {
+        // Member of the `value` oneof, the only member that we use.
+        #[prost(string, optional, tag = "2")]
+        pub dim_param: Option<String>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ModelProto, OnnxExportOptions, BOOL, CONTRIB_DOMAIN};
+    use crate::test_util::toy_model;
+
+    #[test]
+    fn exports_tokenizer_graph() {
+        let model = toy_model();
+        let onnx = model.to_onnx(&OnnxExportOptions {
+            add_bos: true,
+            ..Default::default()
+        });
+
+        let onnx: ModelProto = prost::Message::decode(onnx.as_slice()).unwrap();
+        assert!(onnx
+            .opset_import
+            .iter()
+            .any(|opset| opset.domain == CONTRIB_DOMAIN));
+
+        let graph = onnx.graph.unwrap();
+        assert_eq!(graph.node.len(), 1);
+        let node = &graph.node[0];
+        assert_eq!(node.op_type, "SentencepieceTokenizer");
+        assert_eq!(node.domain, CONTRIB_DOMAIN);
+        assert_eq!(node.attribute[0].s, model.to_serialized_proto());
+        assert_eq!(node.output, ["tokens", "indices"]);
+
+        let add_bos = graph
+            .initializer
+            .iter()
+            .find(|tensor| tensor.name == "add_bos")
+            .unwrap();
+        assert_eq!(add_bos.data_type, BOOL);
+        assert_eq!(add_bos.int32_data, [1]);
+    }
+}
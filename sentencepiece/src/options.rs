@@ -0,0 +1,472 @@
+use std::fs;
+use std::path::Path;
+
+use prost_derive::Message;
+
+use crate::byte_fallback::split_unknown_bytes;
+use crate::proto::NormalizerSpec;
+#[cfg(feature = "unicode-segmentation")]
+use crate::unknown::split_unknown_graphemes;
+use crate::{IoError, PieceWithId, SentencePieceError, SentencePieceProcessor};
+
+/// Encoding options.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EncodeOptions {
+    /// Add the beginning of sentence piece.
+    ///
+    /// The piece is added before the other pieces, with an empty span at
+    /// the start of the sentence.
+    pub add_bos: bool,
+
+    /// Add the end of sentence piece.
+    ///
+    /// The piece is added after the other pieces, with an empty span at
+    /// the end of the sentence.
+    pub add_eos: bool,
+
+    /// Reverse the order of the pieces.
+    ///
+    /// The pieces are reversed before the beginning and end of sentence
+    /// pieces are added.
+    pub reverse: bool,
+
+    /// Use the unknown piece as the string of unknown pieces.
+    ///
+    /// By default, the string of an unknown piece is the input that it
+    /// covers.
+    pub emit_unk_piece: bool,
+
+    /// Split unknown pieces into byte pieces.
+    ///
+    /// Models that were trained without byte fallback encode characters
+    /// that are not in the vocabulary as the unknown piece, which loses
+    /// the characters. When this option is set, every byte of an unknown
+    /// span is encoded as the piece `<0xXX>` with the identifier
+    /// `unknown_bytes_base + byte` instead. The span of a byte piece is
+    /// the byte in the sentence.
+    ///
+    /// The 256 identifiers starting at the base should not be used by
+    /// the model, e.g. use `spp.len()` to add them after the vocabulary.
+    pub unknown_bytes_base: Option<u32>,
+
+    /// Encode one unknown piece per extended grapheme cluster.
+    ///
+    /// sentencepiece merges a run of unknown characters into a single
+    /// unknown piece, and the span of an unknown piece can end within a
+    /// user-perceived character, such as an emoji ZWJ sequence. When
+    /// this option is set, the spans of unknown pieces are extended to
+    /// grapheme cluster boundaries, trimming the spans of the adjacent
+    /// pieces, and split into one unknown piece per grapheme cluster.
+    ///
+    /// This option requires the `unicode-segmentation` feature. Encoding
+    /// fails with `SentencePieceError::InvalidArgument` when the option
+    /// is set and the feature is disabled.
+    pub split_unknown_graphemes: bool,
+}
+
+/// Decoding options.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DecodeOptions {
+    /// Skip special pieces.
+    ///
+    /// The beginning of sentence, end of sentence, padding, and other
+    /// control pieces are removed before decoding.
+    pub skip_special_tokens: bool,
+
+    /// Replace invalid UTF-8 sequences by the replacement character.
+    ///
+    /// By default, decoding fails with `SentencePieceError::InvalidUtf8`
+    /// when the decoded sentence is not valid UTF-8. When this option is
+    /// set, invalid sequences are replaced by U+FFFD instead.
+    pub lossy_utf8: bool,
+}
+
+/// Options for loading a model.
+///
+/// The options override the normalizer settings of the model. For
+/// example, the "legacy" and "non-legacy" behavior of Llama tokenizers
+/// in Hugging Face `transformers` differ in `add_dummy_prefix`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LoadOptions {
+    /// Override whether a dummy whitespace is added to the start of the
+    /// input.
+    pub add_dummy_prefix: Option<bool>,
+
+    /// Override whether leading, trailing, and duplicate internal
+    /// whitespace is removed from the input.
+    pub remove_extra_whitespaces: Option<bool>,
+}
+
+impl LoadOptions {
+    fn apply(&self, model: &mut OptionsProto) {
+        let normalizer_spec = model.normalizer_spec.get_or_insert_with(Default::default);
+        if let Some(add_dummy_prefix) = self.add_dummy_prefix {
+            normalizer_spec.add_dummy_prefix = Some(add_dummy_prefix);
+        }
+        if let Some(remove_extra_whitespaces) = self.remove_extra_whitespaces {
+            normalizer_spec.remove_extra_whitespaces = Some(remove_extra_whitespaces);
+        }
+    }
+}
+
+/// Model message in which only the normalizer specification is decoded.
+///
+/// The other fields are kept as serialized messages, so that models with
+/// pieces that are not valid UTF-8 can be loaded.
+#[derive(Clone, PartialEq, Message)]
+struct OptionsProto {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pieces: Vec<Vec<u8>>,
+
+    #[prost(bytes = "vec", optional, tag = "2")]
+    trainer_spec: Option<Vec<u8>>,
+
+    #[prost(message, optional, tag = "3")]
+    normalizer_spec: Option<NormalizerSpec>,
+
+    #[prost(bytes = "vec", optional, tag = "4")]
+    self_test_data: Option<Vec<u8>>,
+
+    #[prost(bytes = "vec", optional, tag = "5")]
+    denormalizer_spec: Option<Vec<u8>>,
+}
+
+impl SentencePieceProcessor {
+    /// Construct a processor from a serialized model with the given
+    /// options.
+    pub fn from_serialized_proto_with_options(
+        data: &[u8],
+        options: &LoadOptions,
+    ) -> Result<Self, SentencePieceError> {
+        let mut model: OptionsProto = prost::Message::decode(data)
+            .map_err(|err| SentencePieceError::InvalidProtobuf(err.to_string()))?;
+        options.apply(&mut model);
+        Self::from_serialized_proto(&prost::Message::encode_to_vec(&model))
+    }
+
+    /// Open a sentencepiece model with the given options.
+    pub fn open_with_options(
+        path: impl AsRef<Path>,
+        options: &LoadOptions,
+    ) -> Result<Self, IoError> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|error| IoError::Io {
+            desc: format!("Cannot read model {}", path.display()),
+            error,
+        })?;
+        Ok(Self::from_serialized_proto_with_options(&data, options)?)
+    }
+
+    /// Decode a sentence from piece identifiers with the given options.
+    ///
+    /// With the default options, this is the same as
+    /// [`SentencePieceProcessor::decode_piece_ids`].
+    pub fn decode_with_options(
+        &self,
+        pieces: &[u32],
+        options: &DecodeOptions,
+    ) -> Result<String, SentencePieceError> {
+        let decoded = if options.skip_special_tokens {
+            let special = [self.bos_id(), self.eos_id(), self.pad_id()];
+            let pieces = pieces
+                .iter()
+                .copied()
+                .filter(|&id| !special.contains(&Some(id)) && !self.is_control(id))
+                .collect::<Vec<_>>();
+            self.decode_piece_ids_bytes(&pieces)?
+        } else {
+            self.decode_piece_ids_bytes(pieces)?
+        };
+
+        if options.lossy_utf8 {
+            Ok(String::from_utf8_lossy(&decoded).into_owned())
+        } else {
+            String::from_utf8(decoded)
+                .map_err(|err| SentencePieceError::InvalidUtf8(err.into_bytes()))
+        }
+    }
+
+    /// Encode a sentence with the given options.
+    ///
+    /// With the default options, this is the same as
+    /// [`SentencePieceProcessor::encode`].
+    pub fn encode_with_options(
+        &self,
+        sentence: &str,
+        options: &EncodeOptions,
+    ) -> Result<Vec<PieceWithId>, SentencePieceError> {
+        let pieces = self.encode(sentence)?;
+        let unk_id = self.unk_id();
+
+        let pieces = if options.split_unknown_graphemes {
+            #[cfg(feature = "unicode-segmentation")]
+            {
+                split_unknown_graphemes(sentence, unk_id, pieces)
+            }
+            #[cfg(not(feature = "unicode-segmentation"))]
+            return Err(SentencePieceError::InvalidArgument(
+                "splitting unknown pieces into graphemes requires the unicode-segmentation feature"
+                    .to_string(),
+            ));
+        } else {
+            pieces
+        };
+
+        let mut pieces = match options.unknown_bytes_base {
+            Some(base) => split_unknown_bytes(sentence, unk_id, base, pieces)?,
+            None => pieces,
+        };
+
+        if options.emit_unk_piece {
+            let unk_piece = self.id_to_piece_lossy(unk_id);
+            for piece in pieces.iter_mut().filter(|piece| piece.id == unk_id) {
+                piece.piece = unk_piece.clone();
+            }
+        }
+
+        if options.reverse {
+            pieces.reverse();
+        }
+
+        if options.add_bos {
+            let bos_id = match self.bos_id() {
+                Some(id) => id,
+                None => {
+                    return Err(SentencePieceError::MissingPiece(
+                        self.trainer_spec()?.bos_piece().to_owned(),
+                    ))
+                }
+            };
+            pieces.insert(
+                0,
+                PieceWithId {
+                    piece: self.id_to_piece_lossy(bos_id),
+                    id: bos_id,
+                    span: (0, 0),
+                },
+            );
+        }
+
+        if options.add_eos {
+            let eos_id = match self.eos_id() {
+                Some(id) => id,
+                None => {
+                    return Err(SentencePieceError::MissingPiece(
+                        self.trainer_spec()?.eos_piece().to_owned(),
+                    ))
+                }
+            };
+            let len = sentence.len() as u32;
+            pieces.push(PieceWithId {
+                piece: self.id_to_piece_lossy(eos_id),
+                id: eos_id,
+                span: (len, len),
+            });
+        }
+
+        Ok(pieces)
+    }
+
+    fn id_to_piece_lossy(&self, id: u32) -> String {
+        String::from_utf8_lossy(
+            self.piece_bytes(id)
+                .expect("Identifier is within the vocabulary"),
+        )
+        .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{invalid_utf8_model, toy_model, INVALID_UTF8_PIECE};
+    use crate::{
+        DecodeOptions, EncodeOptions, IoError, LoadOptions, ModelBuilder, PieceType,
+        SentencePieceError, SentencePieceProcessor,
+    };
+
+    fn ids(model: &SentencePieceProcessor, sentence: &str, options: &EncodeOptions) -> Vec<u32> {
+        model
+            .encode_with_options(sentence, options)
+            .unwrap()
+            .into_iter()
+            .map(|piece| piece.id)
+            .collect()
+    }
+
+    #[test]
+    fn adds_bos_and_eos() {
+        let model = toy_model();
+        let options = EncodeOptions {
+            add_bos: true,
+            add_eos: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&model, "I saw a girl.", &options),
+            [1, 8, 465, 10, 947, 4, 2]
+        );
+
+        let pieces = model
+            .encode_with_options("I saw a girl.", &options)
+            .unwrap();
+        assert_eq!(pieces[0].piece, "<s>");
+        assert_eq!(pieces[0].span, (0, 0));
+        assert_eq!(pieces[6].piece, "</s>");
+        assert_eq!(pieces[6].span, (13, 13));
+    }
+
+    #[test]
+    fn reverses_pieces() {
+        let model = toy_model();
+        let options = EncodeOptions {
+            add_bos: true,
+            reverse: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&model, "I saw a girl.", &options),
+            [1, 4, 947, 10, 465, 8]
+        );
+    }
+
+    #[test]
+    fn emits_unk_piece() {
+        let model = toy_model();
+        let options = EncodeOptions {
+            emit_unk_piece: true,
+            ..Default::default()
+        };
+        let pieces = model.encode_with_options("I saw a ☃.", &options).unwrap();
+        let unknown = pieces
+            .iter()
+            .find(|piece| piece.id == model.unk_id())
+            .unwrap();
+        assert_eq!(unknown.piece, "<unk>");
+    }
+
+    #[test]
+    fn rejects_missing_bos_piece() {
+        let model = ModelBuilder::new()
+            .bos_piece("<bos>")
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("a", -1.0, PieceType::Normal)
+            .build()
+            .unwrap();
+        let options = EncodeOptions {
+            add_bos: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            model.encode_with_options("a", &options),
+            Err(SentencePieceError::MissingPiece("<bos>".to_string()))
+        );
+    }
+
+    #[test]
+    fn skips_special_tokens_when_decoding() {
+        let model = toy_model();
+        let ids = [1, 8, 465, 10, 947, 4, 2];
+        let options = DecodeOptions {
+            skip_special_tokens: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            model.decode_with_options(&ids, &options).unwrap(),
+            "I saw a girl."
+        );
+        assert_eq!(
+            model
+                .decode_with_options(&ids, &DecodeOptions::default())
+                .unwrap(),
+            model.decode_piece_ids(&ids).unwrap()
+        );
+    }
+
+    #[test]
+    fn lossy_decoding_keeps_valid_utf8() {
+        let model = toy_model();
+        let ids = [8, 465, 10, 947, 4];
+        let options = DecodeOptions {
+            lossy_utf8: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            model.decode_with_options(&ids, &options).unwrap(),
+            "I saw a girl."
+        );
+    }
+
+    #[test]
+    fn lossy_decoding_replaces_invalid_utf8() {
+        let model = invalid_utf8_model();
+        let options = DecodeOptions {
+            lossy_utf8: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            model.decode_with_options(&[3], &options).unwrap(),
+            "a\u{FFFD}b"
+        );
+        assert_eq!(
+            model.decode_with_options(&[3], &DecodeOptions::default()),
+            Err(SentencePieceError::InvalidUtf8(INVALID_UTF8_PIECE.to_vec()))
+        );
+    }
+
+    #[test]
+    fn overrides_normalizer_settings() {
+        let options = LoadOptions {
+            add_dummy_prefix: Some(false),
+            remove_extra_whitespaces: Some(false),
+        };
+        let model = SentencePieceProcessor::from_serialized_proto_with_options(
+            include_bytes!("../testdata/toy.model"),
+            &options,
+        )
+        .unwrap();
+        assert!(!model.add_dummy_prefix());
+        assert!(!model.remove_extra_whitespaces());
+        assert_eq!(model.normalize("I  saw").unwrap(), "I▁▁saw");
+
+        let model = SentencePieceProcessor::from_serialized_proto_with_options(
+            include_bytes!("../testdata/toy.model"),
+            &LoadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(model.normalize("I  saw").unwrap(), "▁I▁saw");
+    }
+
+    #[test]
+    fn opens_model_with_options() {
+        let options = LoadOptions {
+            add_dummy_prefix: Some(false),
+            remove_extra_whitespaces: None,
+        };
+        let model =
+            SentencePieceProcessor::open_with_options("testdata/toy.model", &options).unwrap();
+        assert!(!model.add_dummy_prefix());
+        assert!(model.remove_extra_whitespaces());
+
+        assert!(matches!(
+            SentencePieceProcessor::open_with_options("testdata/missing.model", &options),
+            Err(IoError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn loads_model_with_invalid_utf8_piece_with_options() {
+        let options = LoadOptions {
+            add_dummy_prefix: Some(false),
+            remove_extra_whitespaces: Some(false),
+        };
+        let model = SentencePieceProcessor::from_serialized_proto_with_options(
+            &invalid_utf8_model().to_serialized_proto(),
+            &options,
+        )
+        .unwrap();
+        assert!(!model.add_dummy_prefix());
+        assert!(!model.remove_extra_whitespaces());
+        assert_eq!(model.len(), 4);
+        assert_eq!(model.piece_bytes(3), Some(INVALID_UTF8_PIECE));
+    }
+}
@@ -0,0 +1,11 @@
+//! Protobuf messages of sentencepiece.
+//!
+//! These are the messages of `sentencepiece.proto` and
+//! `sentencepiece_model.proto`. They can be used to exchange encodings
+//! and models with other sentencepiece implementations, e.g. over gRPC.
+
+pub use crate::model::{
+    model_proto, self_test_data, trainer_spec, ModelProto, NormalizerSpec, SelfTestData,
+    TrainerSpec,
+};
+pub use crate::sentencepiece::{NBestSentencePieceText, SentencePiece, SentencePieceText};
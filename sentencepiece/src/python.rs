@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::SentencePieceProcessor;
+
+/// Python wrapper of [`SentencePieceProcessor`].
+#[pyclass(name = "SentencePieceProcessor", module = "sentencepiece_rs")]
+struct PySentencePieceProcessor {
+    spp: SentencePieceProcessor,
+}
+
+#[pymethods]
+impl PySentencePieceProcessor {
+    #[new]
+    fn new(path: PathBuf) -> PyResult<Self> {
+        Ok(PySentencePieceProcessor {
+            spp: SentencePieceProcessor::open(path).map_err(value_error)?,
+        })
+    }
+
+    #[staticmethod]
+    fn from_serialized_proto(data: &[u8]) -> PyResult<Self> {
+        Ok(PySentencePieceProcessor {
+            spp: SentencePieceProcessor::from_serialized_proto(data).map_err(value_error)?,
+        })
+    }
+
+    fn to_serialized_proto<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.spp.to_serialized_proto())
+    }
+
+    /// Encode a sentence as `(piece, id, begin, end)` tuples.
+    fn encode(&self, sentence: &str) -> PyResult<Vec<(String, u32, u32, u32)>> {
+        Ok(self
+            .spp
+            .encode(sentence)
+            .map_err(value_error)?
+            .into_iter()
+            .map(|piece| (piece.piece, piece.id, piece.span.0, piece.span.1))
+            .collect())
+    }
+
+    fn encode_as_ids(&self, sentence: &str) -> PyResult<Vec<u32>> {
+        self.spp.encode_as_ids(sentence).map_err(value_error)
+    }
+
+    fn encode_as_pieces(&self, sentence: &str) -> PyResult<Vec<String>> {
+        self.spp.encode_as_pieces(sentence).map_err(value_error)
+    }
+
+    fn decode_ids(&self, ids: Vec<u32>) -> PyResult<String> {
+        self.spp.decode_piece_ids(&ids).map_err(value_error)
+    }
+
+    fn decode_pieces(&self, pieces: Vec<String>) -> PyResult<String> {
+        self.spp.decode_pieces(&pieces).map_err(value_error)
+    }
+
+    fn id_to_piece(&self, id: u32) -> PyResult<String> {
+        self.spp
+            .id_to_piece(id)
+            .map(ToOwned::to_owned)
+            .map_err(value_error)
+    }
+
+    fn piece_to_id(&self, piece: &str) -> PyResult<Option<u32>> {
+        self.spp.piece_to_id(piece).map_err(value_error)
+    }
+
+    fn is_byte(&self, id: u32) -> bool {
+        self.spp.is_byte(id)
+    }
+
+    fn is_control(&self, id: u32) -> bool {
+        self.spp.is_control(id)
+    }
+
+    fn is_unused(&self, id: u32) -> bool {
+        self.spp.is_unused(id)
+    }
+
+    fn bos_id(&self) -> Option<u32> {
+        self.spp.bos_id()
+    }
+
+    fn eos_id(&self) -> Option<u32> {
+        self.spp.eos_id()
+    }
+
+    fn pad_id(&self) -> Option<u32> {
+        self.spp.pad_id()
+    }
+
+    fn unk_id(&self) -> u32 {
+        self.spp.unk_id()
+    }
+
+    fn __len__(&self) -> usize {
+        self.spp.len()
+    }
+}
+
+fn value_error(err: impl ToString) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+/// Python module `sentencepiece_rs` with the processor bindings.
+#[pymodule]
+#[pyo3(name = "sentencepiece_rs")]
+pub fn python_module(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PySentencePieceProcessor>()
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::prelude::*;
+    use pyo3::types::{IntoPyDict, PyBytes};
+
+    use super::python_module;
+
+    #[test]
+    fn uses_processor_from_python() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "sentencepiece_rs").unwrap();
+            python_module(py, module).unwrap();
+
+            let locals = [
+                ("spm", module.to_object(py)),
+                (
+                    "model",
+                    PyBytes::new(py, include_bytes!("../testdata/toy.model")).to_object(py),
+                ),
+            ]
+            .into_py_dict(py);
+
+            py.run(
+                r#"
+sp = spm.SentencePieceProcessor.from_serialized_proto(model)
+assert len(sp) == 1000
+assert sp.encode_as_ids("I saw a girl.") == [8, 465, 10, 947, 4]
+assert sp.encode("I saw")[1] == ("▁saw", 465, 1, 5)
+assert sp.decode_ids([8, 465]) == "I saw"
+assert sp.piece_to_id("▁the") == 5
+assert sp.unk_id() == 0 and sp.pad_id() is None
+assert spm.SentencePieceProcessor.from_serialized_proto(sp.to_serialized_proto()).id_to_piece(5) == "▁the"
+try:
+    sp.decode_ids([1000])
+    assert False
+except ValueError:
+    pass
+"#,
+                None,
+                Some(locals),
+            )
+            .unwrap();
+        });
+    }
+}
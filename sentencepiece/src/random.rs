@@ -0,0 +1,45 @@
+use sentencepiece_sys::spp_set_random_generator_seed;
+
+/// Set the seed of the random generator used for sampling.
+///
+/// sentencepiece uses a random generator per thread, which is seeded
+/// when a thread samples for the first time. The seed applies to all
+/// processors, but only to threads that have not sampled yet. To make
+/// sampling reproducible, set the seed before starting the threads that
+/// sample.
+pub fn set_random_seed(seed: u32) {
+    unsafe { spp_set_random_generator_seed(seed) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::set_random_seed;
+    use crate::test_util::toy_model;
+
+    #[test]
+    fn seeded_sampling_is_reproducible() {
+        let model = toy_model();
+        set_random_seed(42);
+
+        let sample = || {
+            thread::scope(|scope| {
+                scope
+                    .spawn(|| {
+                        (0..10)
+                            .map(|_| {
+                                model
+                                    .sample_encode("I saw a girl with a telescope.", 10, 0.5)
+                                    .unwrap()
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .join()
+                    .unwrap()
+            })
+        };
+
+        assert_eq!(sample(), sample());
+    }
+}
@@ -0,0 +1,109 @@
+use prost_derive::Message;
+
+use crate::{PieceWithId, SentencePieceError, SentencePieceProcessor};
+
+/// Encoded sentence without the input and surface strings.
+///
+/// The input and surface strings are copied from the input, so they are
+/// not valid UTF-8 when the input is not. They are skipped as unknown
+/// fields when decoding this message.
+#[derive(Clone, PartialEq, Message)]
+struct RawSentencePieceText {
+    /// A sequence of sentence pieces.
+    #[prost(message, repeated, tag = "2")]
+    pieces: Vec<RawSentencePiece>,
+}
+
+/// Piece of an encoded sentence without the surface string.
+#[derive(Clone, Eq, PartialEq, Message)]
+struct RawSentencePiece {
+    /// Internal representation for the decoder.
+    #[prost(string, optional, tag = "1")]
+    piece: Option<String>,
+
+    /// Vocabulary id.
+    #[prost(uint32, optional, tag = "2")]
+    id: Option<u32>,
+
+    /// Starting position.
+    #[prost(uint32, optional, tag = "4")]
+    begin: Option<u32>,
+
+    /// End position.
+    #[prost(uint32, optional, tag = "5")]
+    end: Option<u32>,
+}
+
+impl SentencePieceProcessor {
+    /// Encode a sentence that is not necessarily valid UTF-8.
+    ///
+    /// The spans of the pieces are byte offsets in the input.
+    /// sentencepiece normalizes every byte of an invalid UTF-8 sequence
+    /// to the replacement character U+FFFD, so models with byte fallback
+    /// encode such a byte as the byte pieces of U+FFFD and other models
+    /// as the unknown piece.
+    pub fn encode_bytes(&self, sentence: &[u8]) -> Result<Vec<PieceWithId>, SentencePieceError> {
+        let sp_text: RawSentencePieceText = self.decode_encode_protobuf_into(sentence)?;
+
+        sp_text
+            .pieces
+            .into_iter()
+            .map(|proto_piece| {
+                Ok(PieceWithId {
+                    piece: proto_piece
+                        .piece
+                        .ok_or_else(|| SentencePieceError::MissingData("piece".to_string()))?,
+                    id: proto_piece
+                        .id
+                        .ok_or_else(|| SentencePieceError::MissingData("id".to_string()))?,
+                    span: (
+                        proto_piece
+                            .begin
+                            .ok_or_else(|| SentencePieceError::MissingData("begin".to_string()))?,
+                        proto_piece
+                            .end
+                            .ok_or_else(|| SentencePieceError::MissingData("end".to_string()))?,
+                    ),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{byte_fallback_model, toy_model};
+
+    #[test]
+    fn encodes_valid_bytes_like_str() {
+        let model = toy_model();
+        let sentence = "I saw a girl with a telescope.";
+        assert_eq!(
+            model.encode_bytes(sentence.as_bytes()).unwrap(),
+            model.encode(sentence).unwrap()
+        );
+    }
+
+    #[test]
+    fn encodes_invalid_utf8_with_byte_fallback() {
+        let model = byte_fallback_model();
+
+        let pieces = model.encode_bytes(b"a\xffa").unwrap();
+        assert!(pieces.iter().all(|piece| piece.id != model.unk_id()));
+
+        let bytes = pieces
+            .iter()
+            .filter(|piece| model.is_byte(piece.id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bytes
+                .iter()
+                .map(|piece| piece.piece.as_str())
+                .collect::<Vec<_>>(),
+            ["<0xEF>", "<0xBF>", "<0xBD>"]
+        );
+        assert!(bytes
+            .iter()
+            .all(|piece| piece.span.0 >= 1 && piece.span.1 <= 2));
+    }
+}
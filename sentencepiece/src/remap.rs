@@ -0,0 +1,244 @@
+use crate::{LoadOptions, SentencePieceError, SentencePieceProcessor};
+
+/// Policy for source pieces that are not in the target vocabulary.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MissingPiecePolicy {
+    /// Map the piece to the unknown piece of the target model.
+    Unknown,
+
+    /// Encode the text of the piece with the target model.
+    ///
+    /// The meta symbol `▁` of the piece is encoded as whitespace. No
+    /// dummy prefix is added and whitespace is not removed, so that a
+    /// piece only starts with `▁` in the target model when it does in
+    /// the source model. Control and byte pieces do not have text and
+    /// are mapped to the unknown piece, as are pieces whose text
+    /// encodes to no pieces.
+    Reencode,
+
+    /// Fail with `SentencePieceError::MissingPiece`.
+    Error,
+}
+
+/// Translation table from the piece identifiers of one model to another.
+///
+/// The table is constructed by matching the piece strings of the two
+/// vocabularies. Since a source piece may have to be split into
+/// several target pieces, an identifier translates to a sequence of
+/// identifiers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IdTranslation {
+    offsets: Vec<usize>,
+    ids: Vec<u32>,
+}
+
+impl IdTranslation {
+    /// Construct a translation table from `source` to `target`.
+    ///
+    /// The unknown piece of `source` always maps to the unknown piece
+    /// of `target`. Other pieces that are not in the vocabulary of
+    /// `target` are handled according to `policy`.
+    pub fn new(
+        source: &SentencePieceProcessor,
+        target: &SentencePieceProcessor,
+        policy: MissingPiecePolicy,
+    ) -> Result<Self, SentencePieceError> {
+        let mut offsets = Vec::with_capacity(source.len() + 1);
+        offsets.push(0);
+        let mut ids = Vec::with_capacity(source.len());
+
+        let source_unk_id = source.unk_id();
+        let target_unk_id = target.unk_id();
+
+        let reencoder = match policy {
+            MissingPiecePolicy::Reencode => {
+                Some(SentencePieceProcessor::from_serialized_proto_with_options(
+                    &target.to_serialized_proto(),
+                    &LoadOptions {
+                        add_dummy_prefix: Some(false),
+                        remove_extra_whitespaces: Some(false),
+                    },
+                )?)
+            }
+            _ => None,
+        };
+
+        for id in 0..source.len() as u32 {
+            let piece = String::from_utf8_lossy(
+                source
+                    .piece_bytes(id)
+                    .expect("Identifier is within the vocabulary"),
+            );
+
+            if id == source_unk_id {
+                ids.push(target_unk_id);
+            } else if let Some(target_id) = lookup(target, &piece) {
+                ids.push(target_id);
+            } else if let Some(reencoder) = &reencoder {
+                let reencoded = if source.is_control(id) || source.is_byte(id) {
+                    Vec::new()
+                } else {
+                    reencoder.encode_as_ids(&piece.replace('▁', " "))?
+                };
+
+                if reencoded.is_empty() {
+                    ids.push(target_unk_id);
+                } else {
+                    ids.extend(reencoded);
+                }
+            } else {
+                match policy {
+                    MissingPiecePolicy::Error => {
+                        return Err(SentencePieceError::MissingPiece(piece.into_owned()))
+                    }
+                    _ => ids.push(target_unk_id),
+                }
+            }
+
+            offsets.push(ids.len());
+        }
+
+        Ok(IdTranslation { offsets, ids })
+    }
+
+    /// Returns `true` if the table does not contain any identifiers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the number of source identifiers in the table.
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Translate a sequence of source identifiers.
+    pub fn translate(&self, ids: &[u32]) -> Result<Vec<u32>, SentencePieceError> {
+        let mut translated = Vec::with_capacity(ids.len());
+        for &id in ids {
+            translated.extend_from_slice(
+                self.translate_id(id)
+                    .ok_or(SentencePieceError::InvalidPieceId(id))?,
+            );
+        }
+        Ok(translated)
+    }
+
+    /// Translate a single source identifier.
+    ///
+    /// Returns `None` when the identifier is not in the source vocabulary.
+    pub fn translate_id(&self, id: u32) -> Option<&[u32]> {
+        let id = id as usize;
+        if id >= self.len() {
+            return None;
+        }
+
+        Some(&self.ids[self.offsets[id]..self.offsets[id + 1]])
+    }
+}
+
+fn lookup(spp: &SentencePieceProcessor, piece: &str) -> Option<u32> {
+    // Pieces with nul bytes cannot be looked up, treat them as missing.
+    spp.piece_to_id(piece).ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::toy_model;
+    use crate::{
+        IdTranslation, MissingPiecePolicy, ModelBuilder, PieceType, SentencePieceError,
+        SentencePieceProcessor,
+    };
+
+    #[test]
+    fn translation_to_same_model_is_identity() {
+        let model = toy_model();
+        let translation = IdTranslation::new(&model, &model, MissingPiecePolicy::Error).unwrap();
+        assert_eq!(translation.len(), model.len());
+
+        let ids = [8, 465, 10, 947, 41, 10, 170, 168, 110, 28, 20, 143, 4];
+        assert_eq!(translation.translate(&ids).unwrap(), ids);
+    }
+
+    #[test]
+    fn reencodes_missing_pieces_with_target_model() {
+        let source = toy_model();
+        let target = ModelBuilder::new()
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("<s>", 0.0, PieceType::Control)
+            .piece("</s>", 0.0, PieceType::Control)
+            .piece("▁gi", -1.0, PieceType::Normal)
+            .piece("rl", -1.0, PieceType::Normal)
+            .pieces(
+                ["▁", "g", "i", "r", "l"]
+                    .iter()
+                    .map(|&piece| (piece, -5.0, PieceType::Normal)),
+            )
+            .build()
+            .unwrap();
+        let source_id = |piece| source.piece_to_id(piece).unwrap().unwrap();
+        let target_id = |piece| target.piece_to_id(piece).unwrap().unwrap();
+
+        let translation =
+            IdTranslation::new(&source, &target, MissingPiecePolicy::Reencode).unwrap();
+        assert_eq!(
+            translation.translate_id(source_id("▁girl")).unwrap(),
+            [target_id("▁gi"), target_id("rl")]
+        );
+        // Characters that the target model does not know are unknown.
+        assert_eq!(translation.translate_id(source_id(".")).unwrap(), [0]);
+    }
+
+    fn small_target() -> SentencePieceProcessor {
+        ModelBuilder::new()
+            .piece("<s>", 0.0, PieceType::Control)
+            .piece("</s>", 0.0, PieceType::Control)
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("▁the", -1.0, PieceType::Normal)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn maps_missing_pieces_to_unknown() {
+        let source = toy_model();
+        let target = small_target();
+        let unk_id = target.unk_id();
+        assert_eq!(unk_id, 2);
+
+        let translation =
+            IdTranslation::new(&source, &target, MissingPiecePolicy::Unknown).unwrap();
+        assert_eq!(translation.len(), source.len());
+        assert_eq!(translation.translate_id(source.unk_id()).unwrap(), [unk_id]);
+        assert_eq!(translation.translate_id(1).unwrap(), [0]);
+        assert_eq!(
+            translation
+                .translate_id(source.piece_to_id("▁the").unwrap().unwrap())
+                .unwrap(),
+            [3]
+        );
+        assert_eq!(translation.translate_id(3).unwrap(), [unk_id]);
+    }
+
+    #[test]
+    fn reports_missing_pieces() {
+        let source = toy_model();
+        let target = small_target();
+        assert_eq!(
+            IdTranslation::new(&source, &target, MissingPiecePolicy::Error),
+            Err(SentencePieceError::MissingPiece(
+                source.id_to_piece(3).unwrap().to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn translation_rejects_out_of_range_ids() {
+        let model = toy_model();
+        let translation = IdTranslation::new(&model, &model, MissingPiecePolicy::Unknown).unwrap();
+        assert_eq!(translation.translate_id(1000), None);
+        assert_eq!(
+            translation.translate(&[8, 1000]),
+            Err(SentencePieceError::InvalidPieceId(1000))
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::num::NonZeroUsize;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::{IoError, SentencePieceProcessor};
+
+type Job = (usize, Vec<Vec<u32>>);
+
+type JobResult = (usize, Result<(usize, Vec<Vec<u32>>), IoError>);
+
+/// Format of a dataset of piece identifier sequences.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TokenFormat {
+    /// Sequences of little-endian `u32` identifiers, each preceded by
+    /// its length as a little-endian `u32`.
+    Binary,
+
+    /// One JSON array of identifiers per line, such as `[8, 465, 4]`.
+    ///
+    /// Empty lines are skipped.
+    Jsonl,
+}
+
+impl TokenFormat {
+    fn read_sequence(
+        self,
+        reader: &mut impl BufRead,
+        line: &mut String,
+    ) -> Result<Option<Vec<u32>>, IoError> {
+        match self {
+            TokenFormat::Binary => read_binary_sequence(reader).map_err(read_error),
+            TokenFormat::Jsonl => loop {
+                line.clear();
+                if reader.read_line(line).map_err(read_error)? == 0 {
+                    return Ok(None);
+                }
+
+                let trimmed = line.trim();
+                if !trimmed.is_empty() {
+                    return parse_json_ids(trimmed).map(Some);
+                }
+            },
+        }
+    }
+
+    fn write_sequence(self, writer: &mut impl Write, ids: &[u32]) -> io::Result<()> {
+        match self {
+            TokenFormat::Binary => {
+                writer.write_all(&(ids.len() as u32).to_le_bytes())?;
+                for id in ids {
+                    writer.write_all(&id.to_le_bytes())?;
+                }
+            }
+            TokenFormat::Jsonl => {
+                writer.write_all(b"[")?;
+                for (idx, id) in ids.iter().enumerate() {
+                    if idx != 0 {
+                        writer.write_all(b",")?;
+                    }
+                    write!(writer, "{}", id)?;
+                }
+                writer.write_all(b"]\n")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics of a re-tokenization run.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RetokenizeStats {
+    /// Number of sequences.
+    pub sequences: usize,
+
+    /// Number of pieces in the source dataset.
+    pub source_pieces: usize,
+
+    /// Number of pieces in the target dataset.
+    pub target_pieces: usize,
+}
+
+/// Re-tokenize a dataset of piece identifiers from one model to another.
+///
+/// Every sequence is decoded with the source model and the resulting
+/// text is encoded with the target model. The dataset is streamed in
+/// batches that are processed by worker threads. The number of batches
+/// in flight is bounded, so memory use does not depend on the size of
+/// the dataset. The order of the sequences is preserved.
+pub struct Retokenizer<'a> {
+    source: &'a SentencePieceProcessor,
+    target: &'a SentencePieceProcessor,
+    input_format: TokenFormat,
+    output_format: TokenFormat,
+    threads: usize,
+    batch_size: usize,
+}
+
+impl<'a> Retokenizer<'a> {
+    /// Construct a re-tokenizer from `source` to `target`.
+    ///
+    /// By default, the input and output are in the JSONL format, the
+    /// number of threads is the available parallelism, and batches
+    /// contain 1024 sequences.
+    pub fn new(source: &'a SentencePieceProcessor, target: &'a SentencePieceProcessor) -> Self {
+        Retokenizer {
+            source,
+            target,
+            input_format: TokenFormat::Jsonl,
+            output_format: TokenFormat::Jsonl,
+            threads: thread::available_parallelism()
+                .map(NonZeroUsize::get)
+                .unwrap_or(1),
+            batch_size: 1024,
+        }
+    }
+
+    /// Set the number of sequences per batch.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set the format of the input dataset.
+    pub fn with_input_format(mut self, format: TokenFormat) -> Self {
+        self.input_format = format;
+        self
+    }
+
+    /// Set the format of the output dataset.
+    pub fn with_output_format(mut self, format: TokenFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Set the number of worker threads.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Re-tokenize the dataset in `reader` and write it to `writer`.
+    pub fn run<R, W>(&self, reader: R, writer: W) -> Result<RetokenizeStats, IoError>
+    where
+        R: BufRead + Send,
+        W: Write,
+    {
+        // Every batch that is read holds a permit until it is written,
+        // which bounds the number of batches in memory.
+        let capacity = 2 * self.threads;
+        let (permit_tx, permit_rx) = sync_channel(capacity);
+        for _ in 0..capacity {
+            permit_tx
+                .send(())
+                .expect("Permit channel has sufficient capacity");
+        }
+
+        let (job_tx, job_rx) = sync_channel(capacity);
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let (result_tx, result_rx) = sync_channel(capacity);
+
+        thread::scope(|scope| {
+            {
+                let result_tx = result_tx.clone();
+                scope.spawn(move || self.read_batches(reader, job_tx, result_tx, permit_rx));
+            }
+
+            for _ in 0..self.threads {
+                let job_rx = job_rx.clone();
+                let result_tx = result_tx.clone();
+                scope.spawn(move || self.process_batches(job_rx, result_tx));
+            }
+
+            // Workers own the remaining receiver and senders, so that the
+            // channels are closed when the workers are done.
+            drop(job_rx);
+            drop(result_tx);
+
+            self.write_batches(writer, result_rx, permit_tx)
+        })
+    }
+
+    fn process_batches(&self, jobs: Arc<Mutex<Receiver<Job>>>, results: SyncSender<JobResult>) {
+        loop {
+            let job = jobs.lock().expect("Job queue lock is poisoned").recv();
+            let (idx, batch) = match job {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+
+            let source_pieces = batch.iter().map(Vec::len).sum();
+            let result = batch
+                .iter()
+                .map(|ids| self.retokenize(ids))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|batch| (source_pieces, batch));
+
+            if results.send((idx, result)).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn read_batches(
+        &self,
+        mut reader: impl BufRead,
+        jobs: SyncSender<Job>,
+        results: SyncSender<JobResult>,
+        permits: Receiver<()>,
+    ) {
+        let mut line = String::new();
+        for idx in 0.. {
+            if permits.recv().is_err() {
+                return;
+            }
+
+            let mut batch = Vec::with_capacity(self.batch_size);
+            while batch.len() < self.batch_size {
+                match self.input_format.read_sequence(&mut reader, &mut line) {
+                    Ok(Some(ids)) => batch.push(ids),
+                    Ok(None) => break,
+                    Err(err) => {
+                        // Reported by the writer after all preceding batches.
+                        let _ = results.send((idx, Err(err)));
+                        return;
+                    }
+                }
+            }
+
+            let last = batch.len() < self.batch_size;
+            if !batch.is_empty() && jobs.send((idx, batch)).is_err() {
+                return;
+            }
+
+            if last {
+                return;
+            }
+        }
+    }
+
+    fn retokenize(&self, ids: &[u32]) -> Result<Vec<u32>, IoError> {
+        let text = self.source.decode_piece_ids(ids)?;
+        Ok(self
+            .target
+            .encode(&text)?
+            .into_iter()
+            .map(|piece| piece.id)
+            .collect())
+    }
+
+    fn write_batches(
+        &self,
+        writer: impl Write,
+        results: Receiver<JobResult>,
+        permits: SyncSender<()>,
+    ) -> Result<RetokenizeStats, IoError> {
+        let mut writer = BufWriter::new(writer);
+        let mut stats = RetokenizeStats::default();
+
+        // Batches can be completed out of order, hold them until all
+        // preceding batches are written.
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (idx, result) in results {
+            pending.insert(idx, result);
+
+            while let Some(result) = pending.remove(&next) {
+                let (source_pieces, batch) = result?;
+                for ids in &batch {
+                    self.output_format
+                        .write_sequence(&mut writer, ids)
+                        .map_err(write_error)?;
+                    stats.target_pieces += ids.len();
+                }
+                stats.sequences += batch.len();
+                stats.source_pieces += source_pieces;
+                next += 1;
+
+                // Fails when the reader is done, which is fine.
+                let _ = permits.send(());
+            }
+        }
+
+        writer.flush().map_err(write_error)?;
+
+        Ok(stats)
+    }
+}
+
+fn parse_json_ids(line: &str) -> Result<Vec<u32>, IoError> {
+    let ids = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(|| IoError::Format(format!("Expected a JSON array: {}", line)))?;
+
+    if ids.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    ids.split(',')
+        .map(|id| {
+            let id = id.trim();
+            id.parse()
+                .map_err(|_| IoError::Format(format!("Invalid piece identifier: {}", id)))
+        })
+        .collect()
+}
+
+fn read_binary_sequence(reader: &mut impl BufRead) -> io::Result<Option<Vec<u32>>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    // The byte length can overflow on targets with a 32-bit usize.
+    let n_bytes = (u32::from_le_bytes(len) as usize)
+        .checked_mul(4)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Sequence is too long"))?;
+
+    // The length is not trusted, so memory is only allocated for the
+    // data that is actually read.
+    let mut data = Vec::new();
+    reader
+        .by_ref()
+        .take(n_bytes as u64)
+        .read_to_end(&mut data)?;
+    if data.len() != n_bytes {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Sequence is truncated",
+        ));
+    }
+
+    Ok(Some(
+        data.chunks_exact(4)
+            .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+            .collect(),
+    ))
+}
+
+fn read_error(error: io::Error) -> IoError {
+    IoError::Io {
+        desc: "Cannot read piece identifiers".to_string(),
+        error,
+    }
+}
+
+fn write_error(error: io::Error) -> IoError {
+    IoError::Io {
+        desc: "Cannot write piece identifiers".to_string(),
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use super::read_binary_sequence;
+    use crate::test_util::toy_model;
+    use crate::{IoError, Retokenizer, TokenFormat};
+
+    #[test]
+    fn rejects_truncated_binary_sequence() {
+        let mut data = (1u32 << 28).to_le_bytes().to_vec();
+        data.extend_from_slice(&8u32.to_le_bytes());
+        let error = read_binary_sequence(&mut Cursor::new(data)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn retokenizes_jsonl_in_order() {
+        let model = toy_model();
+        let input =
+            "[8,465,10,947,41,10,170,168,110,28,20,143,4]\n\n[]\n[8, 465, 10, 947, 4]\n".repeat(10);
+
+        let mut output = Vec::new();
+        let stats = Retokenizer::new(&model, &model)
+            .with_batch_size(1)
+            .with_threads(4)
+            .run(input.as_bytes(), &mut output)
+            .unwrap();
+
+        assert_eq!(stats.sequences, 30);
+        assert_eq!(stats.source_pieces, 180);
+        assert_eq!(stats.target_pieces, 180);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[8,465,10,947,41,10,170,168,110,28,20,143,4]\n[]\n[8,465,10,947,4]\n".repeat(10)
+        );
+    }
+
+    #[test]
+    fn converts_jsonl_to_binary() {
+        let model = toy_model();
+
+        let mut output = Vec::new();
+        Retokenizer::new(&model, &model)
+            .with_output_format(TokenFormat::Binary)
+            .run("[8,465,4]\n".as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(output, [3, 0, 0, 0, 8, 0, 0, 0, 209, 1, 0, 0, 4, 0, 0, 0]);
+
+        let mut roundtrip = Vec::new();
+        Retokenizer::new(&model, &model)
+            .with_input_format(TokenFormat::Binary)
+            .run(output.as_slice(), &mut roundtrip)
+            .unwrap();
+        assert_eq!(roundtrip, b"[8,465,4]\n");
+    }
+
+    #[test]
+    fn rejects_invalid_jsonl() {
+        let model = toy_model();
+        let result = Retokenizer::new(&model, &model)
+            .with_batch_size(1)
+            .run("[8,465,4]\n[8,foo]\n".as_bytes(), Vec::new());
+        assert!(matches!(result, Err(IoError::Format(_))));
+    }
+}
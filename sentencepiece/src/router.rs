@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use crate::{IdTranslation, MissingPiecePolicy, SentencePieceError, SentencePieceProcessor};
+
+type Classifier = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Router that encodes sentences with per-language models.
+///
+/// The router holds a processor for each language tag. Sentences are
+/// encoded with the model of their language, which is either given
+/// explicitly or determined by a classifier. The pieces of every model
+/// are translated to the vocabulary of a unified model using
+/// [`IdTranslation`], so that all encodings share the same identifier
+/// space and can be decoded with [`Router::decode`].
+pub struct Router {
+    unified: SentencePieceProcessor,
+    policy: MissingPiecePolicy,
+    models: HashMap<String, (SentencePieceProcessor, IdTranslation)>,
+    classifier: Option<Classifier>,
+    default_language: Option<String>,
+}
+
+impl Router {
+    /// Construct a router with the given unified model.
+    ///
+    /// Pieces of language models that are not in the vocabulary of the
+    /// unified model are handled according to `policy`.
+    pub fn new(unified: SentencePieceProcessor, policy: MissingPiecePolicy) -> Self {
+        Router {
+            unified,
+            policy,
+            models: HashMap::new(),
+            classifier: None,
+            default_language: None,
+        }
+    }
+
+    /// Add the model for a language.
+    ///
+    /// Replaces the existing model for the language, if any.
+    pub fn add_model(
+        &mut self,
+        language: impl Into<String>,
+        spp: SentencePieceProcessor,
+    ) -> Result<(), SentencePieceError> {
+        let translation = IdTranslation::new(&spp, &self.unified, self.policy)?;
+        self.models.insert(language.into(), (spp, translation));
+        Ok(())
+    }
+
+    /// Decode a sentence from unified piece identifiers.
+    pub fn decode(&self, ids: &[u32]) -> Result<String, SentencePieceError> {
+        self.unified.decode_piece_ids(ids)
+    }
+
+    /// Encode a sentence as unified piece identifiers.
+    ///
+    /// The language of the sentence is determined by the classifier.
+    /// The default language is used when the router does not have a
+    /// classifier or the classifier does not return a language.
+    pub fn encode(&self, sentence: &str) -> Result<Vec<u32>, SentencePieceError> {
+        let language = self
+            .classifier
+            .as_ref()
+            .and_then(|classifier| classifier(sentence))
+            .or_else(|| self.default_language.clone())
+            .ok_or(SentencePieceError::UndeterminedLanguage)?;
+
+        self.encode_language(&language, sentence)
+    }
+
+    /// Encode a sentence in the given language as unified piece identifiers.
+    pub fn encode_language(
+        &self,
+        language: &str,
+        sentence: &str,
+    ) -> Result<Vec<u32>, SentencePieceError> {
+        let (spp, translation) = self
+            .models
+            .get(language)
+            .ok_or_else(|| SentencePieceError::UnknownLanguage(language.to_string()))?;
+
+        let ids = spp
+            .encode(sentence)?
+            .into_iter()
+            .map(|piece| piece.id)
+            .collect::<Vec<_>>();
+
+        translation.translate(&ids)
+    }
+
+    /// Get the language tags of the models in the router.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.models.keys().map(String::as_str)
+    }
+
+    /// Get the unified model.
+    pub fn unified(&self) -> &SentencePieceProcessor {
+        &self.unified
+    }
+
+    /// Set the classifier that determines the language of a sentence.
+    ///
+    /// The classifier returns the language tag of a sentence or `None`
+    /// when the language cannot be determined.
+    pub fn with_classifier<F>(mut self, classifier: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.classifier = Some(Box::new(classifier));
+        self
+    }
+
+    /// Set the language that is used when the language of a sentence
+    /// cannot be determined.
+    pub fn with_default_language(mut self, language: impl Into<String>) -> Self {
+        self.default_language = Some(language.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::toy_model;
+    use crate::{MissingPiecePolicy, ModelBuilder, PieceType, Router, SentencePieceError};
+
+    fn router() -> Router {
+        let small = ModelBuilder::new()
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("<s>", 0.0, PieceType::Control)
+            .piece("</s>", 0.0, PieceType::Control)
+            .piece("▁", -2.0, PieceType::Normal)
+            .piece("a", -3.0, PieceType::Normal)
+            .piece("b", -3.0, PieceType::Normal)
+            .piece("▁ab", -1.0, PieceType::Normal)
+            .build()
+            .unwrap();
+
+        let mut router = Router::new(toy_model(), MissingPiecePolicy::Reencode)
+            .with_classifier(|sentence| {
+                if sentence
+                    .chars()
+                    .all(|ch| ch == 'a' || ch == 'b' || ch == ' ')
+                {
+                    Some("ab".to_string())
+                } else {
+                    None
+                }
+            })
+            .with_default_language("en");
+        router.add_model("en", toy_model()).unwrap();
+        router.add_model("ab", small).unwrap();
+        router
+    }
+
+    #[test]
+    fn encodes_with_language_model() {
+        let router = router();
+        assert_eq!(
+            router.encode_language("en", "I saw a girl.").unwrap(),
+            [8, 465, 10, 947, 4]
+        );
+
+        let ids = router.encode_language("ab", "ab ba").unwrap();
+        assert_eq!(router.decode(&ids).unwrap(), "ab ba");
+    }
+
+    #[test]
+    fn encodes_with_classifier() {
+        let router = router();
+        assert_eq!(
+            router.encode("ab ba").unwrap(),
+            router.encode_language("ab", "ab ba").unwrap()
+        );
+        assert_eq!(
+            router.encode("I saw a girl.").unwrap(),
+            [8, 465, 10, 947, 4]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_language() {
+        let router = router();
+        assert_eq!(
+            router.encode_language("nl", "Ik zag een meisje."),
+            Err(SentencePieceError::UnknownLanguage("nl".to_string()))
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// Capacity of a buffer that is retained after a call.
+///
+/// Larger buffers are shrunk, so that a single long input does not
+/// keep memory reserved for the lifetime of the thread.
+const MAX_RETAINED_CAPACITY: usize = 1 << 20;
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
+
+/// Reusable buffers for the output of sentencepiece.
+///
+/// Buffers are reused between calls on the same thread, so that
+/// encoding and decoding do not allocate memory for intermediate
+/// results once the buffers have grown to the size of the inputs.
+/// Buffers keep at most [`MAX_RETAINED_CAPACITY`] bytes between calls.
+#[derive(Default)]
+pub(crate) struct Scratch {
+    pub(crate) data: Vec<u8>,
+    pub(crate) pieces: Vec<u8>,
+}
+
+/// Call `f` with the scratch buffers of the current thread.
+///
+/// Fresh buffers are used when the buffers of the thread are in use.
+pub(crate) fn with_scratch<R>(f: impl FnOnce(&mut Scratch) -> R) -> R {
+    SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+        Ok(mut scratch) => {
+            let result = f(&mut scratch);
+            scratch.data.shrink_to(MAX_RETAINED_CAPACITY);
+            scratch.pieces.shrink_to(MAX_RETAINED_CAPACITY);
+            result
+        }
+        Err(_) => f(&mut Scratch::default()),
+    })
+}
+
+/// Reserve room for `size` bytes in a `Vec<u8>`.
+///
+/// The vector is cleared. Its length must be set after sentencepiece
+/// filled the reserved memory. Returns a null pointer when the memory
+/// cannot be reserved, since panics must not unwind into sentencepiece.
+pub(crate) unsafe extern "C" fn reserve_buffer(buffer: *mut c_void, size: usize) -> *mut c_void {
+    let buffer = &mut *(buffer as *mut Vec<u8>);
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        buffer.clear();
+        buffer.reserve(size);
+        buffer.as_mut_ptr() as *mut c_void
+    }))
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Get a buffer as the context of [`reserve_buffer`].
+pub(crate) fn buffer_ctx(buffer: &mut Vec<u8>) -> *mut c_void {
+    buffer as *mut Vec<u8> as *mut c_void
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{buffer_ctx, reserve_buffer, with_scratch, MAX_RETAINED_CAPACITY};
+
+    #[test]
+    fn reserve_buffer_returns_null_on_overflow() {
+        let mut buffer = vec![1u8, 2, 3];
+        let data = unsafe { reserve_buffer(buffer_ctx(&mut buffer), usize::MAX) };
+        assert!(data.is_null());
+
+        let data = unsafe { reserve_buffer(buffer_ctx(&mut buffer), 16) };
+        assert!(!data.is_null());
+        assert!(buffer.is_empty());
+        assert!(buffer.capacity() >= 16);
+    }
+
+    #[test]
+    fn shrinks_large_buffers() {
+        with_scratch(|scratch| scratch.data.reserve(4 * MAX_RETAINED_CAPACITY));
+        with_scratch(|scratch| assert!(scratch.data.capacity() <= MAX_RETAINED_CAPACITY));
+    }
+}
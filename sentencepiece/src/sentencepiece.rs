@@ -1,10 +1,11 @@
 use prost_derive::Message;
 
+/// Encoded sentence.
 #[derive(Clone, PartialEq, Message)]
 pub struct SentencePieceText {
     /// User input or postprocessed text.
     #[prost(string, optional, tag = "1")]
-    pub piece: Option<String>,
+    pub text: Option<String>,
 
     /// A sequence of sentence pieces.
     #[prost(message, repeated, tag = "2")]
@@ -15,6 +16,7 @@ pub struct SentencePieceText {
     pub score: Option<f32>,
 }
 
+/// Piece of an encoded sentence.
 #[derive(Clone, Eq, PartialEq, Message)]
 pub struct SentencePiece {
     /// Internal representation for the decoder.
@@ -37,3 +39,11 @@ pub struct SentencePiece {
     #[prost(uint32, optional, tag = "5")]
     pub end: Option<u32>,
 }
+
+/// N-best encodings of a sentence.
+#[derive(Clone, PartialEq, Message)]
+pub struct NBestSentencePieceText {
+    /// The encodings, from best to worst.
+    #[prost(message, repeated, tag = "1")]
+    pub nbests: Vec<SentencePieceText>,
+}
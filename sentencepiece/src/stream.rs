@@ -0,0 +1,231 @@
+use std::io::{BufRead, Lines};
+
+use crate::{IoError, PieceWithId, SentencePieceError, SentencePieceProcessor};
+
+const REPLACEMENT_CHARACTER: &str = "\u{FFFD}";
+
+/// Iterator that encodes sentences lazily.
+///
+/// Constructed by [`SentencePieceProcessor::encode_iter`].
+pub struct EncodeIter<'a, I> {
+    spp: &'a SentencePieceProcessor,
+    sentences: I,
+}
+
+impl<'a, I> Iterator for EncodeIter<'a, I>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    type Item = Result<Vec<PieceWithId>, SentencePieceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.sentences
+            .next()
+            .map(|sentence| self.spp.encode(sentence.as_ref()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.sentences.size_hint()
+    }
+}
+
+/// Iterator that encodes the lines of a reader lazily.
+///
+/// Constructed by [`SentencePieceProcessor::encode_lines`].
+pub struct EncodeLines<'a, R> {
+    spp: &'a SentencePieceProcessor,
+    lines: Lines<R>,
+}
+
+impl<'a, R> Iterator for EncodeLines<'a, R>
+where
+    R: BufRead,
+{
+    type Item = Result<Vec<PieceWithId>, IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next().map(|line| {
+            let line = line.map_err(|error| IoError::Io {
+                desc: "Cannot read sentence".to_string(),
+                error,
+            })?;
+            Ok(self.spp.encode(&line)?)
+        })
+    }
+}
+
+/// Decoder that decodes piece identifiers one at a time.
+///
+/// Decoding every identifier separately gives incorrect results, since
+/// the whitespace of a piece depends on its position and a character can
+/// be split over multiple byte fallback pieces. This decoder decodes the
+/// identifiers in context and only emits text when it is complete.
+///
+/// Constructed by [`SentencePieceProcessor::streaming_decoder`].
+pub struct StreamingDecoder<'a> {
+    spp: &'a SentencePieceProcessor,
+    ids: Vec<u32>,
+    prefix_offset: usize,
+    read_offset: usize,
+}
+
+impl<'a> StreamingDecoder<'a> {
+    /// Add a piece identifier.
+    ///
+    /// Returns the text that was completed by the piece, or `None` when
+    /// the piece does not complete any text, e.g. when it is a byte
+    /// fallback piece in the middle of a character.
+    pub fn push_id(&mut self, id: u32) -> Result<Option<String>, SentencePieceError> {
+        self.ids.push(id);
+
+        let (prefix, text) = self.decode_window()?;
+        if text.len() <= prefix.len() || text.ends_with(REPLACEMENT_CHARACTER.as_bytes()) {
+            return Ok(None);
+        }
+
+        self.prefix_offset = self.read_offset;
+        self.read_offset = self.ids.len();
+
+        Ok(Some(
+            String::from_utf8_lossy(&text[prefix.len()..]).into_owned(),
+        ))
+    }
+
+    /// Finish decoding.
+    ///
+    /// Returns the text that was not emitted yet. Incomplete characters
+    /// are decoded as the replacement character U+FFFD.
+    pub fn finish(self) -> Result<Option<String>, SentencePieceError> {
+        let (prefix, text) = self.decode_window()?;
+        if text.len() <= prefix.len() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            String::from_utf8_lossy(&text[prefix.len()..]).into_owned(),
+        ))
+    }
+
+    /// Decode the emitted and the full window.
+    ///
+    /// The window starts at the pieces that were emitted last, so that
+    /// the whitespace of the new pieces is decoded in context.
+    fn decode_window(&self) -> Result<(Vec<u8>, Vec<u8>), SentencePieceError> {
+        let prefix = self
+            .spp
+            .decode_piece_ids_bytes(&self.ids[self.prefix_offset..self.read_offset])?;
+        let text = self
+            .spp
+            .decode_piece_ids_bytes(&self.ids[self.prefix_offset..])?;
+        Ok((prefix, text))
+    }
+}
+
+impl SentencePieceProcessor {
+    /// Encode sentences lazily.
+    ///
+    /// The sentences are encoded as the iterator is consumed, so large
+    /// corpora can be encoded without keeping all encodings in memory.
+    pub fn encode_iter<I>(&self, sentences: I) -> EncodeIter<'_, I::IntoIter>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        EncodeIter {
+            spp: self,
+            sentences: sentences.into_iter(),
+        }
+    }
+
+    /// Construct a decoder that decodes piece identifiers one at a time.
+    pub fn streaming_decoder(&self) -> StreamingDecoder<'_> {
+        StreamingDecoder {
+            spp: self,
+            ids: Vec::new(),
+            prefix_offset: 0,
+            read_offset: 0,
+        }
+    }
+
+    /// Encode the lines of a reader lazily.
+    ///
+    /// Every line is encoded as a sentence, without its line ending.
+    pub fn encode_lines<R>(&self, reader: R) -> EncodeLines<'_, R>
+    where
+        R: BufRead,
+    {
+        EncodeLines {
+            spp: self,
+            lines: reader.lines(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{byte_fallback_model, toy_model};
+
+    #[test]
+    fn encodes_sentences_lazily() {
+        let model = toy_model();
+        let sentences = ["I saw a girl.", "I saw a girl with a telescope."];
+        let encoded = model
+            .encode_iter(sentences.iter())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            encoded,
+            sentences
+                .iter()
+                .map(|sentence| model.encode(sentence).unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn encodes_lines() {
+        let model = toy_model();
+        let encoded = model
+            .encode_lines("I saw a girl.\r\nI saw a girl with a telescope.\n".as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(encoded.len(), 2);
+        assert_eq!(encoded[0], model.encode("I saw a girl.").unwrap());
+        assert_eq!(
+            encoded[1],
+            model.encode("I saw a girl with a telescope.").unwrap()
+        );
+    }
+
+    #[test]
+    fn decodes_pieces_incrementally() {
+        let model = toy_model();
+        let ids = [8, 465, 10, 947, 41, 10, 170, 168, 110, 28, 20, 143, 4];
+
+        let mut decoder = model.streaming_decoder();
+        let mut decoded = String::new();
+        for &id in &ids {
+            decoded.push_str(&decoder.push_id(id).unwrap().unwrap_or_default());
+        }
+        assert_eq!(decoder.finish().unwrap(), None);
+        assert_eq!(decoded, model.decode_piece_ids(&ids).unwrap());
+    }
+
+    #[test]
+    fn buffers_incomplete_characters() {
+        let model = byte_fallback_model();
+        let ids = model.encode_as_ids("a☃a").unwrap();
+
+        let mut decoder = model.streaming_decoder();
+        let increments = ids
+            .iter()
+            .map(|&id| decoder.push_id(id).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            increments.iter().flatten().collect::<Vec<_>>(),
+            ["a", "☃", "a"]
+        );
+        assert_eq!(decoder.finish().unwrap(), None);
+    }
+}
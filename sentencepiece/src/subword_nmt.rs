@@ -0,0 +1,308 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::BufRead;
+
+use crate::{
+    IoError, ModelBuilder, ModelType, PieceType, SentencePieceError, SentencePieceProcessor,
+};
+
+const END_OF_WORD: &str = "</w>";
+
+/// BPE codes of subword-nmt.
+///
+/// The codes can be converted to a sentencepiece BPE model. The
+/// end-of-word marker `</w>` of subword-nmt corresponds to a `▁` suffix
+/// in sentencepiece, so the model treats whitespace as a suffix: the
+/// subword-nmt segmentation `low@@ er` corresponds to the pieces
+/// `low er▁`.
+///
+/// The segmentation of the converted model can be compared to the
+/// segmentation of subword-nmt using [`SubwordNmtCodes::check_parity`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubwordNmtCodes {
+    merges: Vec<(String, String)>,
+    ranks: HashMap<(String, String), usize>,
+    alphabet: BTreeSet<char>,
+    end_of_word_suffix: bool,
+}
+
+impl SubwordNmtCodes {
+    /// Read subword-nmt BPE codes.
+    ///
+    /// Both the version 0.1 format, where `</w>` is a separate symbol,
+    /// and the version 0.2 format (starting with `#version: 0.2`), where
+    /// `</w>` is a suffix of the last symbol of a word, are supported.
+    pub fn read(codes: impl BufRead) -> Result<Self, IoError> {
+        let mut merges = Vec::new();
+        let mut end_of_word_suffix = false;
+
+        for (idx, line) in codes.lines().enumerate() {
+            let line = line.map_err(|error| IoError::Io {
+                desc: "Cannot read BPE codes".to_string(),
+                error,
+            })?;
+
+            if idx == 0 {
+                if let Some(version) = line.strip_prefix("#version:") {
+                    end_of_word_suffix = version.trim() != "0.1";
+                    continue;
+                }
+            }
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut symbols = line.split_whitespace();
+            match (symbols.next(), symbols.next(), symbols.next()) {
+                (Some(left), Some(right), None) => {
+                    merges.push((to_piece(left), to_piece(right)));
+                }
+                _ => {
+                    return Err(IoError::Format(format!(
+                        "Expected two symbols on line {} of BPE codes: {}",
+                        idx + 1,
+                        line
+                    )))
+                }
+            }
+        }
+
+        let mut ranks = HashMap::with_capacity(merges.len());
+        for (rank, merge) in merges.iter().enumerate() {
+            ranks.entry(merge.clone()).or_insert(rank);
+        }
+
+        let alphabet = merges
+            .iter()
+            .flat_map(|(left, right)| left.chars().chain(right.chars()))
+            .filter(|&ch| ch != '▁')
+            .collect();
+
+        Ok(SubwordNmtCodes {
+            merges,
+            ranks,
+            alphabet,
+            end_of_word_suffix,
+        })
+    }
+
+    /// Add the characters of a subword-nmt vocabulary to the alphabet.
+    ///
+    /// Characters that do not occur in the codes cannot be encoded
+    /// by the converted model. Adding the vocabulary that subword-nmt
+    /// produced makes all characters of the training data known.
+    pub fn add_vocab(&mut self, vocab: impl BufRead) -> Result<(), IoError> {
+        for (idx, line) in vocab.lines().enumerate() {
+            let line = line.map_err(|error| IoError::Io {
+                desc: "Cannot read BPE vocabulary".to_string(),
+                error,
+            })?;
+
+            let token = line.split_whitespace().next().ok_or_else(|| {
+                IoError::Format(format!("Empty line {} in BPE vocabulary", idx + 1))
+            })?;
+            let token = token.strip_suffix("@@").unwrap_or(token);
+            self.alphabet.extend(token.chars());
+        }
+
+        Ok(())
+    }
+
+    /// Compare the segmentations of subword-nmt and a converted model.
+    ///
+    /// Returns the sentences for which the segmentations differ.
+    pub fn check_parity<'a>(
+        &self,
+        spp: &SentencePieceProcessor,
+        sentences: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<ParityMismatch>, SentencePieceError> {
+        let mut mismatches = Vec::new();
+        for sentence in sentences {
+            let expected = self.segment(sentence);
+            let actual = spp
+                .encode(sentence)?
+                .into_iter()
+                .map(|piece| piece.piece)
+                .collect::<Vec<_>>();
+
+            if expected != actual {
+                mismatches.push(ParityMismatch {
+                    sentence: sentence.to_owned(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Segment a sentence in the same way as subword-nmt.
+    ///
+    /// The pieces use the sentencepiece notation, so a word-final piece
+    /// ends with `▁`.
+    pub fn segment(&self, sentence: &str) -> Vec<String> {
+        let mut pieces = Vec::new();
+
+        for word in sentence.split_whitespace() {
+            let mut units = word.chars().map(String::from).collect::<Vec<_>>();
+            if self.end_of_word_suffix {
+                if let Some(last) = units.last_mut() {
+                    last.push('▁');
+                }
+            } else {
+                units.push("▁".to_string());
+            }
+
+            // Like subword-nmt, apply the best-ranked merge to all its
+            // occurrences until no merge applies.
+            while let Some(rank) = units
+                .windows(2)
+                .filter_map(|pair| self.ranks.get(&(pair[0].clone(), pair[1].clone())))
+                .min()
+            {
+                let (left, right) = &self.merges[*rank];
+                let mut merged = Vec::with_capacity(units.len());
+                let mut units_iter = units.into_iter().peekable();
+                while let Some(unit) = units_iter.next() {
+                    if unit == *left && units_iter.peek() == Some(right) {
+                        units_iter.next();
+                        merged.push(format!("{}{}", left, right));
+                    } else {
+                        merged.push(unit);
+                    }
+                }
+                units = merged;
+            }
+
+            pieces.extend(units);
+        }
+
+        pieces
+    }
+
+    /// Convert the codes to a sentencepiece BPE model builder.
+    ///
+    /// The pieces of the model are the results of the merges, in the
+    /// order of the codes, followed by the characters of the alphabet.
+    /// Since sentencepiece always starts from single characters, the
+    /// initial word-final symbols of the version 0.2 format are added as
+    /// pieces with the highest priority.
+    pub fn to_model_builder(&self) -> ModelBuilder {
+        let mut merged = Vec::new();
+        if self.end_of_word_suffix {
+            merged.extend(self.alphabet.iter().map(|ch| format!("{}▁", ch)));
+        }
+        merged.extend(
+            self.merges
+                .iter()
+                .map(|(left, right)| format!("{}{}", left, right)),
+        );
+
+        let mut seen = HashSet::new();
+        let merged = merged
+            .into_iter()
+            .filter(|piece| seen.insert(piece.clone()))
+            .collect::<Vec<_>>();
+
+        let chars = self
+            .alphabet
+            .iter()
+            .map(ToString::to_string)
+            .chain(Some("▁".to_string()))
+            .filter(|piece| seen.insert(piece.clone()))
+            .collect::<Vec<_>>();
+
+        let char_score = -(merged.len() as f32);
+        ModelBuilder::new()
+            .model_type(ModelType::Bpe)
+            .treat_whitespace_as_suffix(true)
+            .piece("<unk>", 0., PieceType::Unknown)
+            .piece("<s>", 0., PieceType::Control)
+            .piece("</s>", 0., PieceType::Control)
+            .pieces(
+                merged
+                    .into_iter()
+                    .enumerate()
+                    .map(|(rank, piece)| (piece, -(rank as f32), PieceType::Normal)),
+            )
+            .pieces(
+                chars
+                    .into_iter()
+                    .map(|piece| (piece, char_score, PieceType::Normal)),
+            )
+    }
+
+    /// Convert the codes to a sentencepiece BPE model.
+    pub fn to_processor(&self) -> Result<SentencePieceProcessor, SentencePieceError> {
+        self.to_model_builder().build()
+    }
+}
+
+/// Sentence with different segmentations in subword-nmt and sentencepiece.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParityMismatch {
+    /// The sentence.
+    pub sentence: String,
+
+    /// The segmentation of subword-nmt, in sentencepiece notation.
+    pub expected: Vec<String>,
+
+    /// The segmentation of sentencepiece.
+    pub actual: Vec<String>,
+}
+
+fn to_piece(symbol: &str) -> String {
+    match symbol.strip_suffix(END_OF_WORD) {
+        Some(prefix) => format!("{}▁", prefix),
+        None => symbol.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SubwordNmtCodes;
+
+    const CODES_V0_2: &str = "#version: 0.2\nl o\nlo w</w>\ne r</w>\nn e\nne w\n";
+
+    const CODES_V0_1: &str = "l o\nlo w\nlow </w>\ne r\ner </w>\n";
+
+    #[test]
+    fn segments_like_subword_nmt() {
+        let codes = SubwordNmtCodes::read(CODES_V0_2.as_bytes()).unwrap();
+        assert_eq!(
+            codes.segment("low lower newer"),
+            ["low▁", "lo", "w", "er▁", "new", "er▁"]
+        );
+
+        let codes = SubwordNmtCodes::read(CODES_V0_1.as_bytes()).unwrap();
+        assert_eq!(codes.segment("low lower"), ["low▁", "low", "er▁"]);
+    }
+
+    #[test]
+    fn converted_model_has_parity() {
+        let sentences = ["low lower", "lower  low", "rowel"];
+        for codes in &[CODES_V0_2, CODES_V0_1] {
+            let codes = SubwordNmtCodes::read(codes.as_bytes()).unwrap();
+            let spp = codes.to_processor().unwrap();
+            assert_eq!(
+                codes.check_parity(&spp, sentences.iter().copied()).unwrap(),
+                []
+            );
+        }
+    }
+
+    #[test]
+    fn adds_vocab_characters() {
+        let mut codes = SubwordNmtCodes::read(CODES_V0_2.as_bytes()).unwrap();
+        codes.add_vocab("lo@@ 10\nx 2\n".as_bytes()).unwrap();
+        let spp = codes.to_processor().unwrap();
+        let pieces = spp
+            .encode("lox")
+            .unwrap()
+            .into_iter()
+            .map(|piece| piece.piece)
+            .collect::<Vec<_>>();
+        assert_eq!(pieces, ["lo", "x▁"]);
+    }
+}
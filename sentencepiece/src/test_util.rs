@@ -0,0 +1,59 @@
+use crate::{ModelBuilder, PieceType, SentencePieceProcessor};
+
+/// Load the toy model of the test data.
+///
+/// The toy model is a unigram model with 1000 pieces.
+pub(crate) fn toy_model() -> SentencePieceProcessor {
+    SentencePieceProcessor::from_serialized_proto(include_bytes!("../testdata/toy.model")).unwrap()
+}
+
+/// Build a unigram model with byte fallback pieces.
+///
+/// The model has the pieces `<unk>`, `<s>`, and `</s>`, followed by the
+/// byte pieces `<0x00>` to `<0xFF>` and the normal pieces `▁` and `a`.
+/// Other characters are encoded as byte pieces.
+pub(crate) fn byte_fallback_model() -> SentencePieceProcessor {
+    ModelBuilder::new()
+        .piece("<unk>", 0.0, PieceType::Unknown)
+        .piece("<s>", 0.0, PieceType::Control)
+        .piece("</s>", 0.0, PieceType::Control)
+        .pieces((0..=255u8).map(|byte| (format!("<0x{:02X}>", byte), 0.0, PieceType::Byte)))
+        .piece("▁", -2.0, PieceType::Normal)
+        .piece("a", -3.0, PieceType::Normal)
+        .build()
+        .unwrap()
+}
+
+/// Bytes of the piece with identifier 3 of [`invalid_utf8_model`].
+pub(crate) const INVALID_UTF8_PIECE: &[u8] = b"a\xE2\x98b";
+
+/// Build a model with a piece that is not valid UTF-8.
+///
+/// Pieces are required to be valid UTF-8 when a model is built, so a
+/// placeholder piece is replaced in the serialized model.
+pub(crate) fn invalid_utf8_model() -> SentencePieceProcessor {
+    let placeholder = b"QXZJ";
+    let mut model = ModelBuilder::new()
+        .piece("<unk>", 0.0, PieceType::Unknown)
+        .piece("<s>", 0.0, PieceType::Control)
+        .piece("</s>", 0.0, PieceType::Control)
+        .piece(
+            std::str::from_utf8(placeholder).unwrap(),
+            -1.0,
+            PieceType::Normal,
+        )
+        .build()
+        .unwrap()
+        .to_serialized_proto();
+
+    let mut positions = model
+        .windows(placeholder.len())
+        .enumerate()
+        .filter(|(_, window)| window == placeholder)
+        .map(|(idx, _)| idx);
+    let idx = positions.next().unwrap();
+    assert!(positions.next().is_none());
+    model[idx..idx + placeholder.len()].copy_from_slice(INVALID_UTF8_PIECE);
+
+    SentencePieceProcessor::from_serialized_proto(&model).unwrap()
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tokenizers::{AddedToken, Model, Token, Trainer};
+
+use crate::SentencePieceProcessor;
+
+/// Adapter that uses a processor as a HuggingFace `tokenizers` model.
+///
+/// The adapter segments sequences with sentencepiece, so that a
+/// `tokenizers` pipeline produces exactly the same pieces as the
+/// processor, while the normalizers and post-processors of `tokenizers`
+/// can be used around it. Since sentencepiece normalizes the input and
+/// handles whitespace itself, the pipeline should not use a `Metaspace`
+/// pre-tokenizer.
+#[derive(Clone, Debug)]
+pub struct TokenizersModel {
+    spp: SentencePieceProcessor,
+}
+
+impl TokenizersModel {
+    /// Construct an adapter for a processor.
+    pub fn new(spp: SentencePieceProcessor) -> Self {
+        TokenizersModel { spp }
+    }
+
+    /// Get the wrapped processor.
+    pub fn processor(&self) -> &SentencePieceProcessor {
+        &self.spp
+    }
+}
+
+impl From<SentencePieceProcessor> for TokenizersModel {
+    fn from(spp: SentencePieceProcessor) -> Self {
+        TokenizersModel::new(spp)
+    }
+}
+
+impl Model for TokenizersModel {
+    type Trainer = TokenizersTrainer;
+
+    fn tokenize(&self, sequence: &str) -> tokenizers::Result<Vec<Token>> {
+        Ok(self
+            .spp
+            .encode(sequence)?
+            .into_iter()
+            .map(|piece| {
+                Token::new(
+                    piece.id,
+                    piece.piece,
+                    (piece.span.0 as usize, piece.span.1 as usize),
+                )
+            })
+            .collect())
+    }
+
+    fn token_to_id(&self, token: &str) -> Option<u32> {
+        // sentencepiece does not look up the unknown piece.
+        let unk_id = self.spp.unk_id();
+        if self.spp.id_to_piece(unk_id).ok() == Some(token) {
+            return Some(unk_id);
+        }
+
+        self.spp.piece_to_id(token).ok().flatten()
+    }
+
+    fn id_to_token(&self, id: u32) -> Option<String> {
+        self.spp.id_to_piece(id).ok().map(ToOwned::to_owned)
+    }
+
+    fn get_vocab(&self) -> HashMap<String, u32> {
+        (0..self.spp.len() as u32)
+            .filter_map(|id| Some((self.id_to_token(id)?, id)))
+            .collect()
+    }
+
+    fn get_vocab_size(&self) -> usize {
+        self.spp.len()
+    }
+
+    /// Save the model as `spiece.model`, prefixed by `prefix` if given.
+    fn save(&self, folder: &Path, prefix: Option<&str>) -> tokenizers::Result<Vec<PathBuf>> {
+        let filename = match prefix {
+            Some(prefix) => format!("{}-spiece.model", prefix),
+            None => "spiece.model".to_string(),
+        };
+        let path = folder.join(filename);
+        fs::write(&path, self.spp.to_serialized_proto())?;
+        Ok(vec![path])
+    }
+
+    fn get_trainer(&self) -> Self::Trainer {
+        TokenizersTrainer
+    }
+}
+
+/// Trainer of [`TokenizersModel`].
+///
+/// Training through `tokenizers` is not supported, use
+/// [`crate::TrainerBuilder`] to train sentencepiece models. Training
+/// returns an error.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokenizersTrainer;
+
+impl Trainer for TokenizersTrainer {
+    type Model = TokenizersModel;
+
+    fn should_show_progress(&self) -> bool {
+        false
+    }
+
+    fn train(&self, _model: &mut Self::Model) -> tokenizers::Result<Vec<AddedToken>> {
+        Err("Training is not supported, use sentencepiece::TrainerBuilder".into())
+    }
+
+    fn feed<I, S, F>(&mut self, _iterator: I, _process: F) -> tokenizers::Result<()>
+    where
+        I: Iterator<Item = S> + Send,
+        S: AsRef<str> + Send,
+        F: Fn(&str) -> tokenizers::Result<Vec<String>> + Sync,
+    {
+        Err("Training is not supported, use sentencepiece::TrainerBuilder".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use tokenizers::decoders::DecoderWrapper;
+    use tokenizers::normalizers::NormalizerWrapper;
+    use tokenizers::pre_tokenizers::PreTokenizerWrapper;
+    use tokenizers::processors::PostProcessorWrapper;
+    use tokenizers::{Model, TokenizerImpl};
+
+    use crate::test_util::toy_model;
+    use crate::{SentencePieceProcessor, TokenizersModel};
+
+    #[test]
+    fn tokenizes_like_processor() {
+        let spp = toy_model();
+        let model = TokenizersModel::new(spp.clone());
+        let sentence = "I saw a girl with a telescope.";
+
+        let tokens = model.tokenize(sentence).unwrap();
+        let pieces = spp.encode(sentence).unwrap();
+        assert_eq!(tokens.len(), pieces.len());
+        for (token, piece) in tokens.iter().zip(&pieces) {
+            assert_eq!(token.id, piece.id);
+            assert_eq!(token.value, piece.piece);
+            assert_eq!(
+                token.offsets,
+                (piece.span.0 as usize, piece.span.1 as usize)
+            );
+        }
+
+        let tokenizer: TokenizerImpl<
+            _,
+            NormalizerWrapper,
+            PreTokenizerWrapper,
+            PostProcessorWrapper,
+            DecoderWrapper,
+        > = TokenizerImpl::new(model);
+        let encoding = tokenizer.encode(sentence, false).unwrap();
+        assert_eq!(encoding.get_ids(), &*spp.encode_as_ids(sentence).unwrap());
+    }
+
+    #[test]
+    fn looks_up_vocabulary() {
+        let model = TokenizersModel::new(toy_model());
+        assert_eq!(model.get_vocab_size(), 1000);
+        assert_eq!(model.get_vocab().len(), 1000);
+        assert_eq!(model.token_to_id("<unk>"), Some(0));
+        assert_eq!(model.token_to_id("▁the"), Some(5));
+        assert_eq!(model.token_to_id("not a piece"), None);
+        assert_eq!(model.id_to_token(5).as_deref(), Some("▁the"));
+        assert_eq!(model.id_to_token(1000), None);
+    }
+
+    #[test]
+    fn saves_model() {
+        let model = TokenizersModel::new(toy_model());
+        let dir = env::temp_dir().join(format!("sentencepiece-tokenizers-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let paths = model.save(&dir, Some("toy")).unwrap();
+        assert_eq!(paths, [dir.join("toy-spiece.model")]);
+        let saved = SentencePieceProcessor::open(&paths[0]).unwrap();
+        assert_eq!(saved.len(), 1000);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use tch::Tensor;
+
+use crate::{BatchOptions, PaddedBatch, SentencePieceError, SentencePieceProcessor};
+
+impl PaddedBatch {
+    /// Get the piece identifiers and attention mask as libtorch tensors.
+    ///
+    /// The tensors have the dtype `i64` and the shape
+    /// *[batch_size, seq_len]*.
+    pub fn to_tch(&self) -> (Tensor, Tensor) {
+        (
+            self.to_tch_tensor(&self.ids),
+            self.to_tch_tensor(&self.attention_mask),
+        )
+    }
+
+    fn to_tch_tensor(&self, data: &[u32]) -> Tensor {
+        let data = data.iter().map(|&v| v as i64).collect::<Vec<_>>();
+        Tensor::from_slice(&data).reshape([self.batch_size as i64, self.seq_len as i64])
+    }
+}
+
+impl SentencePieceProcessor {
+    /// Encode a batch of sentences as libtorch tensors.
+    ///
+    /// Returns the piece identifiers and the attention mask as `i64`
+    /// tensors with the shape *[batch_size, seq_len]*. The identifiers
+    /// are padded with the padding piece of the model, or with
+    /// `fallback_pad_id` if the model does not have a padding piece.
+    pub fn encode_batch_tch(
+        &self,
+        sentences: &[impl AsRef<str>],
+        fallback_pad_id: u32,
+    ) -> Result<(Tensor, Tensor), SentencePieceError> {
+        let batch = self.encode_batch(
+            sentences,
+            &BatchOptions {
+                pad_id: Some(self.pad_id().unwrap_or(fallback_pad_id)),
+                ..Default::default()
+            },
+        )?;
+        Ok(batch.to_tch())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use tch::{Kind, Tensor};
+
+    use crate::test_util::toy_model;
+    use crate::{ModelBuilder, PieceType};
+
+    fn to_vec(tensor: &Tensor) -> Vec<i64> {
+        Vec::<i64>::try_from(tensor.reshape([-1])).unwrap()
+    }
+
+    #[test]
+    fn encodes_batch_as_tch_tensors() {
+        let model = toy_model();
+        assert_eq!(model.pad_id(), None);
+
+        let (ids, attention_mask) = model
+            .encode_batch_tch(&["I saw a girl.", "I saw"], 3)
+            .unwrap();
+
+        assert_eq!(ids.size(), [2, 5]);
+        assert_eq!(ids.kind(), Kind::Int64);
+        assert_eq!(to_vec(&ids), [8, 465, 10, 947, 4, 8, 465, 3, 3, 3]);
+        assert_eq!(attention_mask.kind(), Kind::Int64);
+        assert_eq!(to_vec(&attention_mask), [1, 1, 1, 1, 1, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn pads_tch_tensors_with_model_pad_id() {
+        let model = ModelBuilder::new()
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("<pad>", 0.0, PieceType::Control)
+            .piece("▁a", -1.0, PieceType::Normal)
+            .piece("▁b", -1.0, PieceType::Normal)
+            .build()
+            .unwrap();
+
+        let (ids, _) = model.encode_batch_tch(&["a b", "a"], 3).unwrap();
+        assert_eq!(to_vec(&ids), [2, 3, 2, 1]);
+    }
+}
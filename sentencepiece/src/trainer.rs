@@ -0,0 +1,798 @@
+use std::any::Any;
+use std::ffi::c_void;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use sentencepiece_sys::{spt_log_line, spt_train, spt_train_from_iterator};
+
+use crate::{c_error, CData, IoError, ModelType, SentencePieceError, SentencePieceProcessor};
+
+/// Builder for training sentencepiece models.
+///
+/// The options are passed to `SentencePieceTrainer::Train` as
+/// `spm_train` command-line arguments. Options that are not covered by
+/// the builder can be set with [`TrainerBuilder::option`], see the
+/// sentencepiece documentation for the available options.
+///
+/// sentencepiece writes its log messages to standard error. With the
+/// `log` feature, the messages are passed to the `log` crate instead,
+/// with the target `sentencepiece`. Standard error is redirected while
+/// training in that case. Since standard error is global, trainings are
+/// run one at a time.
+///
+/// ```no_run
+/// use sentencepiece::{ModelType, TrainerBuilder};
+///
+/// let spp = TrainerBuilder::new()
+///     .input("corpus.txt")
+///     .model_type(ModelType::Bpe)
+///     .vocab_size(8000)
+///     .train()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrainerBuilder {
+    character_coverage: Option<f32>,
+    inputs: Vec<(PathBuf, f64)>,
+    model_prefix: Option<PathBuf>,
+    model_type: ModelType,
+    options: Vec<(String, String)>,
+    vocab_size: u32,
+}
+
+impl Default for TrainerBuilder {
+    fn default() -> Self {
+        TrainerBuilder {
+            character_coverage: None,
+            inputs: Vec::new(),
+            model_prefix: None,
+            model_type: ModelType::Unigram,
+            options: Vec::new(),
+            vocab_size: 8000,
+        }
+    }
+}
+
+impl TrainerBuilder {
+    /// Construct a builder for training a unigram model.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the fraction of characters that is covered by the model.
+    ///
+    /// Default: `0.9995`
+    pub fn character_coverage(mut self, character_coverage: f32) -> Self {
+        self.character_coverage = Some(character_coverage);
+        self
+    }
+
+    /// Add an input file.
+    ///
+    /// The input files contain one sentence per line. This method can be
+    /// called multiple times to train on a corpus that is split into
+    /// several files.
+    pub fn input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.inputs.push((path.into(), 1.0));
+        self
+    }
+
+    /// Write the model to `<prefix>.model` and `<prefix>.vocab`.
+    ///
+    /// By default, the model is not written to files.
+    pub fn model_prefix(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.model_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Set the type of the model.
+    ///
+    /// The type is stored in the trained model and can be read back with
+    /// [`SentencePieceProcessor::model_type`].
+    ///
+    /// Default: `ModelType::Unigram`
+    pub fn model_type(mut self, model_type: ModelType) -> Self {
+        self.model_type = model_type;
+        self
+    }
+
+    /// Set a trainer option, e.g. `num_threads`.
+    ///
+    /// The key is the name of the `spm_train` argument without the
+    /// leading dashes.
+    pub fn option(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.options.push((key.into(), value.to_string()));
+        self
+    }
+
+    /// Train a model on the input files.
+    ///
+    /// When a model prefix is set, the model is also written to files.
+    pub fn train(&self) -> Result<SentencePieceProcessor, IoError> {
+        let prefix = match &self.model_prefix {
+            Some(prefix) => prefix,
+            None => {
+                return Ok(SentencePieceProcessor::from_serialized_proto(
+                    &self.train_to_bytes()?,
+                )?)
+            }
+        };
+
+        if self.is_weighted() {
+            self.train_sampled(
+                |trainer, sentences| trainer.train_from_iterator(sentences),
+                |_| (),
+            )
+        } else {
+            let args = self.args(true)?;
+            let status = unsafe {
+                spt_train(
+                    args.as_ptr() as *const c_char,
+                    args.len(),
+                    log_callback(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+            };
+            if status != 0 {
+                return Err(c_error(status).into());
+            }
+
+            Ok(open_trained_model(prefix)?)
+        }
+    }
+
+    /// Train a model on the given sentences.
+    ///
+    /// The input files of the builder are not used. When a model prefix
+    /// is set, the model is also written to files.
+    pub fn train_from_iterator<I>(&self, sentences: I) -> Result<SentencePieceProcessor, IoError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        match &self.model_prefix {
+            Some(prefix) => {
+                self.train_with_callback(sentences, false)?;
+                Ok(open_trained_model(prefix)?)
+            }
+            None => Ok(SentencePieceProcessor::from_serialized_proto(
+                &self.train_from_iterator_to_bytes(sentences)?,
+            )?),
+        }
+    }
+
+    /// Train a model on the given sentences and return the serialized
+    /// model.
+    ///
+    /// The input files and model prefix of the builder are not used.
+    pub fn train_from_iterator_to_bytes<I>(&self, sentences: I) -> Result<Vec<u8>, IoError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        Ok(self.train_with_callback(sentences, true)?)
+    }
+
+    /// Train a model on the input files and return the serialized model.
+    ///
+    /// The model is not written to files, regardless of the model prefix.
+    /// The serialized model can be loaded with
+    /// [`SentencePieceProcessor::from_serialized_proto`].
+    pub fn train_to_bytes(&self) -> Result<Vec<u8>, IoError> {
+        if self.is_weighted() {
+            return self.train_sampled(
+                |trainer, sentences| trainer.train_from_iterator_to_bytes(sentences),
+                |_| (),
+            );
+        }
+
+        let args = self.args(true)?;
+        let mut model = std::ptr::null_mut::<u8>();
+        let mut model_len = 0;
+        let status = unsafe {
+            spt_train(
+                args.as_ptr() as *const c_char,
+                args.len(),
+                log_callback(),
+                &mut model,
+                &mut model_len,
+            )
+        };
+
+        Ok(trained_model_bytes(status, model, model_len)?)
+    }
+
+    /// Train a model on the input files and report progress.
+    ///
+    /// `progress` is called with the number of sentences that were read
+    /// so far after reading each sentence. sentencepiece does not provide
+    /// hooks into the later stages of training, such as EM iterations,
+    /// but logs them (see the `log` feature). The verbosity of the log
+    /// can be reduced with the `minloglevel` option, e.g.
+    /// `option("minloglevel", 1)` only logs warnings and errors.
+    ///
+    /// The lines of the input files are read in Rust and passed to
+    /// sentencepiece as the training sentences. sentencepiece applies
+    /// its input options, such as `input_sentence_size`,
+    /// `shuffle_input_sentence`, and `input_format`, to these sentences
+    /// as it does to the sentences of input files. The number of
+    /// sentences that is reported includes sentences that sentencepiece
+    /// does not sample.
+    pub fn train_with_progress<F>(&self, progress: F) -> Result<SentencePieceProcessor, IoError>
+    where
+        F: FnMut(usize),
+    {
+        self.train_sampled(
+            |trainer, sentences| trainer.train_from_iterator(sentences),
+            progress,
+        )
+    }
+
+    /// Set the size of the vocabulary.
+    ///
+    /// Default: `8000`
+    pub fn vocab_size(mut self, vocab_size: u32) -> Self {
+        self.vocab_size = vocab_size;
+        self
+    }
+
+    /// Add an input file with a sampling weight.
+    ///
+    /// Sentences of the file are sampled in proportion to `weight`, e.g.
+    /// every sentence is used twice with a weight of `2.0` and every
+    /// other sentence is used with a weight of `0.5`. Sampling is
+    /// deterministic, so training on the same inputs gives the same model.
+    ///
+    /// When an input has a weight other than `1.0`, the input files are
+    /// read in Rust rather than by sentencepiece.
+    pub fn weighted_input(mut self, path: impl Into<PathBuf>, weight: f64) -> Self {
+        self.inputs.push((path.into(), weight));
+        self
+    }
+
+    fn is_weighted(&self) -> bool {
+        self.inputs.iter().any(|&(_, weight)| weight != 1.0)
+    }
+
+    /// Train on the sentences of the input files, sampled in Rust.
+    fn train_sampled<T, F>(
+        &self,
+        train: impl FnOnce(&Self, &mut dyn Iterator<Item = String>) -> Result<T, IoError>,
+        mut progress: F,
+    ) -> Result<T, IoError>
+    where
+        F: FnMut(usize),
+    {
+        if let Some((path, weight)) = self
+            .inputs
+            .iter()
+            .find(|(_, weight)| !weight.is_finite() || *weight < 0.)
+        {
+            return Err(SentencePieceError::InvalidArgument(format!(
+                "invalid weight {} for input {}",
+                weight,
+                path.display()
+            ))
+            .into());
+        }
+
+        let mut sentences = WeightedLines::new(&self.inputs);
+        let mut n_sentences = 0;
+        let trained = train(
+            self,
+            &mut sentences.by_ref().inspect(|_| {
+                n_sentences += 1;
+                progress(n_sentences);
+            }),
+        );
+
+        if let Some(error) = sentences.error {
+            return Err(error);
+        }
+
+        trained
+    }
+
+    /// Train on the sentences of an iterator.
+    ///
+    /// Returns the serialized model if `serialize` is `true`, otherwise
+    /// the model is written to the files with the model prefix.
+    fn train_with_callback<I>(
+        &self,
+        sentences: I,
+        serialize: bool,
+    ) -> Result<Vec<u8>, SentencePieceError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let args = self.args(false)?;
+
+        let mut ctx = SentenceCallback {
+            sentences: sentences.into_iter(),
+            current: None,
+            panic: None,
+        };
+
+        let mut model = std::ptr::null_mut::<u8>();
+        let mut model_len = 0;
+        let status = unsafe {
+            spt_train_from_iterator(
+                args.as_ptr() as *const c_char,
+                args.len(),
+                Some(next_sentence::<I::IntoIter>),
+                &mut ctx as *mut SentenceCallback<I::IntoIter> as *mut c_void,
+                log_callback(),
+                if serialize {
+                    &mut model
+                } else {
+                    std::ptr::null_mut()
+                },
+                &mut model_len,
+            )
+        };
+
+        if let Some(payload) = ctx.panic {
+            panic::resume_unwind(payload);
+        }
+
+        trained_model_bytes(status, model, model_len)
+    }
+
+    /// Get the trainer arguments.
+    fn args(&self, with_inputs: bool) -> Result<String, SentencePieceError> {
+        let model_type = match self.model_type {
+            ModelType::Unigram => "unigram",
+            ModelType::Bpe => "bpe",
+            ModelType::Word => "word",
+            ModelType::Char => "char",
+        };
+
+        let mut args = vec![
+            ("model_type".to_string(), model_type.to_string()),
+            ("vocab_size".to_string(), self.vocab_size.to_string()),
+        ];
+
+        if with_inputs && !self.inputs.is_empty() {
+            let inputs = self
+                .inputs
+                .iter()
+                .map(|(path, _)| path_to_arg(path))
+                .collect::<Result<Vec<_>, _>>()?;
+            args.push(("input".to_string(), inputs.join(",")));
+        }
+
+        if let Some(prefix) = &self.model_prefix {
+            args.push(("model_prefix".to_string(), path_to_arg(prefix)?));
+        }
+
+        if let Some(character_coverage) = self.character_coverage {
+            args.push((
+                "character_coverage".to_string(),
+                character_coverage.to_string(),
+            ));
+        }
+
+        args.extend(self.options.iter().cloned());
+
+        args.into_iter()
+            .map(|(key, value)| {
+                // sentencepiece splits the arguments on whitespace.
+                if key.contains(char::is_whitespace) || value.contains(char::is_whitespace) {
+                    return Err(SentencePieceError::InvalidArgument(format!(
+                        "trainer option contains whitespace: {}={}",
+                        key, value
+                    )));
+                }
+                Ok(format!("--{}={}", key, value))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|args| args.join(" "))
+    }
+}
+
+/// State of the sentence callback of the trainer.
+struct SentenceCallback<I>
+where
+    I: Iterator,
+{
+    sentences: I,
+    current: Option<I::Item>,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+unsafe extern "C" fn next_sentence<I>(
+    ctx: *mut c_void,
+    sentence: *mut *const c_char,
+    sentence_len: *mut usize,
+) -> bool
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    let ctx = &mut *(ctx as *mut SentenceCallback<I>);
+    if ctx.panic.is_some() {
+        return false;
+    }
+
+    // Panics must not unwind into sentencepiece, resume them after training.
+    match panic::catch_unwind(AssertUnwindSafe(|| ctx.sentences.next())) {
+        Ok(next) => ctx.current = next,
+        Err(payload) => {
+            ctx.current = None;
+            ctx.panic = Some(payload);
+        }
+    }
+
+    match &ctx.current {
+        Some(current) => {
+            let current = current.as_ref();
+            *sentence = current.as_ptr() as *const c_char;
+            *sentence_len = current.len();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Iterator over the sampled lines of weighted input files.
+///
+/// Line `i` of a file with weight `w` is repeated
+/// `floor((i + 1) * w) - floor(i * w)` times. The iterator ends at the
+/// first I/O error, which is stored in `error`.
+struct WeightedLines<'a> {
+    inputs: std::slice::Iter<'a, (PathBuf, f64)>,
+    current: Option<(&'a Path, f64, Lines<BufReader<File>>)>,
+    error: Option<IoError>,
+    line: String,
+    line_idx: usize,
+    repeat: usize,
+}
+
+impl<'a> WeightedLines<'a> {
+    fn new(inputs: &'a [(PathBuf, f64)]) -> Self {
+        WeightedLines {
+            inputs: inputs.iter(),
+            current: None,
+            error: None,
+            line: String::new(),
+            line_idx: 0,
+            repeat: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for WeightedLines<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.repeat > 0 {
+                self.repeat -= 1;
+                return Some(self.line.clone());
+            }
+
+            let (path, weight, lines) = match &mut self.current {
+                Some((path, weight, lines)) => (*path, *weight, lines),
+                None => {
+                    let (path, weight) = self.inputs.next()?;
+                    match File::open(path) {
+                        Ok(file) => {
+                            self.current = Some((path, *weight, BufReader::new(file).lines()));
+                            self.line_idx = 0;
+                        }
+                        Err(error) => {
+                            self.error = Some(IoError::Io {
+                                desc: format!("Cannot open input {}", path.display()),
+                                error,
+                            });
+                            return None;
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            match lines.next() {
+                Some(Ok(line)) => {
+                    let idx = self.line_idx as f64;
+                    self.repeat = (((idx + 1.) * weight).floor() - (idx * weight).floor()) as usize;
+                    self.line = line;
+                    self.line_idx += 1;
+                }
+                Some(Err(error)) => {
+                    self.error = Some(IoError::Io {
+                        desc: format!("Cannot read input {}", path.display()),
+                        error,
+                    });
+                    return None;
+                }
+                None => self.current = None,
+            }
+        }
+    }
+}
+
+/// Get the callback that receives the log lines of the trainer.
+///
+/// Without the `log` feature, there is no callback and sentencepiece
+/// writes to standard error.
+fn log_callback() -> spt_log_line {
+    #[cfg(feature = "log")]
+    {
+        Some(log_line)
+    }
+    #[cfg(not(feature = "log"))]
+    {
+        None
+    }
+}
+
+#[cfg(feature = "log")]
+unsafe extern "C" fn log_line(line: *const c_char, line_len: usize) {
+    let line = std::slice::from_raw_parts(line as *const u8, line_len);
+    let line = String::from_utf8_lossy(line);
+    let (level, message) = parse_log_line(&line);
+    // Panics must not unwind into sentencepiece.
+    let _ = panic::catch_unwind(|| log::log!(target: "sentencepiece", level, "{}", message));
+}
+
+/// Split a sentencepiece log line in its level and message.
+///
+/// sentencepiece log lines have the form `file.cc(42) LOG(INFO) message`.
+/// Other lines, such as the lines of the trainer specification that is
+/// logged at the start of training, are logged at the `Info` level.
+#[cfg(feature = "log")]
+fn parse_log_line(line: &str) -> (log::Level, &str) {
+    let (severity, message) = match line.find(") LOG(").and_then(|idx| {
+        let rest = &line[idx + ") LOG(".len()..];
+        let end = rest.find(')')?;
+        Some((&rest[..end], rest[end + 1..].trim_start()))
+    }) {
+        Some(parsed) => parsed,
+        None => return (log::Level::Info, line),
+    };
+
+    let level = match severity {
+        "WARNING" => log::Level::Warn,
+        "ERROR" | "FATAL" => log::Level::Error,
+        _ => log::Level::Info,
+    };
+
+    (level, message)
+}
+
+fn open_trained_model(prefix: &Path) -> Result<SentencePieceProcessor, SentencePieceError> {
+    let mut model_path = prefix.to_owned().into_os_string();
+    model_path.push(".model");
+    SentencePieceProcessor::open(model_path)
+}
+
+fn trained_model_bytes(
+    status: i32,
+    model: *mut u8,
+    model_len: usize,
+) -> Result<Vec<u8>, SentencePieceError> {
+    let c_model = CData {
+        data: model,
+        len: model_len,
+    };
+
+    if status != 0 {
+        return Err(c_error(status));
+    }
+
+    Ok(c_model.to_vec())
+}
+
+fn path_to_arg(path: &Path) -> Result<String, SentencePieceError> {
+    path.to_str()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| SentencePieceError::FilenameNotUtf8(path.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::WeightedLines;
+    use crate::{IoError, ModelType, SentencePieceError, SentencePieceProcessor, TrainerBuilder};
+
+    const SENTENCES: [&str; 4] = [
+        "I saw a girl with a telescope.",
+        "The girl saw me with a telescope.",
+        "I saw a telescope.",
+        "A girl with a telescope saw me.",
+    ];
+
+    fn trainer() -> TrainerBuilder {
+        TrainerBuilder::new()
+            .vocab_size(40)
+            .option("hard_vocab_limit", false)
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn parses_log_lines() {
+        assert_eq!(
+            super::parse_log_line("trainer_interface.cc(409) LOG(INFO) Loaded all 4 sentences"),
+            (log::Level::Info, "Loaded all 4 sentences")
+        );
+        assert_eq!(
+            super::parse_log_line("trainer_interface.cc(140) LOG(WARNING) Too many sentences"),
+            (log::Level::Warn, "Too many sentences")
+        );
+        assert_eq!(
+            super::parse_log_line("  model_type: UNIGRAM"),
+            (log::Level::Info, "  model_type: UNIGRAM")
+        );
+    }
+
+    #[test]
+    fn formats_trainer_arguments() {
+        let args = TrainerBuilder::new()
+            .input("a.txt")
+            .input("b.txt")
+            .model_type(ModelType::Bpe)
+            .vocab_size(1000)
+            .character_coverage(1.0)
+            .option("num_threads", 4)
+            .args(true)
+            .unwrap();
+        assert_eq!(
+            args,
+            "--model_type=bpe --vocab_size=1000 --input=a.txt,b.txt --character_coverage=1 --num_threads=4"
+        );
+
+        assert!(matches!(
+            TrainerBuilder::new().input("my corpus.txt").args(true),
+            Err(SentencePieceError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn trains_model_from_iterator() {
+        let spp = trainer().train_from_iterator(SENTENCES.iter()).unwrap();
+        assert!(spp.len() <= 40);
+
+        let ids = spp.encode_as_ids(SENTENCES[0]).unwrap();
+        assert_eq!(spp.decode_piece_ids(&ids).unwrap(), SENTENCES[0]);
+    }
+
+    #[test]
+    fn trains_all_model_types() {
+        for &model_type in &[
+            ModelType::Unigram,
+            ModelType::Bpe,
+            ModelType::Word,
+            ModelType::Char,
+        ] {
+            let spp = trainer()
+                .model_type(model_type)
+                .train_from_iterator(SENTENCES.iter())
+                .unwrap();
+            assert_eq!(spp.model_type(), model_type);
+
+            let ids = spp.encode_as_ids(SENTENCES[2]).unwrap();
+            assert_eq!(spp.decode_piece_ids(&ids).unwrap(), SENTENCES[2]);
+        }
+    }
+
+    #[test]
+    fn trains_model_to_bytes() {
+        let model = trainer()
+            .train_from_iterator_to_bytes(SENTENCES.iter())
+            .unwrap();
+        let spp = SentencePieceProcessor::from_serialized_proto(&model).unwrap();
+        assert!(spp.len() <= 40);
+
+        let input = env::temp_dir().join(format!("sentencepiece-bytes-{}.txt", process::id()));
+        fs::write(&input, SENTENCES.join("\n")).unwrap();
+        let from_file = trainer().input(&input).train_to_bytes().unwrap();
+        assert_eq!(
+            SentencePieceProcessor::from_serialized_proto(&from_file)
+                .unwrap()
+                .len(),
+            spp.len()
+        );
+
+        fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn trains_model_from_files() {
+        let dir = env::temp_dir();
+        let input = dir.join(format!("sentencepiece-train-{}.txt", process::id()));
+        let prefix = dir.join(format!("sentencepiece-train-{}", process::id()));
+        fs::write(&input, SENTENCES.join("\n")).unwrap();
+
+        let spp = trainer()
+            .input(&input)
+            .model_prefix(&prefix)
+            .model_type(ModelType::Bpe)
+            .train()
+            .unwrap();
+        assert_eq!(spp.model_type(), ModelType::Bpe);
+
+        let vocab = prefix.with_extension("vocab");
+        assert_eq!(
+            fs::read_to_string(&vocab).unwrap().lines().count(),
+            spp.len()
+        );
+
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(prefix.with_extension("model")).unwrap();
+        fs::remove_file(vocab).unwrap();
+    }
+
+    #[test]
+    fn samples_weighted_inputs() {
+        let dir = env::temp_dir();
+        let first = dir.join(format!("sentencepiece-weighted-{}-1.txt", process::id()));
+        let second = dir.join(format!("sentencepiece-weighted-{}-2.txt", process::id()));
+        fs::write(&first, "a\nb\n").unwrap();
+        fs::write(&second, "c\nd\ne\nf\n").unwrap();
+
+        let inputs = [(first.clone(), 2.0), (second.clone(), 0.5)];
+        assert_eq!(
+            WeightedLines::new(&inputs).collect::<Vec<_>>(),
+            ["a", "a", "b", "b", "d", "f"]
+        );
+
+        fs::write(&first, SENTENCES[..2].join("\n")).unwrap();
+        fs::write(&second, SENTENCES[2..].join("\n")).unwrap();
+        let spp = trainer()
+            .weighted_input(&first, 2.0)
+            .weighted_input(&second, 0.5)
+            .train()
+            .unwrap();
+        let ids = spp.encode_as_ids(SENTENCES[0]).unwrap();
+        assert_eq!(spp.decode_piece_ids(&ids).unwrap(), SENTENCES[0]);
+
+        assert!(matches!(
+            trainer().weighted_input(&first, -1.0).train(),
+            Err(IoError::SentencePiece(SentencePieceError::InvalidArgument(
+                _
+            )))
+        ));
+        assert!(matches!(
+            trainer()
+                .weighted_input(dir.join("sentencepiece-does-not-exist.txt"), 2.0)
+                .train(),
+            Err(IoError::Io { .. })
+        ));
+
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+
+    #[test]
+    fn reports_training_progress() {
+        let input = env::temp_dir().join(format!("sentencepiece-progress-{}.txt", process::id()));
+        fs::write(&input, SENTENCES.join("\n")).unwrap();
+
+        let mut progress = Vec::new();
+        let spp = trainer()
+            .input(&input)
+            .option("minloglevel", 1)
+            .train_with_progress(|n_sentences| progress.push(n_sentences))
+            .unwrap();
+        assert_eq!(progress, [1, 2, 3, 4]);
+        assert!(spp.len() <= 40);
+
+        fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "sentence iterator panicked")]
+    fn resumes_panics_of_sentence_iterator() {
+        let sentences = SENTENCES
+            .iter()
+            .map(|_| -> &'static str { panic!("sentence iterator panicked") });
+        let _ = trainer().train_from_iterator(sentences);
+    }
+}
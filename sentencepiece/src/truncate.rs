@@ -0,0 +1,230 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{PieceWithId, SentencePieceError, SentencePieceProcessor};
+
+/// Initial number of bytes that is encoded per requested piece.
+const BYTES_PER_PIECE: usize = 8;
+
+/// Encoding that is truncated to a maximum number of pieces.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct TruncatedEncoding {
+    /// The sentence pieces of the encoding.
+    pub pieces: Vec<PieceWithId>,
+
+    /// Whether pieces were removed by truncation.
+    pub truncated: bool,
+
+    /// Byte offset in the input where the encoding stopped.
+    ///
+    /// This is the length of the input when the encoding was not
+    /// truncated.
+    pub end: usize,
+}
+
+impl SentencePieceProcessor {
+    /// Encode a sentence, keeping at most `max_pieces` pieces.
+    ///
+    /// Rather than encoding the full input, growing prefixes of the
+    /// input are encoded until they contain more than `max_pieces`
+    /// pieces. Prefixes end before whitespace, so the pieces are the
+    /// same as the first pieces of the full encoding for models that
+    /// split by whitespace, which is the default. When a part of the
+    /// input does not contain whitespace, such as text in scripts that
+    /// do not separate words by whitespace, the prefix ends at a
+    /// character boundary instead and its last piece is discarded,
+    /// since that piece may continue after the prefix.
+    pub fn encode_truncated(
+        &self,
+        sentence: &str,
+        max_pieces: usize,
+    ) -> Result<TruncatedEncoding, SentencePieceError> {
+        let mut window = max_pieces.saturating_mul(BYTES_PER_PIECE).max(64);
+        let mut scanned = 0;
+
+        loop {
+            let (prefix_end, at_whitespace) = if window >= sentence.len() {
+                (sentence.len(), true)
+            } else {
+                // Only scan the part of the input that was added to the
+                // window, so that the input is scanned once.
+                let window_end = floor_char_boundary(sentence, window);
+                let whitespace = whitespace_before(sentence, scanned, window_end);
+                scanned = window_end;
+                match whitespace {
+                    Some(whitespace) => (whitespace, true),
+                    None => (window_end, false),
+                }
+            };
+
+            let mut pieces = self.encode(&sentence[..prefix_end])?;
+            if !at_whitespace {
+                pieces.pop();
+            }
+
+            if pieces.len() > max_pieces {
+                pieces.truncate(max_pieces);
+                let end = pieces
+                    .last()
+                    .map(|piece| piece.span.1 as usize)
+                    .unwrap_or(0);
+                return Ok(TruncatedEncoding {
+                    pieces,
+                    truncated: true,
+                    end,
+                });
+            }
+
+            if prefix_end == sentence.len() {
+                return Ok(TruncatedEncoding {
+                    pieces,
+                    truncated: false,
+                    end: sentence.len(),
+                });
+            }
+
+            window = window.saturating_mul(2);
+        }
+    }
+
+    /// Encode a sentence as overlapping windows of pieces.
+    ///
+    /// Every window contains at most `max_len` pieces and consecutive
+    /// windows overlap by `stride` pieces. The spans of the pieces are
+    /// offsets in the full input. An input without pieces results in a
+    /// single empty window.
+    ///
+    /// Returns `SentencePieceError::InvalidArgument` when `stride` is not
+    /// smaller than `max_len`.
+    pub fn encode_windows(
+        &self,
+        sentence: &str,
+        max_len: usize,
+        stride: usize,
+    ) -> Result<Vec<Vec<PieceWithId>>, SentencePieceError> {
+        if stride >= max_len {
+            return Err(SentencePieceError::InvalidArgument(format!(
+                "stride ({}) must be smaller than max_len ({})",
+                stride, max_len
+            )));
+        }
+
+        let pieces = self.encode(sentence)?;
+
+        let mut windows = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = (start + max_len).min(pieces.len());
+            windows.push(pieces[start..end].to_vec());
+            if end == pieces.len() {
+                return Ok(windows);
+            }
+            start = end - stride;
+        }
+    }
+}
+
+/// Find the largest character boundary at or before `offset`.
+fn floor_char_boundary(sentence: &str, mut offset: usize) -> usize {
+    while !sentence.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Find the last whitespace in `sentence[begin..end]`.
+///
+/// Whitespace at the start of the sentence is not returned, since it
+/// would give an empty prefix.
+fn whitespace_before(sentence: &str, begin: usize, end: usize) -> Option<usize> {
+    sentence[begin..end]
+        .char_indices()
+        .rev()
+        .find(|(_, ch)| ch.is_whitespace())
+        .map(|(idx, _)| begin + idx)
+        .filter(|&idx| idx > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{floor_char_boundary, whitespace_before};
+    use crate::test_util::toy_model;
+    use crate::SentencePieceError;
+
+    #[test]
+    fn truncates_long_input() {
+        let model = toy_model();
+        let sentence = "I saw a girl with a telescope. ".repeat(100);
+        let full = model.encode(&sentence).unwrap();
+
+        let truncated = model.encode_truncated(&sentence, 20).unwrap();
+        assert!(truncated.truncated);
+        assert_eq!(truncated.pieces, &full[..20]);
+        assert_eq!(truncated.end, full[19].span.1 as usize);
+    }
+
+    #[test]
+    fn truncates_long_input_without_whitespace() {
+        let model = toy_model();
+        for sentence in &[
+            "Isawagirlwithatelescope.".repeat(100),
+            "ｆｕｌｌ".repeat(200),
+        ] {
+            let full = model.encode(sentence).unwrap();
+
+            let truncated = model.encode_truncated(sentence, 20).unwrap();
+            assert!(truncated.truncated);
+            assert_eq!(truncated.pieces, &full[..20]);
+            assert_eq!(truncated.end, full[19].span.1 as usize);
+        }
+    }
+
+    #[test]
+    fn finds_whitespace_in_range() {
+        let sentence = " a b  cd";
+        assert_eq!(whitespace_before(sentence, 0, 1), None);
+        assert_eq!(whitespace_before(sentence, 0, 4), Some(3));
+        assert_eq!(whitespace_before(sentence, 4, 8), Some(5));
+        assert_eq!(whitespace_before(sentence, 6, 8), None);
+        assert_eq!(floor_char_boundary("aｆ", 2), 1);
+        assert_eq!(floor_char_boundary("aｆ", 4), 4);
+    }
+
+    #[test]
+    fn does_not_truncate_short_input() {
+        let model = toy_model();
+        let truncated = model.encode_truncated("I saw a girl.", 5).unwrap();
+        assert!(!truncated.truncated);
+        assert_eq!(truncated.pieces, model.encode("I saw a girl.").unwrap());
+        assert_eq!(truncated.end, 13);
+    }
+
+    #[test]
+    fn encodes_overlapping_windows() {
+        let model = toy_model();
+        let sentence = "I saw a girl with a telescope.";
+        let full = model.encode(sentence).unwrap();
+        assert_eq!(full.len(), 13);
+
+        let windows = model.encode_windows(sentence, 5, 2).unwrap();
+        assert_eq!(windows.len(), 4);
+        assert_eq!(windows[0], &full[0..5]);
+        assert_eq!(windows[1], &full[3..8]);
+        assert_eq!(windows[2], &full[6..11]);
+        assert_eq!(windows[3], &full[9..13]);
+
+        assert_eq!(model.encode_windows("", 5, 2).unwrap(), vec![vec![]]);
+    }
+
+    #[test]
+    fn rejects_invalid_window_arguments() {
+        let model = toy_model();
+        for &(max_len, stride) in &[(5, 5), (5, 6), (0, 0)] {
+            assert!(matches!(
+                model.encode_windows("I saw a girl.", max_len, stride),
+                Err(SentencePieceError::InvalidArgument(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    Array, ArrayRef, ListBuilder, StringArray, UInt32Builder, UInt64Array,
+};
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::cast::{as_list_array, as_string_array, as_uint32_array};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{create_udf, ScalarUDF, Volatility};
+use datafusion::physical_plan::functions::make_scalar_function;
+use datafusion::prelude::SessionContext;
+
+use crate::{SentencePieceError, SentencePieceProcessor};
+
+/// Register `sp_encode`, `sp_count_tokens`, and `sp_decode` in a session.
+///
+/// All functions share the given processor.
+pub fn register_udfs(ctx: &SessionContext, spp: Arc<SentencePieceProcessor>) {
+    ctx.register_udf(sp_count_tokens_udf(spp.clone()));
+    ctx.register_udf(sp_decode_udf(spp.clone()));
+    ctx.register_udf(sp_encode_udf(spp));
+}
+
+/// `sp_count_tokens(text)`: get the number of pieces of a string column.
+///
+/// The function returns a `UInt64` column.
+pub fn sp_count_tokens_udf(spp: Arc<SentencePieceProcessor>) -> ScalarUDF {
+    create_udf(
+        "sp_count_tokens",
+        vec![DataType::Utf8],
+        Arc::new(DataType::UInt64),
+        Volatility::Immutable,
+        make_scalar_function(move |args: &[ArrayRef]| count_tokens(&spp, args)),
+    )
+}
+
+/// `sp_decode(ids)`: decode a column of piece identifier lists.
+///
+/// The function takes a `List<UInt32>` column and returns a `Utf8`
+/// column.
+pub fn sp_decode_udf(spp: Arc<SentencePieceProcessor>) -> ScalarUDF {
+    create_udf(
+        "sp_decode",
+        vec![ids_type()],
+        Arc::new(DataType::Utf8),
+        Volatility::Immutable,
+        make_scalar_function(move |args: &[ArrayRef]| decode(&spp, args)),
+    )
+}
+
+/// `sp_encode(text)`: encode a string column as piece identifiers.
+///
+/// The function returns a `List<UInt32>` column.
+pub fn sp_encode_udf(spp: Arc<SentencePieceProcessor>) -> ScalarUDF {
+    create_udf(
+        "sp_encode",
+        vec![DataType::Utf8],
+        Arc::new(ids_type()),
+        Volatility::Immutable,
+        make_scalar_function(move |args: &[ArrayRef]| encode(&spp, args)),
+    )
+}
+
+fn count_tokens(spp: &SentencePieceProcessor, args: &[ArrayRef]) -> Result<ArrayRef> {
+    let counts = as_string_array(&args[0])?
+        .iter()
+        .map(|text| {
+            text.map(|text| Ok(spp.encode(text).map_err(external)?.len() as u64))
+                .transpose()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Arc::new(UInt64Array::from(counts)))
+}
+
+fn decode(spp: &SentencePieceProcessor, args: &[ArrayRef]) -> Result<ArrayRef> {
+    let lists = as_list_array(&args[0])?;
+
+    let mut sentences = Vec::with_capacity(lists.len());
+    for idx in 0..lists.len() {
+        if lists.is_null(idx) {
+            sentences.push(None);
+            continue;
+        }
+
+        let ids = lists.value(idx);
+        if ids.null_count() != 0 {
+            return Err(DataFusionError::Execution(
+                "sp_decode: piece identifier lists cannot contain nulls".to_string(),
+            ));
+        }
+
+        let ids = as_uint32_array(&ids)?;
+        sentences.push(Some(spp.decode_piece_ids(ids.values()).map_err(external)?));
+    }
+
+    Ok(Arc::new(StringArray::from(sentences)))
+}
+
+fn encode(spp: &SentencePieceProcessor, args: &[ArrayRef]) -> Result<ArrayRef> {
+    let texts = as_string_array(&args[0])?;
+
+    let mut builder = ListBuilder::with_capacity(UInt32Builder::new(), texts.len());
+    for text in texts.iter() {
+        match text {
+            Some(text) => {
+                let pieces = spp.encode(text).map_err(external)?;
+                builder
+                    .values()
+                    .extend(pieces.into_iter().map(|piece| Some(piece.id)));
+                builder.append(true);
+            }
+            None => builder.append(false),
+        }
+    }
+
+    Ok(Arc::new(builder.finish()))
+}
+
+fn external(err: SentencePieceError) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+fn ids_type() -> DataType {
+    DataType::List(Arc::new(Field::new("item", DataType::UInt32, true)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{
+        Array, ArrayRef, ListBuilder, StringArray, UInt32Builder, UInt64Array,
+    };
+    use datafusion::common::cast::{as_list_array, as_string_array, as_uint32_array};
+
+    use super::{count_tokens, decode, encode};
+    use crate::test_util::toy_model;
+
+    #[test]
+    fn encodes_and_counts_string_column() {
+        let model = toy_model();
+        let texts: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("I saw a girl with a telescope."),
+            None,
+        ]));
+
+        let encoded = encode(&model, &[texts.clone()]).unwrap();
+        let lists = as_list_array(&encoded).unwrap();
+        assert!(lists.is_null(1));
+        assert_eq!(
+            as_uint32_array(&lists.value(0)).unwrap().values().as_ref(),
+            &[8, 465, 10, 947, 41, 10, 170, 168, 110, 28, 20, 143, 4]
+        );
+
+        let counts = count_tokens(&model, &[texts]).unwrap();
+        let counts = counts.as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(counts.value(0), 13);
+        assert!(counts.is_null(1));
+    }
+
+    #[test]
+    fn decodes_id_column() {
+        let model = toy_model();
+
+        let mut builder = ListBuilder::new(UInt32Builder::new());
+        builder.values().append_slice(&[8, 465, 10, 947, 4]);
+        builder.append(true);
+        builder.append(false);
+        let ids: ArrayRef = Arc::new(builder.finish());
+
+        let decoded = decode(&model, &[ids]).unwrap();
+        let decoded = as_string_array(&decoded).unwrap();
+        assert_eq!(decoded.value(0), "I saw a girl.");
+        assert!(decoded.is_null(1));
+    }
+}
@@ -0,0 +1,246 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "unicode-segmentation")]
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{PieceWithId, SentencePieceError, SentencePieceProcessor};
+
+/// Encoded sentence with the input spans that the model did not cover.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct CheckedEncoding {
+    /// The sentence pieces of the encoded sentence.
+    pub pieces: Vec<PieceWithId>,
+
+    /// Spans of the input that were encoded as the unknown piece or as
+    /// byte fallback pieces.
+    ///
+    /// The spans are encoded as the byte offsets *[begin, end)*.
+    /// Adjacent spans are merged.
+    pub unknown_spans: Vec<(u32, u32)>,
+}
+
+impl SentencePieceProcessor {
+    /// Encode a sentence and report the spans that were not covered.
+    ///
+    /// In addition to the pieces, the spans of the input that were
+    /// encoded as the unknown piece or as byte fallback pieces are
+    /// returned.
+    pub fn encode_checked(&self, sentence: &str) -> Result<CheckedEncoding, SentencePieceError> {
+        let pieces = self.encode(sentence)?;
+        let unk_id = self.unk_id();
+
+        let mut unknown_spans: Vec<(u32, u32)> = Vec::new();
+        for piece in &pieces {
+            let (begin, end) = piece.span;
+            if begin == end || (piece.id != unk_id && !self.is_byte(piece.id)) {
+                continue;
+            }
+
+            match unknown_spans.last_mut() {
+                Some(last) if last.1 >= begin => last.1 = last.1.max(end),
+                _ => unknown_spans.push((begin, end)),
+            }
+        }
+
+        Ok(CheckedEncoding {
+            pieces,
+            unknown_spans,
+        })
+    }
+}
+
+impl SentencePieceProcessor {
+    /// Get the identifier of the byte fallback piece of a byte.
+    ///
+    /// Returns `None` when the model does not have a piece for the byte,
+    /// e.g. when the model was trained without byte fallback.
+    pub fn byte_to_id(&self, byte: u8) -> Option<u32> {
+        self.piece_to_id(&format!("<0x{:02X}>", byte))
+            .expect("Byte piece does not contain nul")
+            .filter(|&id| self.is_byte(id))
+    }
+
+    /// Get the byte of a byte fallback piece.
+    ///
+    /// Returns `None` when the piece is not a byte fallback piece.
+    pub fn id_to_byte(&self, id: u32) -> Option<u8> {
+        if !self.is_byte(id) {
+            return None;
+        }
+
+        let piece = std::str::from_utf8(self.piece_bytes(id)?).ok()?;
+        let hex = piece.strip_prefix("<0x")?.strip_suffix('>')?;
+        u8::from_str_radix(hex, 16).ok()
+    }
+}
+
+pub(crate) fn is_unknown(piece: &PieceWithId, unk_id: u32) -> bool {
+    piece.id == unk_id && piece.span.0 < piece.span.1
+}
+
+#[cfg(feature = "unicode-segmentation")]
+pub(crate) fn split_unknown_graphemes(
+    sentence: &str,
+    unk_id: u32,
+    pieces: Vec<PieceWithId>,
+) -> Vec<PieceWithId> {
+    let mut boundaries = sentence
+        .grapheme_indices(true)
+        .map(|(idx, _)| idx as u32)
+        .collect::<Vec<_>>();
+    boundaries.push(sentence.len() as u32);
+
+    // Extend unknown spans to grapheme cluster boundaries, merging
+    // spans that overlap after extension.
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for piece in pieces.iter().filter(|piece| is_unknown(piece, unk_id)) {
+        let (begin, end) = piece.span;
+        let begin = boundaries[boundaries.partition_point(|&b| b <= begin) - 1];
+        let end = boundaries[boundaries
+            .partition_point(|&b| b < end)
+            .min(boundaries.len() - 1)];
+        match ranges.last_mut() {
+            Some(last) if last.1 >= begin => last.1 = last.1.max(end),
+            _ => ranges.push((begin, end)),
+        }
+    }
+
+    let mut split = Vec::with_capacity(pieces.len());
+    let mut next_range = 0;
+    for mut piece in pieces {
+        if is_unknown(&piece, unk_id) {
+            // Emit the graphemes of the range that contains the piece,
+            // unless they were emitted for a preceding piece.
+            while next_range < ranges.len() && ranges[next_range].0 <= piece.span.0 {
+                let (begin, end) = ranges[next_range];
+                split.extend(
+                    sentence[begin as usize..end as usize]
+                        .grapheme_indices(true)
+                        .map(|(idx, grapheme)| {
+                            let grapheme_begin = begin + idx as u32;
+                            PieceWithId {
+                                piece: grapheme.to_owned(),
+                                id: unk_id,
+                                span: (grapheme_begin, grapheme_begin + grapheme.len() as u32),
+                            }
+                        }),
+                );
+                next_range += 1;
+            }
+            continue;
+        }
+
+        // Trim the spans of pieces that overlap with unknown ranges.
+        let (mut begin, mut end) = piece.span;
+        if begin < end {
+            for &(range_begin, range_end) in &ranges {
+                if range_begin <= begin && begin < range_end {
+                    begin = range_end.min(end);
+                }
+                if begin < range_end && range_begin < end {
+                    end = range_begin.max(begin);
+                }
+            }
+
+            // Drop pieces that are covered by an unknown range.
+            if begin == end {
+                continue;
+            }
+        }
+
+        piece.span = (begin, end);
+        split.push(piece);
+    }
+
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{byte_fallback_model, toy_model};
+    use crate::EncodeOptions;
+
+    #[test]
+    fn reports_unknown_spans() {
+        let model = toy_model();
+        let checked = model.encode_checked("I saw a ☃☃.").unwrap();
+        assert_eq!(checked.pieces, model.encode("I saw a ☃☃.").unwrap());
+        assert_eq!(checked.unknown_spans, [(8, 14)]);
+
+        assert_eq!(
+            model.encode_checked("I saw a girl.").unwrap().unknown_spans,
+            []
+        );
+    }
+
+    #[test]
+    fn reports_byte_fallback_spans() {
+        let model = byte_fallback_model();
+        let checked = model.encode_checked("a☃a").unwrap();
+        assert_eq!(checked.unknown_spans, [(1, 4)]);
+    }
+
+    #[test]
+    fn maps_bytes_to_byte_pieces() {
+        let model = byte_fallback_model();
+
+        for byte in 0..=255u8 {
+            let id = model.byte_to_id(byte).unwrap();
+            assert_eq!(id, 3 + byte as u32);
+            assert_eq!(model.id_to_byte(id), Some(byte));
+        }
+        assert_eq!(model.id_to_byte(0), None);
+        assert_eq!(model.id_to_byte(260), None);
+
+        let model = toy_model();
+        assert_eq!(model.byte_to_id(b'a'), None);
+        assert_eq!(model.id_to_byte(5), None);
+    }
+
+    #[cfg(not(feature = "unicode-segmentation"))]
+    #[test]
+    fn rejects_grapheme_option_without_feature() {
+        let options = EncodeOptions {
+            split_unknown_graphemes: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            toy_model().encode_with_options("I saw a ☃.", &options),
+            Err(crate::SentencePieceError::InvalidArgument(_))
+        ));
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn splits_unknown_pieces_into_graphemes() {
+        let model = toy_model();
+        let options = EncodeOptions {
+            split_unknown_graphemes: true,
+            ..Default::default()
+        };
+        let sentence = "I saw a ☃👍🏽.";
+        let pieces = model.encode_with_options(sentence, &options).unwrap();
+
+        let unknown = pieces
+            .iter()
+            .filter(|piece| piece.id == model.unk_id())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            unknown
+                .iter()
+                .map(|piece| piece.piece.as_str())
+                .collect::<Vec<_>>(),
+            ["☃", "👍🏽"]
+        );
+        assert_eq!(
+            unknown.iter().map(|piece| piece.span).collect::<Vec<_>>(),
+            [(8, 11), (11, 19)]
+        );
+
+        // Spans of the pieces do not overlap.
+        assert!(pieces
+            .windows(2)
+            .all(|pair| pair[0].span.1 <= pair[1].span.0));
+    }
+}
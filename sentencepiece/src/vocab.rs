@@ -0,0 +1,617 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use prost_derive::Message;
+
+use crate::model::model_proto::sentence_piece::Type;
+use crate::{IoError, PieceType, SentencePieceProcessor};
+
+/// Piece in a vocabulary.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct VocabPiece {
+    /// The piece as a string.
+    pub piece: String,
+
+    /// The score of the piece.
+    pub score: f32,
+
+    /// The type of the piece.
+    pub piece_type: PieceType,
+}
+
+/// Snapshot of the vocabulary of a model.
+///
+/// The snapshot is decoupled from the model protobuf, so that it can be
+/// stored and compared without depending on the protobuf types.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Vocab {
+    /// The pieces of the vocabulary, indexed by their identifiers.
+    pub pieces: Vec<VocabPiece>,
+
+    /// Identifier of the unknown piece.
+    pub unk_id: u32,
+
+    /// Identifier of the beginning of sentence piece.
+    pub bos_id: Option<u32>,
+
+    /// Identifier of the end of sentence piece.
+    pub eos_id: Option<u32>,
+
+    /// Identifier of the padding piece.
+    pub pad_id: Option<u32>,
+}
+
+impl Vocab {
+    /// Compare this vocabulary to a newer vocabulary.
+    ///
+    /// Pieces are matched by their strings.
+    pub fn diff(&self, new: &Vocab) -> VocabDiff {
+        let new_ids = piece_ids(new);
+        let old_ids = piece_ids(self);
+
+        let mut diff = VocabDiff {
+            added: new
+                .pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, piece)| !old_ids.contains_key(piece.piece.as_str()))
+                .map(|(id, piece)| (id as u32, piece.piece.clone()))
+                .collect(),
+            ..Default::default()
+        };
+
+        for (old_id, old_piece) in self.pieces.iter().enumerate() {
+            let old_id = old_id as u32;
+            let piece = &old_piece.piece;
+
+            let new_id = match new_ids.get(piece.as_str()) {
+                Some(&new_id) => new_id,
+                None => {
+                    diff.removed.push((old_id, piece.clone()));
+                    continue;
+                }
+            };
+
+            if old_id != new_id {
+                diff.id_changes.push(IdChange {
+                    piece: piece.clone(),
+                    old_id,
+                    new_id,
+                });
+            }
+
+            let new_piece = &new.pieces[new_id as usize];
+            if old_piece.score != new_piece.score {
+                diff.score_changes.push(ScoreChange {
+                    piece: piece.clone(),
+                    old_score: old_piece.score,
+                    new_score: new_piece.score,
+                });
+            }
+
+            if old_piece.piece_type != new_piece.piece_type {
+                diff.type_changes.push(TypeChange {
+                    piece: piece.clone(),
+                    old_type: old_piece.piece_type,
+                    new_type: new_piece.piece_type,
+                });
+            }
+        }
+
+        let special_ids = [
+            (SpecialPiece::Unknown, Some(self.unk_id), Some(new.unk_id)),
+            (SpecialPiece::Bos, self.bos_id, new.bos_id),
+            (SpecialPiece::Eos, self.eos_id, new.eos_id),
+            (SpecialPiece::Pad, self.pad_id, new.pad_id),
+        ];
+        diff.special_id_changes = special_ids
+            .iter()
+            .filter(|(_, old_id, new_id)| old_id != new_id)
+            .map(|&(special, old_id, new_id)| SpecialIdChange {
+                special,
+                old_id,
+                new_id,
+            })
+            .collect();
+
+        diff
+    }
+
+    /// Returns `true` if the vocabulary does not contain any pieces.
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    /// Get the number of pieces in the vocabulary.
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+}
+
+/// Special piece of a vocabulary.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum SpecialPiece {
+    /// Unknown piece.
+    Unknown,
+
+    /// Beginning of sentence piece.
+    Bos,
+
+    /// End of sentence piece.
+    Eos,
+
+    /// Padding piece.
+    Pad,
+}
+
+/// Piece with a different identifier in the new vocabulary.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct IdChange {
+    /// The piece as a string.
+    pub piece: String,
+
+    /// Identifier in the old vocabulary.
+    pub old_id: u32,
+
+    /// Identifier in the new vocabulary.
+    pub new_id: u32,
+}
+
+/// Piece with a different score in the new vocabulary.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ScoreChange {
+    /// The piece as a string.
+    pub piece: String,
+
+    /// Score in the old vocabulary.
+    pub old_score: f32,
+
+    /// Score in the new vocabulary.
+    pub new_score: f32,
+}
+
+impl ScoreChange {
+    /// Get the difference between the new and the old score.
+    pub fn delta(&self) -> f32 {
+        self.new_score - self.old_score
+    }
+}
+
+/// Special piece with a different identifier in the new vocabulary.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct SpecialIdChange {
+    /// The special piece.
+    pub special: SpecialPiece,
+
+    /// Identifier in the old vocabulary.
+    pub old_id: Option<u32>,
+
+    /// Identifier in the new vocabulary.
+    pub new_id: Option<u32>,
+}
+
+/// Piece with a different type in the new vocabulary.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct TypeChange {
+    /// The piece as a string.
+    pub piece: String,
+
+    /// Type in the old vocabulary.
+    pub old_type: PieceType,
+
+    /// Type in the new vocabulary.
+    pub new_type: PieceType,
+}
+
+/// Differences between two vocabularies.
+///
+/// Constructed using [`Vocab::diff`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct VocabDiff {
+    /// Pieces that are only in the new vocabulary, with their new identifiers.
+    pub added: Vec<(u32, String)>,
+
+    /// Pieces that are only in the old vocabulary, with their old identifiers.
+    pub removed: Vec<(u32, String)>,
+
+    /// Pieces with a different identifier.
+    pub id_changes: Vec<IdChange>,
+
+    /// Pieces with a different score.
+    pub score_changes: Vec<ScoreChange>,
+
+    /// Pieces with a different type.
+    pub type_changes: Vec<TypeChange>,
+
+    /// Special pieces with a different identifier.
+    pub special_id_changes: Vec<SpecialIdChange>,
+}
+
+impl VocabDiff {
+    /// Returns `true` if identifiers of the old vocabulary are valid in the new vocabulary.
+    ///
+    /// This is the case when no pieces were removed or moved and the
+    /// special pieces are unchanged, so that identifier sequences of the
+    /// old vocabulary decode identically with the new vocabulary. Note
+    /// that the new model may still segment text differently, e.g. due
+    /// to added pieces or score changes.
+    pub fn is_id_compatible(&self) -> bool {
+        self.removed.is_empty() && self.id_changes.is_empty() && self.special_id_changes.is_empty()
+    }
+
+    /// Returns `true` if the vocabularies are identical.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.score_changes.is_empty()
+            && self.type_changes.is_empty()
+            && self.is_id_compatible()
+    }
+}
+
+/// Number of pieces of each type in a model.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct PieceTypeCounts {
+    /// Number of normal pieces.
+    pub normal: usize,
+
+    /// Number of unknown pieces.
+    pub unknown: usize,
+
+    /// Number of control pieces.
+    pub control: usize,
+
+    /// Number of user-defined pieces.
+    pub user_defined: usize,
+
+    /// Number of byte pieces.
+    pub byte: usize,
+
+    /// Number of unused pieces.
+    pub unused: usize,
+}
+
+/// Model message that only declares the pieces.
+///
+/// sentencepiece does not require pieces to be valid UTF-8, so pieces
+/// are decoded as bytes.
+#[derive(Clone, PartialEq, Message)]
+struct PiecesProto {
+    #[prost(message, repeated, tag = "1")]
+    pieces: Vec<RawPiece>,
+}
+
+/// Sentence piece with the piece as bytes.
+#[derive(Clone, PartialEq, Message)]
+struct RawPiece {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    piece: Option<Vec<u8>>,
+
+    #[prost(float, optional, tag = "2")]
+    score: Option<f32>,
+
+    #[prost(enumeration = "Type", optional, tag = "3", default = "Normal")]
+    r#type: Option<i32>,
+}
+
+fn piece_ids(vocab: &Vocab) -> HashMap<&str, u32> {
+    let mut ids = HashMap::with_capacity(vocab.len());
+    for (id, piece) in vocab.pieces.iter().enumerate() {
+        ids.entry(piece.piece.as_str()).or_insert(id as u32);
+    }
+    ids
+}
+
+impl SentencePieceProcessor {
+    /// Count the pieces of each type.
+    pub fn piece_type_counts(&self) -> PieceTypeCounts {
+        let mut counts = PieceTypeCounts::default();
+        for piece in self.raw_pieces() {
+            let count = match piece.r#type().into() {
+                PieceType::Normal => &mut counts.normal,
+                PieceType::Unknown => &mut counts.unknown,
+                PieceType::Control => &mut counts.control,
+                PieceType::UserDefined => &mut counts.user_defined,
+                PieceType::Byte => &mut counts.byte,
+                PieceType::Unused => &mut counts.unused,
+            };
+            *count += 1;
+        }
+        counts
+    }
+
+    /// Iterate over the pieces of the vocabulary with their identifiers.
+    ///
+    /// The pieces are ordered by identifier. Pieces that are not valid
+    /// UTF-8 are converted lossily, use
+    /// [`SentencePieceProcessor::piece_bytes`] to get their bytes.
+    pub fn pieces(&self) -> impl Iterator<Item = (u32, VocabPiece)> {
+        self.raw_pieces()
+            .into_iter()
+            .enumerate()
+            .map(|(id, piece)| {
+                (
+                    id as u32,
+                    VocabPiece {
+                        piece_type: piece.r#type().into(),
+                        piece: String::from_utf8_lossy(piece.piece()).into_owned(),
+                        score: piece.score(),
+                    },
+                )
+            })
+    }
+
+    /// Get the pieces of the model, without requiring them to be UTF-8.
+    fn raw_pieces(&self) -> Vec<RawPiece> {
+        let proto: PiecesProto = prost::Message::decode(self.to_serialized_proto().as_slice())
+            .expect("Received invalid model protobuf from sentencepiece");
+        proto.pieces
+    }
+
+    /// Get the vocabulary in the `.vocab` format of `spm_train`.
+    ///
+    /// See [`SentencePieceProcessor::write_vocab`] for the format.
+    pub fn to_vocab_string(&self) -> String {
+        let mut vocab = Vec::new();
+        self.write_vocab_pieces(&mut vocab)
+            .expect("Writing to a vector cannot fail");
+        String::from_utf8(vocab).expect("Vocabulary is not UTF-8, please report this bug.")
+    }
+
+    /// Write the vocabulary in the `.vocab` format of `spm_train`.
+    ///
+    /// Every line contains a piece and its score, separated by a tab.
+    /// Scores are formatted with six significant digits, like
+    /// `spm_train` does.
+    pub fn write_vocab(&self, mut writer: impl Write) -> Result<(), IoError> {
+        self.write_vocab_pieces(&mut writer)
+            .map_err(|error| IoError::Io {
+                desc: "Cannot write vocabulary".to_string(),
+                error,
+            })
+    }
+
+    fn write_vocab_pieces(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (_, piece) in self.pieces() {
+            writeln!(writer, "{}\t{}", piece.piece, format_score(piece.score))?;
+        }
+        writer.flush()
+    }
+
+    /// Get the control pieces with their identifiers.
+    ///
+    /// Control pieces, such as `<s>` and `</s>`, are never produced by
+    /// encoding the input.
+    pub fn control_pieces(&self) -> Vec<(u32, String)> {
+        self.pieces_of_type(PieceType::Control)
+    }
+
+    /// Get the user-defined symbols with their identifiers.
+    ///
+    /// User-defined symbols, such as `<mask>` or language tags, are
+    /// always encoded as a single piece.
+    pub fn user_defined_symbols(&self) -> Vec<(u32, String)> {
+        self.pieces_of_type(PieceType::UserDefined)
+    }
+
+    fn pieces_of_type(&self, piece_type: PieceType) -> Vec<(u32, String)> {
+        self.pieces()
+            .filter(|(_, piece)| piece.piece_type == piece_type)
+            .map(|(id, piece)| (id, piece.piece))
+            .collect()
+    }
+
+    /// Get a snapshot of the vocabulary.
+    pub fn vocab(&self) -> Vocab {
+        let pieces = self.pieces().map(|(_, piece)| piece).collect();
+
+        Vocab {
+            pieces,
+            unk_id: self.unk_id(),
+            bos_id: self.bos_id(),
+            eos_id: self.eos_id(),
+            pad_id: self.pad_id(),
+        }
+    }
+}
+
+/// Format a score with six significant digits, like `%g`.
+fn format_score(score: f32) -> String {
+    if score == 0. || !score.is_finite() {
+        return score.to_string();
+    }
+
+    let scientific = format!("{:.5e}", score);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Scientific notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("Exponent is an integer");
+
+    if !(-4..6).contains(&exponent) {
+        let mantissa = trim_fraction(mantissa);
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    } else {
+        let precision = (5 - exponent) as usize;
+        trim_fraction(&format!("{:.*}", precision, score)).to_string()
+    }
+}
+
+/// Remove trailing zeros of the fractional part of a number.
+fn trim_fraction(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_score;
+    use crate::test_util::{invalid_utf8_model, toy_model};
+    use crate::{IdChange, ModelBuilder, PieceType, PieceTypeCounts, SpecialPiece, VocabPiece};
+
+    #[test]
+    fn iterates_over_pieces() {
+        let model = toy_model();
+        let pieces = model.pieces().collect::<Vec<_>>();
+        assert_eq!(pieces.len(), 1000);
+        assert!(pieces
+            .iter()
+            .enumerate()
+            .all(|(idx, (id, _))| idx as u32 == *id));
+        assert_eq!(pieces[0].1.piece, "<unk>");
+        assert_eq!(pieces[0].1.piece_type, PieceType::Unknown);
+        assert_eq!(pieces[5].1.piece, "▁the");
+    }
+
+    #[test]
+    fn converts_invalid_utf8_pieces_lossily() {
+        let model = invalid_utf8_model();
+        let pieces = model.pieces().collect::<Vec<_>>();
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces[3].1.piece, "a\u{FFFD}b");
+        assert_eq!(pieces[3].1.score, -1.0);
+        assert_eq!(pieces[3].1.piece_type, PieceType::Normal);
+
+        assert_eq!(model.vocab().len(), 4);
+        assert_eq!(model.piece_type_counts().normal, 1);
+        assert_eq!(model.control_pieces().len(), 2);
+        assert!(model.user_defined_symbols().is_empty());
+        assert_eq!(
+            model.to_vocab_string().lines().nth(3),
+            Some("a\u{FFFD}b\t-1")
+        );
+        assert!(model.model_proto().is_err());
+    }
+
+    #[test]
+    fn writes_vocab_in_spm_train_format() {
+        let vocab = toy_model().to_vocab_string();
+        let lines = vocab.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1000);
+        assert_eq!(lines[..3], ["<unk>\t0", "<s>\t0", "</s>\t0"]);
+        assert_eq!(lines[5], "▁the\t-3.56031");
+
+        let mut written = Vec::new();
+        toy_model().write_vocab(&mut written).unwrap();
+        assert_eq!(written, vocab.as_bytes());
+    }
+
+    #[test]
+    fn formats_scores_like_printf() {
+        assert_eq!(format_score(0.), "0");
+        assert_eq!(format_score(-5.), "-5");
+        assert_eq!(format_score(-3.5603127), "-3.56031");
+        assert_eq!(format_score(-12.25), "-12.25");
+        assert_eq!(format_score(0.0001), "0.0001");
+        assert_eq!(format_score(0.00001), "1e-05");
+        assert_eq!(format_score(-1234567.), "-1.23457e+06");
+    }
+
+    #[test]
+    fn queries_special_pieces() {
+        let model = toy_model();
+        assert_eq!(
+            model.control_pieces(),
+            [(1, "<s>".to_string()), (2, "</s>".to_string())]
+        );
+        assert!(model.user_defined_symbols().is_empty());
+
+        let model = ModelBuilder::new()
+            .piece("<unk>", 0.0, PieceType::Unknown)
+            .piece("<s>", 0.0, PieceType::Control)
+            .piece("</s>", 0.0, PieceType::Control)
+            .piece("<mask>", 0.0, PieceType::UserDefined)
+            .piece("▁", -2.0, PieceType::Normal)
+            .piece("a", -3.0, PieceType::Normal)
+            .build()
+            .unwrap();
+        assert_eq!(model.user_defined_symbols(), [(3, "<mask>".to_string())]);
+    }
+
+    #[test]
+    fn extracts_vocab_from_model() {
+        let vocab = toy_model().vocab();
+        assert_eq!(vocab.len(), 1000);
+        assert_eq!(vocab.unk_id, 0);
+        assert_eq!(vocab.bos_id, Some(1));
+        assert_eq!(vocab.eos_id, Some(2));
+        assert_eq!(vocab.pad_id, None);
+
+        assert_eq!(vocab.pieces[0].piece, "<unk>");
+        assert_eq!(vocab.pieces[0].piece_type, PieceType::Unknown);
+        assert_eq!(vocab.pieces[1].piece_type, PieceType::Control);
+        assert_eq!(vocab.pieces[5].piece, "▁the");
+        assert_eq!(vocab.pieces[5].piece_type, PieceType::Normal);
+        assert!((vocab.pieces[5].score - -3.5603127).abs() < 1e-6);
+    }
+
+    #[test]
+    fn counts_piece_types() {
+        assert_eq!(
+            toy_model().piece_type_counts(),
+            PieceTypeCounts {
+                normal: 997,
+                unknown: 1,
+                control: 2,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diffs_vocabs() {
+        let old = toy_model().vocab();
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = old.clone();
+        new.pieces.swap(5, 6);
+        new.pieces[7].score += 1.;
+        new.pieces.pop();
+        new.pieces.push(VocabPiece {
+            piece: "▁telescope".to_string(),
+            score: -10.,
+            piece_type: PieceType::UserDefined,
+        });
+        new.pad_id = Some(999);
+
+        let removed = old.pieces[999].piece.clone();
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, [(999, "▁telescope".to_string())]);
+        assert_eq!(diff.removed, [(999, removed)]);
+        assert_eq!(
+            diff.id_changes,
+            [
+                IdChange {
+                    piece: old.pieces[5].piece.clone(),
+                    old_id: 5,
+                    new_id: 6
+                },
+                IdChange {
+                    piece: old.pieces[6].piece.clone(),
+                    old_id: 6,
+                    new_id: 5
+                }
+            ]
+        );
+        assert_eq!(diff.score_changes.len(), 1);
+        assert!((diff.score_changes[0].delta() - 1.).abs() < 1e-6);
+        assert!(diff.type_changes.is_empty());
+        assert_eq!(diff.special_id_changes.len(), 1);
+        assert_eq!(diff.special_id_changes[0].special, SpecialPiece::Pad);
+        assert!(!diff.is_id_compatible());
+    }
+}